@@ -1,13 +1,11 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 use evm::backend::{MemoryAccount, MemoryBackend, MemoryVicinity};
-use evm::executor::{MemoryStackState, StackExecutor, StackSubstateMetadata};
+use evm::executor::stack::{MemoryStackState, StackExecutor, StackSubstateMetadata};
 use evm::Config;
 use primitive_types::{H160, U256};
 use std::{collections::BTreeMap, str::FromStr};
 
-fn run_loop_contract() {
-	let config = Config::istanbul();
-
+fn run_loop_contract(config: &Config) {
 	let vicinity = MemoryVicinity {
 		gas_price: U256::zero(),
 		origin: H160::default(),
@@ -28,7 +26,7 @@ fn run_loop_contract() {
 			nonce: U256::one(),
 			balance: U256::from(10000000),
 			storage: BTreeMap::new(),
-			code: hex::decode("6080604052348015600f57600080fd5b506004361060285760003560e01c80630f14a40614602d575b600080fd5b605660048036036020811015604157600080fd5b8101908080359060200190929190505050606c565b6040518082815260200191505060405180910390f35b6000806000905060005b83811015608f5760018201915080806001019150506076565b508091505091905056fea26469706673582212202bc9ec597249a9700278fe4ce78da83273cb236e76d4d6797b441454784f901d64736f6c63430007040033").unwrap(),
+			code: hex::decode("6080604052348015600f57600080fd5b506004361060285760003560e01c80630f14a40614602d575b600080fd5b605660048036036020811015604157600080fd5b8101908080359060200190929190505050606c565b6040518082815260200191505060405180910390f35b6000806000905060005b83811015608f5760018201915080806001019150506076565b508091505091905056fea26469706673582212202bc9ec597249a9700278fe4ce78da83273cb236e76d4d6797b441454784f901d64736f6c63430007040033").unwrap().into(),
 		}
 	);
 	state.insert(
@@ -37,15 +35,15 @@ fn run_loop_contract() {
 			nonce: U256::one(),
 			balance: U256::from(10000000),
 			storage: BTreeMap::new(),
-			code: Vec::new(),
+			code: Default::default(),
 		},
 	);
 
 	let backend = MemoryBackend::new(&vicinity, state);
-	let metadata = StackSubstateMetadata::new(u64::MAX, &config);
+	let metadata = StackSubstateMetadata::new(u64::MAX, config);
 	let state = MemoryStackState::new(metadata, &backend);
 	let precompiles = BTreeMap::new();
-	let mut executor = StackExecutor::new_with_precompiles(state, &config, &precompiles);
+	let mut executor = StackExecutor::new_with_precompiles(state, config, &precompiles);
 
 	let _reason = executor.transact_call(
 		H160::from_str("0xf000000000000000000000000000000000000000").unwrap(),
@@ -60,7 +58,16 @@ fn run_loop_contract() {
 }
 
 fn criterion_benchmark(c: &mut Criterion) {
-	c.bench_function("loop contract", |b| b.iter(|| run_loop_contract()));
+	let config = Config::istanbul();
+	c.bench_function("loop contract", |b| b.iter(|| run_loop_contract(&config)));
+
+	let config = Config {
+		precharge_block_gas: true,
+		..Config::istanbul()
+	};
+	c.bench_function("loop contract, precharged block gas", |b| {
+		b.iter(|| run_loop_contract(&config))
+	});
 }
 
 criterion_group!(benches, criterion_benchmark);
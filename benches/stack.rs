@@ -0,0 +1,32 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use evm::{Capture, ExitReason, ExitSucceed, Machine};
+use std::sync::Arc;
+
+/// Code counting down from 0x100000, with arithmetic and stack operations
+/// on each iteration:
+///
+/// ```text
+/// 0x00 PUSH3 0x100000
+/// 0x04 JUMPDEST
+///      DUP1 DUP1 MUL DUP2 ADD PUSH1 7 XOR DUP2 AND POP
+///      PUSH1 1 SWAP1 SUB
+///      DUP1 PUSH1 4 JUMPI
+///      STOP
+/// ```
+const COUNTDOWN: &str = "621000005b8080028101600718811650600190038060045700";
+
+fn run_countdown(code: &Arc<Vec<u8>>) {
+	let mut machine = Machine::new(code.clone(), Arc::new(Vec::new()), 1024, 10000);
+	assert_eq!(
+		machine.run(),
+		Capture::Exit(ExitReason::Succeed(ExitSucceed::Stopped))
+	);
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+	let code = Arc::new(hex::decode(COUNTDOWN).unwrap());
+	c.bench_function("stack countdown", |b| b.iter(|| run_countdown(&code)));
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
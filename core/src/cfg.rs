@@ -0,0 +1,530 @@
+use crate::{Opcode, Valids};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::cmp::min;
+
+/// Destination of a `JUMP` or `JUMPI`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum JumpTarget {
+	/// Destination is pushed right before the jump and is a valid jump
+	/// destination.
+	Static(usize),
+	/// Destination is pushed right before the jump, but is not a valid jump
+	/// destination. Taking the jump always fails.
+	Invalid,
+	/// Destination is computed at runtime.
+	Dynamic,
+}
+
+/// How control leaves a basic block.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BlockExit {
+	/// Execution continues with the next block, either because it starts
+	/// with `JUMPDEST` or because the end of code is reached.
+	FallThrough,
+	/// Unconditional `JUMP`.
+	Jump(JumpTarget),
+	/// Conditional `JUMPI`. Falls through to the next block if not taken.
+	JumpI(JumpTarget),
+	/// Execution halts with the given opcode.
+	Halt(Opcode),
+}
+
+/// A straight-line run of code with a single entry and a single exit.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BasicBlock {
+	/// Position of the first opcode of the block.
+	pub start: usize,
+	/// Position right after the last opcode of the block, including any push
+	/// data.
+	pub end: usize,
+	/// How control leaves the block.
+	pub exit: BlockExit,
+	/// Indexes of blocks that can statically be reached from this block.
+	pub successors: Vec<usize>,
+}
+
+/// An entry of a Solidity-style function selector dispatch table.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Dispatch {
+	/// Four-byte function selector.
+	pub selector: [u8; 4],
+	/// Position of the function body the selector jumps to.
+	pub target: usize,
+}
+
+/// Control flow graph of contract code, built from static analysis.
+#[derive(Clone, Debug)]
+pub struct ControlFlowGraph {
+	blocks: Vec<BasicBlock>,
+	reachable: Vec<bool>,
+	dispatch: Vec<Dispatch>,
+	has_dynamic_jumps: bool,
+}
+
+impl ControlFlowGraph {
+	/// Analyze given code bytes. `valids` must be the jump destination
+	/// mapping of the same code.
+	pub fn new(code: &[u8], valids: &Valids) -> Self {
+		Self::new_with_immediates(code, valids, |_| 0)
+	}
+
+	/// Analyze given code bytes, where `immediates` gives the length of the
+	/// immediate data following opcodes other than `PUSH`. `valids` must be
+	/// the jump destination mapping of the same code, built with the same
+	/// `immediates`.
+	pub fn new_with_immediates<F: Fn(Opcode) -> usize>(
+		code: &[u8],
+		valids: &Valids,
+		immediates: F,
+	) -> Self {
+		let mut blocks = Vec::new();
+		let mut dispatch = Vec::new();
+		let mut has_dynamic_jumps = false;
+
+		// Opcodes of the current block, with their positions.
+		let mut ops: Vec<(usize, Opcode)> = Vec::new();
+		let mut start = 0;
+		let mut i = 0;
+
+		while i < code.len() {
+			let opcode = Opcode(code[i]);
+
+			if opcode == Opcode::JUMPDEST && i != start {
+				blocks.push(BasicBlock {
+					start,
+					end: i,
+					exit: BlockExit::FallThrough,
+					successors: Vec::new(),
+				});
+				ops.clear();
+				start = i;
+			}
+
+			ops.push((i, opcode));
+			let len = match opcode.is_push() {
+				Some(n) => n as usize,
+				None => immediates(opcode),
+			};
+			let next = min(i + 1 + len, code.len());
+
+			let exit = match opcode {
+				Opcode::JUMP | Opcode::JUMPI => {
+					let target = jump_target(code, valids, &ops);
+					if target == JumpTarget::Dynamic {
+						has_dynamic_jumps = true;
+					}
+
+					if opcode == Opcode::JUMP {
+						Some(BlockExit::Jump(target))
+					} else {
+						if let (JumpTarget::Static(target), Some(selector)) =
+							(target, dispatch_selector(code, &ops))
+						{
+							dispatch.push(Dispatch { selector, target });
+						}
+						Some(BlockExit::JumpI(target))
+					}
+				}
+				Opcode::STOP
+				| Opcode::RETURN
+				| Opcode::REVERT
+				| Opcode::INVALID
+				| Opcode::SUICIDE => Some(BlockExit::Halt(opcode)),
+				_ => None,
+			};
+
+			if let Some(exit) = exit {
+				blocks.push(BasicBlock {
+					start,
+					end: next,
+					exit,
+					successors: Vec::new(),
+				});
+				ops.clear();
+				start = next;
+			}
+
+			i = next;
+		}
+
+		if start < code.len() {
+			blocks.push(BasicBlock {
+				start,
+				end: code.len(),
+				exit: BlockExit::FallThrough,
+				successors: Vec::new(),
+			});
+		}
+
+		let mut cfg = Self {
+			blocks,
+			reachable: Vec::new(),
+			dispatch,
+			has_dynamic_jumps,
+		};
+		cfg.link();
+		cfg.mark_reachable(valids);
+		cfg
+	}
+
+	fn link(&mut self) {
+		for index in 0..self.blocks.len() {
+			let next = if index + 1 < self.blocks.len() {
+				Some(index + 1)
+			} else {
+				None
+			};
+
+			let mut successors = Vec::new();
+			match self.blocks[index].exit {
+				BlockExit::FallThrough => successors.extend(next),
+				BlockExit::Jump(target) => successors.extend(self.target_index(target)),
+				BlockExit::JumpI(target) => {
+					successors.extend(self.target_index(target));
+					successors.extend(next);
+				}
+				BlockExit::Halt(_) => (),
+			}
+			successors.dedup();
+
+			self.blocks[index].successors = successors;
+		}
+	}
+
+	fn target_index(&self, target: JumpTarget) -> Option<usize> {
+		match target {
+			JumpTarget::Static(position) => self.block_index(position),
+			JumpTarget::Invalid | JumpTarget::Dynamic => None,
+		}
+	}
+
+	fn mark_reachable(&mut self, valids: &Valids) {
+		let mut reachable = Vec::new();
+		reachable.resize(self.blocks.len(), false);
+
+		let mut queue = VecDeque::new();
+		if !self.blocks.is_empty() {
+			reachable[0] = true;
+			queue.push_back(0);
+		}
+
+		let mut dynamic_seen = false;
+		loop {
+			while let Some(index) = queue.pop_front() {
+				let block = &self.blocks[index];
+				if let BlockExit::Jump(JumpTarget::Dynamic)
+				| BlockExit::JumpI(JumpTarget::Dynamic) = block.exit
+				{
+					dynamic_seen = true;
+				}
+
+				for &successor in &block.successors {
+					if !reachable[successor] {
+						reachable[successor] = true;
+						queue.push_back(successor);
+					}
+				}
+			}
+
+			if !dynamic_seen {
+				break;
+			}
+
+			// A reachable dynamic jump can land on any jump destination, so
+			// conservatively consider all of them reachable.
+			for (index, block) in self.blocks.iter().enumerate() {
+				if !reachable[index] && valids.is_valid(block.start) {
+					reachable[index] = true;
+					queue.push_back(index);
+				}
+			}
+			if queue.is_empty() {
+				break;
+			}
+		}
+
+		self.reachable = reachable;
+	}
+
+	/// All basic blocks, ordered by position.
+	pub fn blocks(&self) -> &[BasicBlock] {
+		&self.blocks
+	}
+
+	/// Index of the block containing the given position, if any.
+	pub fn block_index(&self, position: usize) -> Option<usize> {
+		let index = match self.blocks.binary_search_by(|b| b.start.cmp(&position)) {
+			Ok(index) => index,
+			Err(0) => return None,
+			Err(index) => index - 1,
+		};
+
+		if position < self.blocks[index].end {
+			Some(index)
+		} else {
+			None
+		}
+	}
+
+	/// The block containing the given position, if any.
+	pub fn block_at(&self, position: usize) -> Option<&BasicBlock> {
+		self.block_index(position).map(|index| &self.blocks[index])
+	}
+
+	/// Whether the block at the given index may be executed. Blocks only
+	/// reachable through dynamic jumps are considered reachable as long as
+	/// they start with `JUMPDEST`.
+	pub fn is_reachable(&self, index: usize) -> bool {
+		self.reachable.get(index).copied().unwrap_or(false)
+	}
+
+	/// Blocks that can never be executed.
+	pub fn unreachable_blocks(&self) -> impl Iterator<Item = &BasicBlock> {
+		self.blocks
+			.iter()
+			.zip(self.reachable.iter())
+			.filter(|(_, reachable)| !**reachable)
+			.map(|(block, _)| block)
+	}
+
+	/// Whether any jump destination could not be resolved statically.
+	pub fn has_dynamic_jumps(&self) -> bool {
+		self.has_dynamic_jumps
+	}
+
+	/// Function selector dispatch entries found in the code, in code order.
+	pub fn dispatch(&self) -> &[Dispatch] {
+		&self.dispatch
+	}
+}
+
+impl BasicBlock {
+	/// Length of the block in code bytes.
+	pub fn len(&self) -> usize {
+		self.end - self.start
+	}
+
+	/// Returns true if the block is empty.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+/// Immediate value of a push opcode, as it would be seen by the machine.
+fn push_data(code: &[u8], position: usize, opcode: Opcode) -> &[u8] {
+	let n = opcode.is_push().unwrap_or(0) as usize;
+	&code[min(position + 1, code.len())..min(position + 1 + n, code.len())]
+}
+
+/// Resolve the target of the jump at the end of `ops`.
+fn jump_target(code: &[u8], valids: &Valids, ops: &[(usize, Opcode)]) -> JumpTarget {
+	let (position, opcode) = match ops.len().checked_sub(2).map(|i| ops[i]) {
+		Some((position, opcode)) if opcode.is_push().is_some() => (position, opcode),
+		_ => return JumpTarget::Dynamic,
+	};
+
+	let data = push_data(code, position, opcode);
+	let significant = data.iter().skip_while(|b| **b == 0).count();
+	if significant > core::mem::size_of::<usize>() {
+		return JumpTarget::Invalid;
+	}
+
+	let dest = data.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+	if valids.is_valid(dest) {
+		JumpTarget::Static(dest)
+	} else {
+		JumpTarget::Invalid
+	}
+}
+
+/// Match the selector comparison solc emits for each public function, that
+/// is `PUSH4 selector, [DUPn,] EQ, PUSHn target, JUMPI`.
+fn dispatch_selector(code: &[u8], ops: &[(usize, Opcode)]) -> Option<[u8; 4]> {
+	let mut rest = ops.iter().rev().skip(2);
+
+	if rest.next()?.1 != Opcode::EQ {
+		return None;
+	}
+
+	let mut op = rest.next()?;
+	if (Opcode::DUP1.0..=Opcode::DUP16.0).contains(&op.1 .0) {
+		op = rest.next()?;
+	}
+
+	match op.1.is_push() {
+		Some(n) if n <= 4 => {
+			let data = push_data(code, op.0, op.1);
+			let mut selector = [0u8; 4];
+			selector[(4 - data.len())..].copy_from_slice(data);
+			Some(selector)
+		}
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn dispatch_and_reachability() {
+		let code = hex::decode(concat!(
+			"60003560e01c", // 0x00: selector from calldata
+			"8063a9059cbb", // 0x06: DUP1 PUSH4 transfer(address,uint256)
+			"14601757",     // 0x0c: EQ PUSH1 0x17 JUMPI
+			"60006000fd",   // 0x10: revert(0, 0)
+			"5b00",         // 0x15: (unreachable) JUMPDEST STOP
+			"5b6000",       // 0x17: JUMPDEST PUSH1 0
+			"56"            // 0x1a: JUMP to an invalid destination
+		))
+		.unwrap();
+		let valids = Valids::new(&code);
+		let cfg = ControlFlowGraph::new(&code, &valids);
+
+		assert_eq!(
+			cfg.dispatch(),
+			&[Dispatch {
+				selector: [0xa9, 0x05, 0x9c, 0xbb],
+				target: 0x17,
+			}]
+		);
+		assert!(!cfg.has_dynamic_jumps());
+		assert_eq!(
+			cfg.blocks()[0].exit,
+			BlockExit::JumpI(JumpTarget::Static(0x17))
+		);
+		assert_eq!(cfg.blocks()[0].successors, vec![3, 1]);
+		assert_eq!(
+			cfg.block_at(0x15).unwrap().exit,
+			BlockExit::Halt(Opcode::STOP)
+		);
+		assert_eq!(
+			cfg.block_at(0x1a).unwrap().exit,
+			BlockExit::Jump(JumpTarget::Invalid)
+		);
+		assert_eq!(
+			cfg.unreachable_blocks()
+				.map(|b| b.start)
+				.collect::<Vec<_>>(),
+			vec![0x15]
+		);
+	}
+
+	fn starts(cfg: &ControlFlowGraph) -> Vec<usize> {
+		cfg.blocks().iter().map(|b| b.start).collect()
+	}
+
+	fn unreachable(cfg: &ControlFlowGraph) -> Vec<usize> {
+		cfg.unreachable_blocks().map(|b| b.start).collect()
+	}
+
+	#[test]
+	fn dynamic_jump_reachability() {
+		let code = hex::decode(concat!(
+			"60003556", // 0x00: JUMP to a destination from calldata
+			"5b00",     // 0x04: JUMPDEST STOP
+			"600100",   // 0x06: (unreachable) PUSH1 1 STOP
+			"5b00"      // 0x09: JUMPDEST STOP
+		))
+		.unwrap();
+		let valids = Valids::new(&code);
+		let cfg = ControlFlowGraph::new(&code, &valids);
+
+		assert!(cfg.has_dynamic_jumps());
+		assert_eq!(starts(&cfg), vec![0x00, 0x04, 0x06, 0x09]);
+		assert_eq!(cfg.blocks()[0].exit, BlockExit::Jump(JumpTarget::Dynamic));
+		assert!(cfg.blocks()[0].successors.is_empty());
+		// Any jump destination can be reached, but not other blocks.
+		assert_eq!(unreachable(&cfg), vec![0x06]);
+
+		// A dynamic jump that cannot be reached does not make jump
+		// destinations reachable.
+		let code = hex::decode(concat!(
+			"00",       // 0x00: STOP
+			"60003556", // 0x01: (unreachable) JUMP from calldata
+			"5b00"      // 0x05: (unreachable) JUMPDEST STOP
+		))
+		.unwrap();
+		let valids = Valids::new(&code);
+		let cfg = ControlFlowGraph::new(&code, &valids);
+
+		assert!(cfg.has_dynamic_jumps());
+		assert_eq!(unreachable(&cfg), vec![0x01, 0x05]);
+	}
+
+	#[test]
+	fn truncated_push_at_end_of_code() {
+		let code = hex::decode(concat!(
+			"5b600056", // 0x00: JUMPDEST PUSH1 0 JUMP
+			"6105"      // 0x04: PUSH2 with a single byte of data
+		))
+		.unwrap();
+		let valids = Valids::new(&code);
+		let cfg = ControlFlowGraph::new(&code, &valids);
+
+		assert_eq!(
+			cfg.blocks(),
+			&[
+				BasicBlock {
+					start: 0x00,
+					end: 0x04,
+					exit: BlockExit::Jump(JumpTarget::Static(0x00)),
+					successors: vec![0],
+				},
+				BasicBlock {
+					start: 0x04,
+					end: 0x06,
+					exit: BlockExit::FallThrough,
+					successors: Vec::new(),
+				},
+			]
+		);
+		assert_eq!(unreachable(&cfg), vec![0x04]);
+	}
+
+	#[test]
+	fn jump_destination_in_push_data() {
+		let code = hex::decode(concat!(
+			"605b", // 0x00: PUSH1 with a JUMPDEST byte as data
+			"6001", // 0x02: PUSH1 1
+			"56",   // 0x04: JUMP into the push data
+			"5b00"  // 0x05: (unreachable) JUMPDEST STOP
+		))
+		.unwrap();
+		let valids = Valids::new(&code);
+		let cfg = ControlFlowGraph::new(&code, &valids);
+
+		assert_eq!(starts(&cfg), vec![0x00, 0x05]);
+		assert_eq!(cfg.block_index(0x01), Some(0));
+		assert_eq!(cfg.blocks()[0].exit, BlockExit::Jump(JumpTarget::Invalid));
+		assert_eq!(unreachable(&cfg), vec![0x05]);
+	}
+
+	#[test]
+	fn custom_immediates() {
+		let code = hex::decode(concat!(
+			"0c5b00", // 0x00: opcode 0x0c with two bytes of immediate data
+			"6006",   // 0x03: PUSH1 6
+			"56",     // 0x05: JUMP
+			"5b00"    // 0x06: JUMPDEST STOP
+		))
+		.unwrap();
+		let immediates = |opcode| if opcode == Opcode(0x0c) { 2 } else { 0 };
+		let valids = Valids::new_with_immediates(&code, immediates);
+		let cfg = ControlFlowGraph::new_with_immediates(&code, &valids, immediates);
+
+		assert!(!valids.is_valid(0x01));
+		assert_eq!(starts(&cfg), vec![0x00, 0x06]);
+		assert_eq!(
+			cfg.blocks()[0].exit,
+			BlockExit::Jump(JumpTarget::Static(0x06))
+		);
+		assert!(unreachable(&cfg).is_empty());
+
+		// Without the immediates, the data is taken for opcodes.
+		let valids = Valids::new(&code);
+		let cfg = ControlFlowGraph::new(&code, &valids);
+		assert_eq!(starts(&cfg), vec![0x00, 0x01, 0x03, 0x06]);
+	}
+}
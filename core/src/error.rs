@@ -150,6 +150,9 @@ pub enum ExitError {
 	/// Attempt to create an empty account (runtime, unused).
 	#[cfg_attr(feature = "with-codec", codec(index = 12))]
 	CreateEmpty,
+	/// Execution exceeds a resource limit (runtime).
+	#[cfg_attr(feature = "with-codec", codec(index = 16))]
+	OutOfResource,
 
 	/// Other normal errors.
 	#[cfg_attr(feature = "with-codec", codec(index = 13))]
@@ -179,6 +182,10 @@ pub enum ExitFatal {
 
 	/// Other fatal errors.
 	Other(Cow<'static, str>),
+	/// The configured limit of executed opcodes has been reached.
+	StepLimitReached,
+	/// Execution has been cancelled by the environment.
+	Interrupted,
 }
 
 impl From<ExitFatal> for ExitReason {
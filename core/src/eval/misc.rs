@@ -72,7 +72,7 @@ pub fn calldatacopy(state: &mut Machine) -> Control {
 
 #[inline]
 pub fn pop(state: &mut Machine) -> Control {
-	pop!(state, _val);
+	pop_u256!(state, _val);
 	Control::Continue(1)
 }
 
@@ -125,9 +125,9 @@ pub fn jump(state: &mut Machine) -> Control {
 #[inline]
 pub fn jumpi(state: &mut Machine) -> Control {
 	pop_u256!(state, dest);
-	pop!(state, value);
+	pop_u256!(state, value);
 
-	if value != H256::zero() {
+	if !value.is_zero() {
 		let dest = as_usize_or_fail!(dest, ExitError::InvalidJump);
 		if state.valids.is_valid(dest) {
 			Control::Jump(dest)
@@ -155,10 +155,9 @@ pub fn msize(state: &mut Machine) -> Control {
 pub fn push(state: &mut Machine, n: usize, position: usize) -> Control {
 	let end = min(position + 1 + n, state.code.len());
 	let slice = &state.code[(position + 1)..end];
-	let mut val = [0u8; 32];
-	val[(32 - slice.len())..32].copy_from_slice(slice);
+	let val = U256::from_big_endian(slice);
 
-	push!(state, H256(val));
+	push_u256!(state, val);
 	Control::Continue(1 + n)
 }
 
@@ -168,7 +167,7 @@ pub fn dup(state: &mut Machine, n: usize) -> Control {
 		Ok(value) => value,
 		Err(e) => return Control::Exit(e.into()),
 	};
-	push!(state, value);
+	push_u256!(state, value);
 	Control::Continue(1)
 }
 
@@ -7,38 +7,43 @@
 extern crate alloc;
 extern crate core;
 
+mod cfg;
 mod error;
 mod eval;
 mod memory;
 mod opcode;
+mod snapshot;
 mod stack;
 mod utils;
 mod valids;
 
+pub use crate::cfg::{BasicBlock, BlockExit, ControlFlowGraph, Dispatch, JumpTarget};
 pub use crate::error::{Capture, ExitError, ExitFatal, ExitReason, ExitRevert, ExitSucceed, Trap};
 pub use crate::memory::Memory;
 pub use crate::opcode::Opcode;
+pub use crate::snapshot::MachineSnapshot;
 pub use crate::stack::Stack;
 pub use crate::valids::Valids;
 
 use crate::eval::{eval, Control};
-use alloc::rc::Rc;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::convert::TryFrom;
 use core::ops::Range;
 use primitive_types::U256;
 
 /// Core execution layer for EVM.
 pub struct Machine {
 	/// Program data.
-	data: Rc<Vec<u8>>,
+	data: Arc<Vec<u8>>,
 	/// Program code.
-	code: Rc<Vec<u8>>,
+	code: Arc<Vec<u8>>,
 	/// Program counter.
 	position: Result<usize, ExitReason>,
 	/// Return value.
 	return_range: Range<U256>,
 	/// Code validity maps.
-	valids: Valids,
+	valids: Arc<Valids>,
 	/// Memory.
 	memory: Memory,
 	/// Stack.
@@ -62,20 +67,48 @@ impl Machine {
 	pub fn memory_mut(&mut self) -> &mut Memory {
 		&mut self.memory
 	}
+	/// Reference of the valid jump destination mapping of the code.
+	pub fn valids(&self) -> &Valids {
+		&self.valids
+	}
 	/// Return a reference of the program counter.
 	pub fn position(&self) -> &Result<usize, ExitReason> {
 		&self.position
 	}
+	/// Reference of the program code.
+	pub fn code(&self) -> &[u8] {
+		&self.code
+	}
+	/// Reference of the program data.
+	pub fn data(&self) -> &[u8] {
+		&self.data
+	}
+	/// Memory range of the return value.
+	pub fn return_range(&self) -> &Range<U256> {
+		&self.return_range
+	}
 
 	/// Create a new machine with given code and data.
 	pub fn new(
-		code: Rc<Vec<u8>>,
-		data: Rc<Vec<u8>>,
+		code: Arc<Vec<u8>>,
+		data: Arc<Vec<u8>>,
 		stack_limit: usize,
 		memory_limit: usize,
 	) -> Self {
-		let valids = Valids::new(&code[..]);
+		let valids = Arc::new(Valids::new(&code[..]));
 
+		Self::new_with_valids(code, valids, data, stack_limit, memory_limit)
+	}
+
+	/// Create a new machine with given code, its already computed valid
+	/// mapping and data.
+	pub fn new_with_valids(
+		code: Arc<Vec<u8>>,
+		valids: Arc<Valids>,
+		data: Arc<Vec<u8>>,
+		stack_limit: usize,
+		memory_limit: usize,
+	) -> Self {
 		Self {
 			data,
 			code,
@@ -87,6 +120,84 @@ impl Machine {
 		}
 	}
 
+	/// Take a snapshot of the complete machine state.
+	pub fn snapshot(&self) -> MachineSnapshot {
+		MachineSnapshot {
+			data: self.data.as_ref().clone(),
+			code: self.code.as_ref().clone(),
+			position: self.position.clone().map(|position| position as u64),
+			return_start: self.return_range.start,
+			return_end: self.return_range.end,
+			memory: self.memory.data().clone(),
+			memory_effective_len: self.memory.effective_len(),
+			memory_limit: self.memory.limit() as u64,
+			stack: self.stack.data().clone(),
+			stack_limit: self.stack.limit() as u64,
+		}
+	}
+
+	/// Restore a machine from a snapshot, so that it continues execution
+	/// exactly where the snapshotted machine was. The snapshot is considered
+	/// untrusted, and is rejected if it does not fit within its own limits.
+	pub fn restore(snapshot: MachineSnapshot) -> Result<Self, ExitError> {
+		Self::restore_with_immediates(snapshot, |_| 0)
+	}
+
+	/// Restore a machine from a snapshot of code with custom opcodes, where
+	/// `immediates` gives the length of the immediate data following opcodes
+	/// other than `PUSH`, as for `Valids::new_with_immediates`.
+	pub fn restore_with_immediates<F: Fn(Opcode) -> usize>(
+		snapshot: MachineSnapshot,
+		immediates: F,
+	) -> Result<Self, ExitError> {
+		let valids = Arc::new(Valids::new_with_immediates(&snapshot.code, immediates));
+
+		Self::restore_with_valids(snapshot, valids)
+	}
+
+	/// Restore a machine from a snapshot, with the already computed valid
+	/// mapping of its code.
+	pub fn restore_with_valids(
+		snapshot: MachineSnapshot,
+		valids: Arc<Valids>,
+	) -> Result<Self, ExitError> {
+		let to_usize = |value: u64| usize::try_from(value).map_err(|_| ExitError::InvalidRange);
+
+		let position = match snapshot.position {
+			Ok(position) => Ok(to_usize(position)?),
+			Err(reason) => Err(reason),
+		};
+
+		let mut memory = Memory::new(to_usize(snapshot.memory_limit)?);
+		memory
+			.set(0, &snapshot.memory, None)
+			.map_err(|_| ExitError::InvalidRange)?;
+		memory.resize_end(snapshot.memory_effective_len)?;
+
+		let mut stack = Stack::new(to_usize(snapshot.stack_limit)?);
+		for value in snapshot.stack {
+			stack.push(value)?;
+		}
+
+		Ok(Self {
+			data: Arc::new(snapshot.data),
+			code: Arc::new(snapshot.code),
+			position,
+			return_range: snapshot.return_start..snapshot.return_end,
+			valids,
+			memory,
+			stack,
+		})
+	}
+
+	/// Move the position forward by `len` bytes, to skip immediate data.
+	/// Does nothing if the machine has exited.
+	pub fn advance(&mut self, len: usize) {
+		if let Ok(position) = self.position.as_mut() {
+			*position = position.saturating_add(len);
+		}
+	}
+
 	/// Explicit exit of the machine. Further step will return error.
 	pub fn exit(&mut self, reason: ExitReason) {
 		self.position = Err(reason);
@@ -0,0 +1,37 @@
+use crate::ExitReason;
+use alloc::vec::Vec;
+use primitive_types::U256;
+
+/// Complete state of a `Machine` at a point of its execution.
+///
+/// Sizes and positions are stored as `u64` so that the snapshot encodes the
+/// same way on every platform. Valid jump destinations are not part of the
+/// snapshot, and are recomputed from the code on restore.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+	feature = "with-codec",
+	derive(codec::Encode, codec::Decode, scale_info::TypeInfo)
+)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MachineSnapshot {
+	/// Program data.
+	pub data: Vec<u8>,
+	/// Program code.
+	pub code: Vec<u8>,
+	/// Program counter, or the reason the machine exited.
+	pub position: Result<u64, ExitReason>,
+	/// Start of the return value range in memory.
+	pub return_start: U256,
+	/// End of the return value range in memory.
+	pub return_end: U256,
+	/// Memory content.
+	pub memory: Vec<u8>,
+	/// Effective length of the memory.
+	pub memory_effective_len: U256,
+	/// Memory limit.
+	pub memory_limit: u64,
+	/// Stack items, from bottom to top.
+	pub stack: Vec<U256>,
+	/// Stack limit.
+	pub stack_limit: u64,
+}
@@ -1,11 +1,15 @@
 use crate::ExitError;
 use alloc::vec::Vec;
-use primitive_types::H256;
+use primitive_types::{H256, U256};
 
 /// EVM stack.
+///
+/// Values are held as native `U256` words, so that arithmetic does not need
+/// any conversion. Use the `*_h256` variants for values that are used as
+/// bytes, such as addresses, storage keys and hashes.
 #[derive(Clone, Debug)]
 pub struct Stack {
-	data: Vec<H256>,
+	data: Vec<U256>,
 	limit: usize,
 }
 
@@ -38,21 +42,21 @@ impl Stack {
 
 	#[inline]
 	/// Stack data.
-	pub fn data(&self) -> &Vec<H256> {
+	pub fn data(&self) -> &Vec<U256> {
 		&self.data
 	}
 
 	#[inline]
 	/// Pop a value from the stack. If the stack is already empty, returns the
 	/// `StackUnderflow` error.
-	pub fn pop(&mut self) -> Result<H256, ExitError> {
+	pub fn pop(&mut self) -> Result<U256, ExitError> {
 		self.data.pop().ok_or(ExitError::StackUnderflow)
 	}
 
 	#[inline]
 	/// Push a new value into the stack. If it will exceed the stack limit,
 	/// returns `StackOverflow` error and leaves the stack unchanged.
-	pub fn push(&mut self, value: H256) -> Result<(), ExitError> {
+	pub fn push(&mut self, value: U256) -> Result<(), ExitError> {
 		if self.data.len() + 1 > self.limit {
 			return Err(ExitError::StackOverflow);
 		}
@@ -64,7 +68,7 @@ impl Stack {
 	/// Peek a value at given index for the stack, where the top of
 	/// the stack is at index `0`. If the index is too large,
 	/// `StackError::Underflow` is returned.
-	pub fn peek(&self, no_from_top: usize) -> Result<H256, ExitError> {
+	pub fn peek(&self, no_from_top: usize) -> Result<U256, ExitError> {
 		if self.data.len() > no_from_top {
 			Ok(self.data[self.data.len() - no_from_top - 1])
 		} else {
@@ -76,7 +80,7 @@ impl Stack {
 	/// Set a value at given index for the stack, where the top of the
 	/// stack is at index `0`. If the index is too large,
 	/// `StackError::Underflow` is returned.
-	pub fn set(&mut self, no_from_top: usize, val: H256) -> Result<(), ExitError> {
+	pub fn set(&mut self, no_from_top: usize, val: U256) -> Result<(), ExitError> {
 		if self.data.len() > no_from_top {
 			let len = self.data.len();
 			self.data[len - no_from_top - 1] = val;
@@ -85,4 +89,94 @@ impl Stack {
 			Err(ExitError::StackUnderflow)
 		}
 	}
+
+	#[inline]
+	/// Pop a value from the stack as big-endian bytes. If the stack is
+	/// already empty, returns the `StackUnderflow` error.
+	pub fn pop_h256(&mut self) -> Result<H256, ExitError> {
+		self.pop().map(u256_to_h256)
+	}
+
+	#[inline]
+	/// Push a new value given as big-endian bytes into the stack. If it will
+	/// exceed the stack limit, returns `StackOverflow` error and leaves the
+	/// stack unchanged.
+	pub fn push_h256(&mut self, value: H256) -> Result<(), ExitError> {
+		self.push(U256::from_big_endian(&value[..]))
+	}
+
+	#[inline]
+	/// Peek a value at given index for the stack as big-endian bytes, where
+	/// the top of the stack is at index `0`. If the index is too large,
+	/// `StackError::Underflow` is returned.
+	pub fn peek_h256(&self, no_from_top: usize) -> Result<H256, ExitError> {
+		self.peek(no_from_top).map(u256_to_h256)
+	}
+}
+
+#[inline]
+fn u256_to_h256(value: U256) -> H256 {
+	let mut ret = H256::default();
+	value.to_big_endian(&mut ret[..]);
+	ret
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn h256_conversions() {
+		let mut stack = Stack::new(8);
+		let mut high = [0; 32];
+		high[0] = 0x80;
+		let high = H256(high);
+
+		stack.push_h256(H256::from_low_u64_be(1)).unwrap();
+		stack.push_h256(high).unwrap();
+		assert_eq!(stack.data(), &[U256::one(), U256::one() << 255]);
+
+		stack.push(U256::from(0x1234)).unwrap();
+		assert_eq!(stack.peek_h256(0), Ok(H256::from_low_u64_be(0x1234)));
+		assert_eq!(stack.peek_h256(1), Ok(high));
+		assert_eq!(stack.pop_h256(), Ok(H256::from_low_u64_be(0x1234)));
+		assert_eq!(stack.pop_h256(), Ok(high));
+
+		stack.push(U256::MAX).unwrap();
+		assert_eq!(stack.pop_h256(), Ok(H256::repeat_byte(0xff)));
+		assert_eq!(stack.pop(), Ok(U256::one()));
+	}
+
+	#[test]
+	fn underflow() {
+		let mut stack = Stack::new(8);
+		assert_eq!(stack.pop(), Err(ExitError::StackUnderflow));
+		assert_eq!(stack.pop_h256(), Err(ExitError::StackUnderflow));
+		assert_eq!(stack.peek(0), Err(ExitError::StackUnderflow));
+
+		stack.push(U256::one()).unwrap();
+		assert_eq!(stack.peek(0), Ok(U256::one()));
+		assert_eq!(stack.peek(1), Err(ExitError::StackUnderflow));
+		assert_eq!(stack.peek_h256(1), Err(ExitError::StackUnderflow));
+		assert_eq!(stack.set(1, U256::zero()), Err(ExitError::StackUnderflow));
+		assert_eq!(stack.set(0, U256::from(2)), Ok(()));
+		assert_eq!(stack.data(), &[U256::from(2)]);
+	}
+
+	#[test]
+	fn overflow() {
+		let mut stack = Stack::new(2);
+		stack.push(U256::one()).unwrap();
+		stack.push_h256(H256::from_low_u64_be(2)).unwrap();
+
+		assert_eq!(stack.push(U256::from(3)), Err(ExitError::StackOverflow));
+		assert_eq!(
+			stack.push_h256(H256::from_low_u64_be(3)),
+			Err(ExitError::StackOverflow)
+		);
+		assert_eq!(stack.data(), &[U256::one(), U256::from(2)]);
+
+		stack.pop().unwrap();
+		assert_eq!(stack.push(U256::from(3)), Ok(()));
+	}
 }
@@ -8,6 +8,13 @@ pub struct Valids(Vec<bool>);
 impl Valids {
 	/// Create a new valid mapping from given code bytes.
 	pub fn new(code: &[u8]) -> Self {
+		Self::new_with_immediates(code, |_| 0)
+	}
+
+	/// Create a new valid mapping from given code bytes, where `immediates`
+	/// gives the length of the immediate data following opcodes other than
+	/// `PUSH`.
+	pub fn new_with_immediates<F: Fn(Opcode) -> usize>(code: &[u8], immediates: F) -> Self {
 		let mut valids: Vec<bool> = Vec::with_capacity(code.len());
 		valids.resize(code.len(), false);
 
@@ -20,7 +27,7 @@ impl Valids {
 			} else if let Some(v) = opcode.is_push() {
 				i += v as usize + 1;
 			} else {
-				i += 1;
+				i += immediates(opcode) + 1;
 			}
 		}
 
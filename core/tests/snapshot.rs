@@ -0,0 +1,139 @@
+//! A machine restored from a snapshot resumes exactly where the snapshotted
+//! machine was.
+
+use evm_core::{
+	Capture, ExitError, ExitReason, ExitSucceed, Machine, MachineSnapshot, Opcode, Valids,
+};
+use std::sync::Arc;
+
+// PUSH1 0x20, PUSH1 0, MSTORE, then count down from 5, and RETURN the first
+// word of memory.
+const COUNTDOWN: &str = "6020600052 6005 5b 6001900380600757 60206000f3";
+
+fn machine(code: &str) -> Machine {
+	Machine::new(
+		Arc::new(hex::decode(code.replace(' ', "")).unwrap()),
+		Arc::new(vec![0xaa, 0xbb]),
+		1024,
+		10000,
+	)
+}
+
+/// Step a machine at most `steps` times.
+fn step(machine: &mut Machine, steps: usize) {
+	for _ in 0..steps {
+		if machine.step().is_err() {
+			break;
+		}
+	}
+}
+
+#[test]
+fn resumed_execution() {
+	let mut uninterrupted = machine(COUNTDOWN);
+	assert_eq!(
+		uninterrupted.run(),
+		Capture::Exit(ExitReason::Succeed(ExitSucceed::Returned))
+	);
+	assert_eq!(uninterrupted.return_value()[31], 0x20);
+
+	// Snapshot at each step, including after the exit.
+	for steps in 0..40 {
+		let mut interrupted = machine(COUNTDOWN);
+		step(&mut interrupted, steps);
+
+		let snapshot = interrupted.snapshot();
+		let mut restored = Machine::restore(snapshot.clone()).unwrap();
+		assert_eq!(restored.snapshot(), snapshot);
+
+		assert_eq!(
+			restored.run(),
+			Capture::Exit(ExitReason::Succeed(ExitSucceed::Returned))
+		);
+		assert_eq!(restored.snapshot(), uninterrupted.snapshot(), "{}", steps);
+		assert_eq!(restored.return_value(), uninterrupted.return_value());
+	}
+}
+
+#[test]
+fn restore_with_immediates() {
+	// PUSH1 4, JUMP to the immediate data of opcode 0x0c, which is a
+	// JUMPDEST byte, then STOP.
+	let code = "6004 56 0c5b 00";
+	let immediates = |opcode| if opcode == Opcode(0x0c) { 1 } else { 0 };
+
+	let mut snapshot = machine(code);
+	step(&mut snapshot, 1);
+	let snapshot = snapshot.snapshot();
+
+	let mut restored = Machine::restore_with_immediates(snapshot.clone(), immediates).unwrap();
+	assert_eq!(
+		restored.run(),
+		Capture::Exit(ExitReason::Error(ExitError::InvalidJump))
+	);
+
+	// Same as an uninterrupted machine analysing the same immediates.
+	let bytes = Arc::new(snapshot.code.clone());
+	let valids = Arc::new(Valids::new_with_immediates(&bytes, immediates));
+	let mut uninterrupted =
+		Machine::new_with_valids(bytes, valids, Arc::new(Vec::new()), 1024, 10000);
+	assert_eq!(
+		uninterrupted.run(),
+		Capture::Exit(ExitReason::Error(ExitError::InvalidJump))
+	);
+
+	// Without the immediates, the data is a valid jump destination.
+	let mut restored = Machine::restore(snapshot).unwrap();
+	assert_eq!(
+		restored.run(),
+		Capture::Exit(ExitReason::Succeed(ExitSucceed::Stopped))
+	);
+}
+
+#[test]
+fn rejects_snapshot_beyond_limits() {
+	let mut snapshot = machine(COUNTDOWN);
+	step(&mut snapshot, 4);
+	let snapshot = snapshot.snapshot();
+
+	assert!(Machine::restore(MachineSnapshot {
+		stack_limit: 0,
+		..snapshot.clone()
+	})
+	.is_err());
+	assert!(Machine::restore(MachineSnapshot {
+		memory_limit: 31,
+		..snapshot
+	})
+	.is_err());
+}
+
+#[cfg(feature = "with-serde")]
+#[test]
+fn serde_round_trip() {
+	let mut machine = machine(COUNTDOWN);
+	step(&mut machine, 6);
+	let snapshot = machine.snapshot();
+
+	let json = serde_json::to_string(&snapshot).unwrap();
+	assert_eq!(
+		serde_json::from_str::<MachineSnapshot>(&json).unwrap(),
+		snapshot
+	);
+}
+
+#[cfg(feature = "with-codec")]
+#[test]
+fn codec_round_trip() {
+	use codec::{Decode, Encode};
+
+	let mut machine = machine(COUNTDOWN);
+	step(&mut machine, 6);
+	let snapshot = machine.snapshot();
+
+	let encoded = snapshot.encode();
+	assert_eq!(
+		MachineSnapshot::decode(&mut &encoded[..]).unwrap(),
+		snapshot
+	);
+}
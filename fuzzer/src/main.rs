@@ -1,5 +1,5 @@
 use evm_core::Machine;
-use std::rc::Rc;
+use std::sync::Arc;
 
 fn find_subsequence(haystack: &[u8], needle: &[u8]) -> Option<usize> {
 	return haystack
@@ -27,7 +27,7 @@ fn handle_data(sequence: &[u8]) {
 	let (code, data) = split_at_delim(sequence, vec![0xde, 0xad, 0xbe, 0xef].as_slice());
 	let stack_limit = 1024;
 	let memory_limit = 10000;
-	let mut vm = Machine::new(Rc::new(code), Rc::new(data), stack_limit, memory_limit);
+	let mut vm = Machine::new(Arc::new(code), Arc::new(data), stack_limit, memory_limit);
 	let res = vm.run();
 	#[cfg(not(fuzzing))]
 	{
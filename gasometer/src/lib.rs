@@ -25,8 +25,13 @@ macro_rules! event {
 mod consts;
 mod costs;
 mod memory;
+mod resource;
+mod schedule;
 mod utils;
 
+pub use crate::resource::{ResourceMeter, ResourceUsage};
+pub use crate::schedule::{Ethereum, GasSchedule};
+
 use alloc::vec::Vec;
 use core::cmp::max;
 use evm_core::{ExitError, Opcode, Stack};
@@ -59,20 +64,33 @@ pub struct Snapshot {
 pub struct Gasometer<'config> {
 	gas_limit: u64,
 	config: &'config Config,
+	schedule: &'config dyn GasSchedule,
 	inner: Result<Inner<'config>, ExitError>,
 }
 
 impl<'config> Gasometer<'config> {
-	/// Create a new gasometer with given gas limit and config.
+	/// Create a new gasometer with given gas limit and config, using the
+	/// Ethereum gas schedule.
 	pub fn new(gas_limit: u64, config: &'config Config) -> Self {
+		Self::new_with_schedule(gas_limit, config, &Ethereum)
+	}
+
+	/// Create a new gasometer with given gas limit, config and gas schedule.
+	pub fn new_with_schedule(
+		gas_limit: u64,
+		config: &'config Config,
+		schedule: &'config dyn GasSchedule,
+	) -> Self {
 		Self {
 			gas_limit,
 			config,
+			schedule,
 			inner: Ok(Inner {
 				memory_gas: 0,
 				used_gas: 0,
 				refunded_gas: 0,
 				config,
+				schedule,
 			}),
 		}
 	}
@@ -97,6 +115,12 @@ impl<'config> Gasometer<'config> {
 		self.config
 	}
 
+	#[inline]
+	/// Reference of the gas schedule.
+	pub fn schedule(&self) -> &'config dyn GasSchedule {
+		self.schedule
+	}
+
 	#[inline]
 	/// Remaining gas.
 	pub fn gas(&self) -> u64 {
@@ -163,7 +187,7 @@ impl<'config> Gasometer<'config> {
 	#[inline]
 	/// Record `CREATE` code deposit.
 	pub fn record_deposit(&mut self, len: usize) -> Result<(), ExitError> {
-		let cost = len as u64 * consts::G_CODEDEPOSIT;
+		let cost = self.schedule.code_deposit_cost(len);
 		self.record_cost(cost)
 	}
 
@@ -445,34 +469,39 @@ pub fn dynamic_opcode_cost<H: Handler>(
 ) -> Result<(GasCost, StorageTarget, Option<MemoryCost>), ExitError> {
 	let mut storage_target = StorageTarget::None;
 	let gas_cost = match opcode {
-		Opcode::RETURN => GasCost::Zero,
+		Opcode::RETURN => GasCost::Zero(opcode),
 
-		Opcode::MLOAD | Opcode::MSTORE | Opcode::MSTORE8 => GasCost::VeryLow,
+		Opcode::MLOAD | Opcode::MSTORE | Opcode::MSTORE8 => GasCost::VeryLow(opcode),
 
-		Opcode::REVERT if config.has_revert => GasCost::Zero,
+		Opcode::REVERT if config.has_revert => GasCost::Zero(opcode),
 		Opcode::REVERT => GasCost::Invalid(opcode),
 
-		Opcode::CHAINID if config.has_chain_id => GasCost::Base,
+		Opcode::CHAINID if config.has_chain_id => GasCost::Base(opcode),
 		Opcode::CHAINID => GasCost::Invalid(opcode),
 
-		Opcode::SHL | Opcode::SHR | Opcode::SAR if config.has_bitwise_shifting => GasCost::VeryLow,
+		Opcode::SHL | Opcode::SHR | Opcode::SAR if config.has_bitwise_shifting => {
+			GasCost::VeryLow(opcode)
+		}
 		Opcode::SHL | Opcode::SHR | Opcode::SAR => GasCost::Invalid(opcode),
 
-		Opcode::SELFBALANCE if config.has_self_balance => GasCost::Low,
+		Opcode::SELFBALANCE if config.has_self_balance => GasCost::Low(opcode),
 		Opcode::SELFBALANCE => GasCost::Invalid(opcode),
 
-		Opcode::BASEFEE if config.has_base_fee => GasCost::Base,
+		Opcode::BASEFEE if config.has_base_fee => GasCost::Base(opcode),
 		Opcode::BASEFEE => GasCost::Invalid(opcode),
 
+		Opcode::PUSH0 if config.has_push0 => GasCost::Base(opcode),
+		Opcode::PUSH0 => GasCost::Invalid(opcode),
+
 		Opcode::EXTCODESIZE => {
-			let target = stack.peek(0)?.into();
+			let target = stack.peek_h256(0)?.into();
 			storage_target = StorageTarget::Address(target);
 			GasCost::ExtCodeSize {
 				target_is_cold: handler.is_cold(target, None),
 			}
 		}
 		Opcode::BALANCE => {
-			let target = stack.peek(0)?.into();
+			let target = stack.peek_h256(0)?.into();
 			storage_target = StorageTarget::Address(target);
 			GasCost::Balance {
 				target_is_cold: handler.is_cold(target, None),
@@ -481,7 +510,7 @@ pub fn dynamic_opcode_cost<H: Handler>(
 		Opcode::BLOCKHASH => GasCost::BlockHash,
 
 		Opcode::EXTCODEHASH if config.has_ext_code_hash => {
-			let target = stack.peek(0)?.into();
+			let target = stack.peek_h256(0)?.into();
 			storage_target = StorageTarget::Address(target);
 			GasCost::ExtCodeHash {
 				target_is_cold: handler.is_cold(target, None),
@@ -490,43 +519,44 @@ pub fn dynamic_opcode_cost<H: Handler>(
 		Opcode::EXTCODEHASH => GasCost::Invalid(opcode),
 
 		Opcode::CALLCODE => {
-			let target = stack.peek(1)?.into();
+			let target = stack.peek_h256(1)?.into();
 			storage_target = StorageTarget::Address(target);
 			GasCost::CallCode {
-				value: U256::from_big_endian(&stack.peek(2)?[..]),
-				gas: U256::from_big_endian(&stack.peek(0)?[..]),
+				value: stack.peek(2)?,
+				gas: stack.peek(0)?,
 				target_is_cold: handler.is_cold(target, None),
 				target_exists: handler.exists(target),
 			}
 		}
-		Opcode::STATICCALL => {
-			let target = stack.peek(1)?.into();
+		Opcode::STATICCALL if config.has_static_call => {
+			let target = stack.peek_h256(1)?.into();
 			storage_target = StorageTarget::Address(target);
 			GasCost::StaticCall {
-				gas: U256::from_big_endian(&stack.peek(0)?[..]),
+				gas: stack.peek(0)?,
 				target_is_cold: handler.is_cold(target, None),
 				target_exists: handler.exists(target),
 			}
 		}
+		Opcode::STATICCALL => GasCost::Invalid(opcode),
 		Opcode::SHA3 => GasCost::Sha3 {
-			len: U256::from_big_endian(&stack.peek(1)?[..]),
+			len: stack.peek(1)?,
 		},
 		Opcode::EXTCODECOPY => {
-			let target = stack.peek(0)?.into();
+			let target = stack.peek_h256(0)?.into();
 			storage_target = StorageTarget::Address(target);
 			GasCost::ExtCodeCopy {
 				target_is_cold: handler.is_cold(target, None),
-				len: U256::from_big_endian(&stack.peek(3)?[..]),
+				len: stack.peek(3)?,
 			}
 		}
 		Opcode::CALLDATACOPY | Opcode::CODECOPY => GasCost::VeryLowCopy {
-			len: U256::from_big_endian(&stack.peek(2)?[..]),
+			len: stack.peek(2)?,
 		},
 		Opcode::EXP => GasCost::Exp {
-			power: U256::from_big_endian(&stack.peek(1)?[..]),
+			power: stack.peek(1)?,
 		},
 		Opcode::SLOAD => {
-			let index = stack.peek(0)?;
+			let index = stack.peek_h256(0)?;
 			storage_target = StorageTarget::Slot(address, index);
 			GasCost::SLoad {
 				target_is_cold: handler.is_cold(address, Some(index)),
@@ -534,25 +564,25 @@ pub fn dynamic_opcode_cost<H: Handler>(
 		}
 
 		Opcode::DELEGATECALL if config.has_delegate_call => {
-			let target = stack.peek(1)?.into();
+			let target = stack.peek_h256(1)?.into();
 			storage_target = StorageTarget::Address(target);
 			GasCost::DelegateCall {
-				gas: U256::from_big_endian(&stack.peek(0)?[..]),
+				gas: stack.peek(0)?,
 				target_is_cold: handler.is_cold(target, None),
 				target_exists: handler.exists(target),
 			}
 		}
 		Opcode::DELEGATECALL => GasCost::Invalid(opcode),
 
-		Opcode::RETURNDATASIZE if config.has_return_data => GasCost::Base,
+		Opcode::RETURNDATASIZE if config.has_return_data => GasCost::Base(opcode),
 		Opcode::RETURNDATACOPY if config.has_return_data => GasCost::VeryLowCopy {
-			len: U256::from_big_endian(&stack.peek(2)?[..]),
+			len: stack.peek(2)?,
 		},
 		Opcode::RETURNDATASIZE | Opcode::RETURNDATACOPY => GasCost::Invalid(opcode),
 
 		Opcode::SSTORE if !is_static => {
-			let index = stack.peek(0)?;
-			let value = stack.peek(1)?;
+			let index = stack.peek_h256(0)?;
+			let value = stack.peek_h256(1)?;
 			storage_target = StorageTarget::Slot(address, index);
 
 			GasCost::SStore {
@@ -564,30 +594,30 @@ pub fn dynamic_opcode_cost<H: Handler>(
 		}
 		Opcode::LOG0 if !is_static => GasCost::Log {
 			n: 0,
-			len: U256::from_big_endian(&stack.peek(1)?[..]),
+			len: stack.peek(1)?,
 		},
 		Opcode::LOG1 if !is_static => GasCost::Log {
 			n: 1,
-			len: U256::from_big_endian(&stack.peek(1)?[..]),
+			len: stack.peek(1)?,
 		},
 		Opcode::LOG2 if !is_static => GasCost::Log {
 			n: 2,
-			len: U256::from_big_endian(&stack.peek(1)?[..]),
+			len: stack.peek(1)?,
 		},
 		Opcode::LOG3 if !is_static => GasCost::Log {
 			n: 3,
-			len: U256::from_big_endian(&stack.peek(1)?[..]),
+			len: stack.peek(1)?,
 		},
 		Opcode::LOG4 if !is_static => GasCost::Log {
 			n: 4,
-			len: U256::from_big_endian(&stack.peek(1)?[..]),
+			len: stack.peek(1)?,
 		},
 		Opcode::CREATE if !is_static => GasCost::Create,
 		Opcode::CREATE2 if !is_static && config.has_create2 => GasCost::Create2 {
-			len: U256::from_big_endian(&stack.peek(2)?[..]),
+			len: stack.peek(2)?,
 		},
 		Opcode::SUICIDE if !is_static => {
-			let target = stack.peek(0)?.into();
+			let target = stack.peek_h256(0)?.into();
 			storage_target = StorageTarget::Address(target);
 			GasCost::Suicide {
 				value: handler.balance(address),
@@ -596,21 +626,24 @@ pub fn dynamic_opcode_cost<H: Handler>(
 				already_removed: handler.deleted(address),
 			}
 		}
-		Opcode::CALL
-			if !is_static
-				|| (is_static && U256::from_big_endian(&stack.peek(2)?[..]) == U256::zero()) =>
-		{
-			let target = stack.peek(1)?.into();
+		Opcode::CALL if !is_static || (is_static && stack.peek(2)? == U256::zero()) => {
+			let target = stack.peek_h256(1)?.into();
 			storage_target = StorageTarget::Address(target);
 			GasCost::Call {
-				value: U256::from_big_endian(&stack.peek(2)?[..]),
-				gas: U256::from_big_endian(&stack.peek(0)?[..]),
+				value: stack.peek(2)?,
+				gas: stack.peek(0)?,
 				target_is_cold: handler.is_cold(target, None),
 				target_exists: handler.exists(target),
 			}
 		}
 
-		_ => GasCost::Invalid(opcode),
+		_ => match config.custom_opcodes.get(opcode) {
+			Some(custom) => GasCost::Custom {
+				opcode,
+				gas: custom.gas,
+			},
+			None => GasCost::Invalid(opcode),
+		},
 	};
 
 	let memory_cost = match opcode {
@@ -622,54 +655,54 @@ pub fn dynamic_opcode_cost<H: Handler>(
 		| Opcode::LOG2
 		| Opcode::LOG3
 		| Opcode::LOG4 => Some(MemoryCost {
-			offset: U256::from_big_endian(&stack.peek(0)?[..]),
-			len: U256::from_big_endian(&stack.peek(1)?[..]),
+			offset: stack.peek(0)?,
+			len: stack.peek(1)?,
 		}),
 
 		Opcode::CODECOPY | Opcode::CALLDATACOPY | Opcode::RETURNDATACOPY => Some(MemoryCost {
-			offset: U256::from_big_endian(&stack.peek(0)?[..]),
-			len: U256::from_big_endian(&stack.peek(2)?[..]),
+			offset: stack.peek(0)?,
+			len: stack.peek(2)?,
 		}),
 
 		Opcode::EXTCODECOPY => Some(MemoryCost {
-			offset: U256::from_big_endian(&stack.peek(1)?[..]),
-			len: U256::from_big_endian(&stack.peek(3)?[..]),
+			offset: stack.peek(1)?,
+			len: stack.peek(3)?,
 		}),
 
 		Opcode::MLOAD | Opcode::MSTORE => Some(MemoryCost {
-			offset: U256::from_big_endian(&stack.peek(0)?[..]),
+			offset: stack.peek(0)?,
 			len: U256::from(32),
 		}),
 
 		Opcode::MSTORE8 => Some(MemoryCost {
-			offset: U256::from_big_endian(&stack.peek(0)?[..]),
+			offset: stack.peek(0)?,
 			len: U256::from(1),
 		}),
 
 		Opcode::CREATE | Opcode::CREATE2 => Some(MemoryCost {
-			offset: U256::from_big_endian(&stack.peek(1)?[..]),
-			len: U256::from_big_endian(&stack.peek(2)?[..]),
+			offset: stack.peek(1)?,
+			len: stack.peek(2)?,
 		}),
 
 		Opcode::CALL | Opcode::CALLCODE => Some(
 			MemoryCost {
-				offset: U256::from_big_endian(&stack.peek(3)?[..]),
-				len: U256::from_big_endian(&stack.peek(4)?[..]),
+				offset: stack.peek(3)?,
+				len: stack.peek(4)?,
 			}
 			.join(MemoryCost {
-				offset: U256::from_big_endian(&stack.peek(5)?[..]),
-				len: U256::from_big_endian(&stack.peek(6)?[..]),
+				offset: stack.peek(5)?,
+				len: stack.peek(6)?,
 			}),
 		),
 
 		Opcode::DELEGATECALL | Opcode::STATICCALL => Some(
 			MemoryCost {
-				offset: U256::from_big_endian(&stack.peek(2)?[..]),
-				len: U256::from_big_endian(&stack.peek(3)?[..]),
+				offset: stack.peek(2)?,
+				len: stack.peek(3)?,
 			}
 			.join(MemoryCost {
-				offset: U256::from_big_endian(&stack.peek(4)?[..]),
-				len: U256::from_big_endian(&stack.peek(5)?[..]),
+				offset: stack.peek(4)?,
+				len: stack.peek(5)?,
 			}),
 		),
 
@@ -686,6 +719,7 @@ struct Inner<'config> {
 	used_gas: u64,
 	refunded_gas: i64,
 	config: &'config Config,
+	schedule: &'config dyn GasSchedule,
 }
 
 impl<'config> Inner<'config> {
@@ -707,7 +741,7 @@ impl<'config> Inner<'config> {
 		let rem = end % 32;
 		let new = if rem == 0 { end / 32 } else { end / 32 + 1 };
 
-		Ok(max(self.memory_gas, memory::memory_gas(new)?))
+		Ok(max(self.memory_gas, self.schedule.memory_gas(new)?))
 	}
 
 	fn extra_check(&self, cost: GasCost, after_gas: u64) -> Result<(), ExitError> {
@@ -724,135 +758,38 @@ impl<'config> Inner<'config> {
 
 	/// Returns the gas cost numerical value.
 	fn gas_cost(&self, cost: GasCost, gas: u64) -> Result<u64, ExitError> {
-		Ok(match cost {
-			GasCost::Call {
-				value,
-				target_is_cold,
-				target_exists,
-				..
-			} => costs::call_cost(
-				value,
-				target_is_cold,
-				true,
-				true,
-				!target_exists,
-				self.config,
-			),
-			GasCost::CallCode {
-				value,
-				target_is_cold,
-				target_exists,
-				..
-			} => costs::call_cost(
-				value,
-				target_is_cold,
-				true,
-				false,
-				!target_exists,
-				self.config,
-			),
-			GasCost::DelegateCall {
-				target_is_cold,
-				target_exists,
-				..
-			} => costs::call_cost(
-				U256::zero(),
-				target_is_cold,
-				false,
-				false,
-				!target_exists,
-				self.config,
-			),
-			GasCost::StaticCall {
-				target_is_cold,
-				target_exists,
-				..
-			} => costs::call_cost(
-				U256::zero(),
-				target_is_cold,
-				false,
-				true,
-				!target_exists,
-				self.config,
-			),
-
-			GasCost::Suicide {
-				value,
-				target_is_cold,
-				target_exists,
-				..
-			} => costs::suicide_cost(value, target_is_cold, target_exists, self.config),
-			GasCost::SStore {
-				original,
-				current,
-				new,
-				target_is_cold,
-			} => costs::sstore_cost(original, current, new, gas, target_is_cold, self.config)?,
-
-			GasCost::Sha3 { len } => costs::sha3_cost(len)?,
-			GasCost::Log { n, len } => costs::log_cost(n, len)?,
-			GasCost::VeryLowCopy { len } => costs::verylowcopy_cost(len)?,
-			GasCost::Exp { power } => costs::exp_cost(power, self.config)?,
-			GasCost::Create => consts::G_CREATE,
-			GasCost::Create2 { len } => costs::create2_cost(len)?,
-			GasCost::SLoad { target_is_cold } => costs::sload_cost(target_is_cold, self.config),
-
-			GasCost::Zero => consts::G_ZERO,
-			GasCost::Base => consts::G_BASE,
-			GasCost::VeryLow => consts::G_VERYLOW,
-			GasCost::Low => consts::G_LOW,
-			GasCost::Invalid(opcode) => return Err(ExitError::InvalidCode(opcode)),
-
-			GasCost::ExtCodeSize { target_is_cold } => {
-				costs::address_access_cost(target_is_cold, self.config.gas_ext_code, self.config)
-			}
-			GasCost::ExtCodeCopy {
-				target_is_cold,
-				len,
-			} => costs::extcodecopy_cost(len, target_is_cold, self.config)?,
-			GasCost::Balance { target_is_cold } => {
-				costs::address_access_cost(target_is_cold, self.config.gas_balance, self.config)
-			}
-			GasCost::BlockHash => consts::G_BLOCKHASH,
-			GasCost::ExtCodeHash { target_is_cold } => costs::address_access_cost(
-				target_is_cold,
-				self.config.gas_ext_code_hash,
-				self.config,
-			),
-		})
+		self.schedule.gas_cost(cost, gas, self.config)
 	}
 
 	fn gas_refund(&self, cost: GasCost) -> i64 {
-		match cost {
-			_ if self.config.estimate => 0,
-
-			GasCost::SStore {
-				original,
-				current,
-				new,
-				..
-			} => costs::sstore_refund(original, current, new, self.config),
-			GasCost::Suicide {
-				already_removed, ..
-			} if !self.config.decrease_clears_refund => costs::suicide_refund(already_removed),
-			_ => 0,
+		if self.config.estimate {
+			return 0;
 		}
+
+		self.schedule.gas_refund(cost, self.config)
 	}
 }
 
 /// Gas cost.
 #[derive(Debug, Clone, Copy)]
 pub enum GasCost {
-	/// Zero gas cost.
-	Zero,
-	/// Base gas cost.
-	Base,
-	/// Very low gas cost.
-	VeryLow,
-	/// Low gas cost.
-	Low,
+	/// Zero gas cost of the opcode.
+	Zero(Opcode),
+	/// Base gas cost of the opcode.
+	Base(Opcode),
+	/// Very low gas cost of the opcode.
+	VeryLow(Opcode),
+	/// Low gas cost of the opcode.
+	Low(Opcode),
 	/// Fail the gasometer.
 	Invalid(Opcode),
+	/// Gas cost of a custom opcode.
+	Custom {
+		/// The custom opcode.
+		opcode: Opcode,
+		/// Gas declared when registering it.
+		gas: u64,
+	},
 
 	/// Gas cost for `EXTCODESIZE`.
 	ExtCodeSize {
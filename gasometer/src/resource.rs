@@ -0,0 +1,217 @@
+use crate::GasCost;
+use core::cmp::min;
+use evm_core::ExitError;
+use primitive_types::{H256, U256};
+
+/// Usage of each metered resource. Also used for resource limits.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ResourceUsage {
+	/// Gas of opcodes and precompiles, other than state access opcodes.
+	pub compute: u64,
+	/// Number of storage slots and accounts read.
+	pub state_reads: u64,
+	/// Number of storage slots written.
+	pub state_writes: u64,
+	/// Bytes of new state: 32 bytes per storage slot set from zero, and
+	/// deployed code. A slot set from zero and cleared again in the same
+	/// transaction, other than by a reverted sub-call, is not new state.
+	pub new_state_bytes: u64,
+	/// Bytes of transaction data or init code.
+	pub calldata_bytes: u64,
+	/// Bytes of log data and topics.
+	pub log_bytes: u64,
+}
+
+impl ResourceUsage {
+	/// Usage without limits.
+	pub const fn unlimited() -> Self {
+		Self {
+			compute: u64::MAX,
+			state_reads: u64::MAX,
+			state_writes: u64::MAX,
+			new_state_bytes: u64::MAX,
+			calldata_bytes: u64::MAX,
+			log_bytes: u64::MAX,
+		}
+	}
+
+	/// Usage of an opcode with the given dynamic cost, charged `gas`.
+	pub fn of_cost(cost: GasCost, gas: u64) -> Self {
+		match cost {
+			GasCost::SLoad { .. }
+			| GasCost::Balance { .. }
+			| GasCost::ExtCodeSize { .. }
+			| GasCost::ExtCodeCopy { .. }
+			| GasCost::ExtCodeHash { .. } => Self {
+				state_reads: 1,
+				..Self::default()
+			},
+			GasCost::SStore {
+				original,
+				current,
+				new,
+				..
+			} => Self {
+				state_writes: 1,
+				new_state_bytes: if original == H256::default()
+					&& current == H256::default()
+					&& new != H256::default()
+				{
+					32
+				} else {
+					0
+				},
+				..Self::default()
+			},
+			GasCost::Log { n, len } => Self {
+				compute: gas,
+				log_bytes: saturating_u64(len).saturating_add(32 * n as u64),
+				..Self::default()
+			},
+			_ => Self {
+				compute: gas,
+				..Self::default()
+			},
+		}
+	}
+
+	/// Bytes of new state released by an opcode with the given dynamic cost,
+	/// which clears a slot set from zero in the same transaction.
+	pub fn released_state_bytes(cost: GasCost) -> u64 {
+		match cost {
+			GasCost::SStore {
+				original,
+				current,
+				new,
+				..
+			} if original == H256::default()
+				&& current != H256::default()
+				&& new == H256::default() =>
+			{
+				32
+			}
+			_ => 0,
+		}
+	}
+
+	/// Add the usage of each resource.
+	pub fn saturating_add(self, other: Self) -> Self {
+		Self {
+			compute: self.compute.saturating_add(other.compute),
+			state_reads: self.state_reads.saturating_add(other.state_reads),
+			state_writes: self.state_writes.saturating_add(other.state_writes),
+			new_state_bytes: self.new_state_bytes.saturating_add(other.new_state_bytes),
+			calldata_bytes: self.calldata_bytes.saturating_add(other.calldata_bytes),
+			log_bytes: self.log_bytes.saturating_add(other.log_bytes),
+		}
+	}
+
+	/// Subtract the usage of each resource.
+	pub fn saturating_sub(self, other: Self) -> Self {
+		Self {
+			compute: self.compute.saturating_sub(other.compute),
+			state_reads: self.state_reads.saturating_sub(other.state_reads),
+			state_writes: self.state_writes.saturating_sub(other.state_writes),
+			new_state_bytes: self.new_state_bytes.saturating_sub(other.new_state_bytes),
+			calldata_bytes: self.calldata_bytes.saturating_sub(other.calldata_bytes),
+			log_bytes: self.log_bytes.saturating_sub(other.log_bytes),
+		}
+	}
+
+	/// Whether no resource is over the given limits.
+	pub fn is_within(&self, limits: &Self) -> bool {
+		self.compute <= limits.compute
+			&& self.state_reads <= limits.state_reads
+			&& self.state_writes <= limits.state_writes
+			&& self.new_state_bytes <= limits.new_state_bytes
+			&& self.calldata_bytes <= limits.calldata_bytes
+			&& self.log_bytes <= limits.log_bytes
+	}
+}
+
+/// Meter of resource usage alongside gas, with a limit for each resource.
+///
+/// Sub-calls are metered by a child meter limited to the remaining
+/// resources. Like gas, the usage of a reverted or failed sub-call is kept
+/// in full, though its state changes are discarded. Like gas refunds, the
+/// new state it released is not released from the parent.
+#[derive(Clone, Debug)]
+pub struct ResourceMeter {
+	limits: ResourceUsage,
+	used: ResourceUsage,
+	/// Bytes of new state released beyond the new state used by this meter,
+	/// which are released from the parent meter on commit.
+	released: u64,
+}
+
+impl ResourceMeter {
+	/// Create a new meter with the given limits.
+	pub fn new(limits: ResourceUsage) -> Self {
+		Self {
+			limits,
+			used: ResourceUsage::default(),
+			released: 0,
+		}
+	}
+
+	/// Resource limits.
+	pub fn limits(&self) -> ResourceUsage {
+		self.limits
+	}
+
+	/// Resources used.
+	pub fn used(&self) -> ResourceUsage {
+		self.used
+	}
+
+	/// Record resource usage. Return `OutOfResource` if it goes over a limit,
+	/// in which case nothing is recorded.
+	pub fn record(&mut self, usage: ResourceUsage) -> Result<(), ExitError> {
+		let used = self.used.saturating_add(usage);
+		if !used.is_within(&self.limits) {
+			return Err(ExitError::OutOfResource);
+		}
+
+		self.used = used;
+		Ok(())
+	}
+
+	/// Record resource usage of an opcode with the given dynamic cost,
+	/// charged `gas`.
+	pub fn record_cost(&mut self, cost: GasCost, gas: u64) -> Result<(), ExitError> {
+		self.record(ResourceUsage::of_cost(cost, gas))?;
+		self.release_state_bytes(ResourceUsage::released_state_bytes(cost));
+		Ok(())
+	}
+
+	/// Release bytes of new state recorded earlier in the transaction.
+	pub fn release_state_bytes(&mut self, bytes: u64) {
+		let released = min(bytes, self.used.new_state_bytes);
+		self.used.new_state_bytes -= released;
+		self.released = self.released.saturating_add(bytes - released);
+	}
+
+	/// Create the meter of a sub-call.
+	pub fn spit_child(&self) -> Self {
+		Self::new(self.limits.saturating_sub(self.used))
+	}
+
+	/// Merge the meter of a committed sub-call.
+	pub fn swallow_commit(&mut self, other: Self) {
+		self.used = self.used.saturating_add(other.used);
+		self.release_state_bytes(other.released);
+	}
+
+	/// Merge the meter of a reverted or failed sub-call.
+	pub fn swallow_revert(&mut self, other: Self) {
+		self.used = self.used.saturating_add(other.used);
+	}
+}
+
+fn saturating_u64(value: U256) -> u64 {
+	if value > U256::from(u64::MAX) {
+		u64::MAX
+	} else {
+		value.as_u64()
+	}
+}
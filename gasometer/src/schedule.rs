@@ -0,0 +1,234 @@
+use crate::{consts, costs, memory, GasCost};
+use core::fmt::Debug;
+use evm_core::{ExitError, Opcode};
+use evm_runtime::Config;
+use primitive_types::U256;
+
+/// Pricing of opcodes and memory used by `Gasometer`.
+///
+/// Every method defaults to the Ethereum pricing, so that a schedule only
+/// needs to override what it changes. Opcodes with a dynamic cost are priced
+/// from their `GasCost`, as computed by `dynamic_opcode_cost`. The fixed
+/// tiers (`GasCost::Zero`, `Base`, `VeryLow` and `Low`) carry their opcode,
+/// so that a schedule can price each of them on its own.
+pub trait GasSchedule: Debug {
+	/// Static cost of an opcode, or `None` if its cost is dynamic.
+	fn static_opcode_cost(&self, opcode: Opcode) -> Option<u64> {
+		crate::static_opcode_cost(opcode)
+	}
+
+	/// Numerical value of a dynamic cost, with `gas` remaining before it is
+	/// recorded.
+	fn gas_cost(&self, cost: GasCost, gas: u64, config: &Config) -> Result<u64, ExitError> {
+		Ok(match cost {
+			GasCost::Call {
+				value,
+				target_is_cold,
+				target_exists,
+				..
+			} => costs::call_cost(value, target_is_cold, true, true, !target_exists, config),
+			GasCost::CallCode {
+				value,
+				target_is_cold,
+				target_exists,
+				..
+			} => costs::call_cost(value, target_is_cold, true, false, !target_exists, config),
+			GasCost::DelegateCall {
+				target_is_cold,
+				target_exists,
+				..
+			} => costs::call_cost(
+				U256::zero(),
+				target_is_cold,
+				false,
+				false,
+				!target_exists,
+				config,
+			),
+			GasCost::StaticCall {
+				target_is_cold,
+				target_exists,
+				..
+			} => costs::call_cost(
+				U256::zero(),
+				target_is_cold,
+				false,
+				true,
+				!target_exists,
+				config,
+			),
+
+			GasCost::Suicide {
+				value,
+				target_is_cold,
+				target_exists,
+				..
+			} => costs::suicide_cost(value, target_is_cold, target_exists, config),
+			GasCost::SStore {
+				original,
+				current,
+				new,
+				target_is_cold,
+			} => costs::sstore_cost(original, current, new, gas, target_is_cold, config)?,
+
+			GasCost::Sha3 { len } => costs::sha3_cost(len)?,
+			GasCost::Log { n, len } => costs::log_cost(n, len)?,
+			GasCost::VeryLowCopy { len } => costs::verylowcopy_cost(len)?,
+			GasCost::Exp { power } => costs::exp_cost(power, config)?,
+			GasCost::Create => consts::G_CREATE,
+			GasCost::Create2 { len } => costs::create2_cost(len)?,
+			GasCost::SLoad { target_is_cold } => costs::sload_cost(target_is_cold, config),
+
+			GasCost::Zero(_) => consts::G_ZERO,
+			GasCost::Base(_) => consts::G_BASE,
+			GasCost::VeryLow(_) => consts::G_VERYLOW,
+			GasCost::Low(_) => consts::G_LOW,
+			GasCost::Invalid(opcode) => return Err(ExitError::InvalidCode(opcode)),
+			GasCost::Custom { gas, .. } => gas,
+
+			GasCost::ExtCodeSize { target_is_cold } => {
+				costs::address_access_cost(target_is_cold, config.gas_ext_code, config)
+			}
+			GasCost::ExtCodeCopy {
+				target_is_cold,
+				len,
+			} => costs::extcodecopy_cost(len, target_is_cold, config)?,
+			GasCost::Balance { target_is_cold } => {
+				costs::address_access_cost(target_is_cold, config.gas_balance, config)
+			}
+			GasCost::BlockHash => consts::G_BLOCKHASH,
+			GasCost::ExtCodeHash { target_is_cold } => {
+				costs::address_access_cost(target_is_cold, config.gas_ext_code_hash, config)
+			}
+		})
+	}
+
+	/// Refund of a dynamic cost.
+	fn gas_refund(&self, cost: GasCost, config: &Config) -> i64 {
+		match cost {
+			GasCost::SStore {
+				original,
+				current,
+				new,
+				..
+			} => costs::sstore_refund(original, current, new, config),
+			GasCost::Suicide {
+				already_removed, ..
+			} if !config.decrease_clears_refund => costs::suicide_refund(already_removed),
+			_ => 0,
+		}
+	}
+
+	/// Total memory gas of `words` 32-byte words of memory.
+	fn memory_gas(&self, words: usize) -> Result<u64, ExitError> {
+		memory::memory_gas(words)
+	}
+
+	/// Cost of depositing `len` bytes of code at the end of a create.
+	fn code_deposit_cost(&self, len: usize) -> u64 {
+		len as u64 * consts::G_CODEDEPOSIT
+	}
+}
+
+/// Ethereum gas schedule, adjusted by the `Config` knobs.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ethereum;
+
+impl GasSchedule for Ethereum {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Gasometer, MemoryCost};
+	use primitive_types::H256;
+
+	/// Flat memory price, cheaper `MSTORE` and `CHAINID`, and a single price
+	/// for any `SSTORE` changing a slot.
+	#[derive(Debug)]
+	struct Appchain;
+
+	impl GasSchedule for Appchain {
+		fn static_opcode_cost(&self, opcode: Opcode) -> Option<u64> {
+			match opcode {
+				Opcode::ADD => Some(1),
+				_ => crate::static_opcode_cost(opcode),
+			}
+		}
+
+		fn gas_cost(&self, cost: GasCost, gas: u64, config: &Config) -> Result<u64, ExitError> {
+			match cost {
+				GasCost::VeryLow(Opcode::MSTORE) => Ok(1),
+				GasCost::Base(Opcode::CHAINID) => Ok(0),
+				GasCost::SStore { current, new, .. } if current == new => Ok(50),
+				GasCost::SStore { .. } => Ok(1_000),
+				_ => Ethereum.gas_cost(cost, gas, config),
+			}
+		}
+
+		fn gas_refund(&self, _cost: GasCost, _config: &Config) -> i64 {
+			0
+		}
+
+		fn memory_gas(&self, words: usize) -> Result<u64, ExitError> {
+			Ok(words as u64 * 10)
+		}
+	}
+
+	fn sstore(current: u64, new: u64) -> GasCost {
+		GasCost::SStore {
+			original: H256::from_low_u64_be(current),
+			current: H256::from_low_u64_be(current),
+			new: H256::from_low_u64_be(new),
+			target_is_cold: true,
+		}
+	}
+
+	fn memory(len: u64) -> Option<MemoryCost> {
+		Some(MemoryCost {
+			offset: U256::zero(),
+			len: U256::from(len),
+		})
+	}
+
+	#[test]
+	fn schedule_prices_recorded_gas() {
+		let config = Config::london();
+		let mut ethereum = Gasometer::new(100_000, &config);
+		let mut appchain = Gasometer::new_with_schedule(100_000, &config, &Appchain);
+
+		let mut record = |cost, memory, expected: (u64, u64)| {
+			let used = (ethereum.total_used_gas(), appchain.total_used_gas());
+			ethereum.record_dynamic_cost(cost, memory).unwrap();
+			appchain.record_dynamic_cost(cost, memory).unwrap();
+			assert_eq!(
+				(
+					ethereum.total_used_gas() - used.0,
+					appchain.total_used_gas() - used.1
+				),
+				expected,
+				"{:?}",
+				cost
+			);
+		};
+
+		// Opcode and memory expansion of 2 words.
+		record(
+			GasCost::VeryLow(Opcode::MSTORE),
+			memory(64),
+			(3 + 6, 1 + 20),
+		);
+		// Same price for other opcodes of the same tier.
+		record(GasCost::VeryLow(Opcode::MLOAD), memory(64), (3, 3));
+		record(GasCost::Base(Opcode::CHAINID), None, (2, 0));
+		record(GasCost::Base(Opcode::BASEFEE), None, (2, 2));
+
+		record(sstore(0, 1), None, (22_100, 1_000));
+		record(sstore(1, 1), None, (2_200, 50));
+		record(sstore(1, 0), None, (5_000, 1_000));
+		assert_eq!(ethereum.refunded_gas(), 4_800);
+		assert_eq!(appchain.refunded_gas(), 0);
+
+		assert_eq!(Ethereum.static_opcode_cost(Opcode::ADD), Some(3));
+		assert_eq!(appchain.schedule().static_opcode_cost(Opcode::ADD), Some(1));
+	}
+}
@@ -0,0 +1,248 @@
+use crate::{Config, Opcode};
+use alloc::collections::BTreeSet;
+
+/// Hard fork a `ConfigBuilder` starts from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Fork {
+	/// Frontier.
+	Frontier,
+	/// Homestead.
+	Homestead,
+	/// Tangerine Whistle.
+	TangerineWhistle,
+	/// Spurious Dragon.
+	SpuriousDragon,
+	/// Byzantium.
+	Byzantium,
+	/// Constantinople.
+	Constantinople,
+	/// Petersburg.
+	Petersburg,
+	/// Istanbul.
+	Istanbul,
+	/// Muir Glacier.
+	MuirGlacier,
+	/// Berlin.
+	Berlin,
+	/// London.
+	London,
+}
+
+impl Fork {
+	/// Configuration of the fork.
+	pub const fn config(&self) -> Config {
+		match self {
+			Self::Frontier => Config::frontier(),
+			Self::Homestead => Config::homestead(),
+			Self::TangerineWhistle => Config::tangerine_whistle(),
+			Self::SpuriousDragon => Config::spurious_dragon(),
+			Self::Byzantium => Config::byzantium(),
+			Self::Constantinople => Config::constantinople(),
+			Self::Petersburg => Config::petersburg(),
+			Self::Istanbul => Config::istanbul(),
+			Self::MuirGlacier => Config::muir_glacier(),
+			Self::Berlin => Config::berlin(),
+			Self::London => Config::london(),
+		}
+	}
+
+	/// EIPs supported by `ConfigBuilder` that are part of the fork.
+	pub fn eips(&self) -> &'static [u32] {
+		const SPURIOUS_DRAGON: &[u32] = &[7, 170];
+		const BYZANTIUM: &[u32] = &[7, 140, 170, 211, 214];
+		const CONSTANTINOPLE: &[u32] = &[7, 140, 145, 170, 211, 214, 1014, 1052];
+		const ISTANBUL: &[u32] = &[7, 140, 145, 170, 211, 214, 1014, 1052, 1344, 2200];
+		const BERLIN: &[u32] = &[
+			7, 140, 145, 170, 211, 214, 1014, 1052, 1344, 2200, 2929, 2930,
+		];
+		const LONDON: &[u32] = &[
+			7, 140, 145, 170, 211, 214, 1014, 1052, 1344, 2200, 2929, 2930, 3198, 3529, 3541,
+		];
+
+		match self {
+			Self::Frontier => &[],
+			Self::Homestead | Self::TangerineWhistle => &[7],
+			Self::SpuriousDragon => SPURIOUS_DRAGON,
+			Self::Byzantium => BYZANTIUM,
+			Self::Constantinople | Self::Petersburg => CONSTANTINOPLE,
+			Self::Istanbul | Self::MuirGlacier => ISTANBUL,
+			Self::Berlin => BERLIN,
+			Self::London => LONDON,
+		}
+	}
+}
+
+/// Errors when building a configuration.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConfigError {
+	/// The opcode is defined by the EVM, or is already registered.
+	UnavailableOpcode(Opcode),
+	/// The EIP is not supported by `ConfigBuilder`.
+	UnknownEip(u32),
+	/// An enabled EIP requires another EIP that is not enabled.
+	MissingDependency {
+		/// Enabled EIP.
+		eip: u32,
+		/// Required EIP.
+		requires: u32,
+	},
+}
+
+/// Supported EIPs, with the EIPs they require.
+const EIPS: [(u32, &[u32]); 16] = [
+	(7, &[]),
+	(140, &[]),
+	(145, &[]),
+	(170, &[]),
+	(211, &[]),
+	(214, &[]),
+	(1014, &[]),
+	(1052, &[]),
+	(1344, &[]),
+	(2200, &[]),
+	(2929, &[2200]),
+	(2930, &[2929]),
+	(3198, &[]),
+	(3529, &[2929]),
+	(3541, &[]),
+	(3855, &[]),
+];
+
+/// Builder of a `Config` from a base fork, with individual EIPs enabled or
+/// disabled on top of it.
+///
+/// Toggling an EIP only toggles the feature it introduces, and keeps the gas
+/// costs of the base fork, except for the gas changes of EIP-2929, EIP-2930
+/// and EIP-3529. Disabling EIP-2929 restores the Istanbul costs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConfigBuilder {
+	base: Fork,
+	#[cfg_attr(feature = "with-serde", serde(default))]
+	enable: BTreeSet<u32>,
+	#[cfg_attr(feature = "with-serde", serde(default))]
+	disable: BTreeSet<u32>,
+}
+
+impl ConfigBuilder {
+	/// Start from the configuration of `base`.
+	pub fn new(base: Fork) -> Self {
+		Self {
+			base,
+			enable: BTreeSet::new(),
+			disable: BTreeSet::new(),
+		}
+	}
+
+	/// Enable an EIP.
+	pub fn enable(mut self, eip: u32) -> Self {
+		self.disable.remove(&eip);
+		self.enable.insert(eip);
+		self
+	}
+
+	/// Disable an EIP.
+	pub fn disable(mut self, eip: u32) -> Self {
+		self.enable.remove(&eip);
+		self.disable.insert(eip);
+		self
+	}
+
+	/// Supported EIPs that the configuration includes.
+	pub fn eips(&self) -> Result<BTreeSet<u32>, ConfigError> {
+		if let Some(eip) = self
+			.enable
+			.iter()
+			.chain(&self.disable)
+			.find(|eip| !EIPS.iter().any(|(supported, _)| supported == *eip))
+		{
+			return Err(ConfigError::UnknownEip(*eip));
+		}
+
+		let eips = self
+			.base
+			.eips()
+			.iter()
+			.chain(&self.enable)
+			.filter(|eip| !self.disable.contains(eip))
+			.copied()
+			.collect::<BTreeSet<_>>();
+
+		for (eip, requires) in EIPS.iter() {
+			if !eips.contains(eip) {
+				continue;
+			}
+			if let Some(requires) = requires.iter().find(|eip| !eips.contains(eip)) {
+				return Err(ConfigError::MissingDependency {
+					eip: *eip,
+					requires: *requires,
+				});
+			}
+		}
+
+		Ok(eips)
+	}
+
+	/// Build the configuration.
+	pub fn build(&self) -> Result<Config, ConfigError> {
+		let eips = self.eips()?;
+		let mut config = self.base.config();
+
+		for eip in self.enable.iter().chain(&self.disable) {
+			apply(&mut config, *eip, eips.contains(eip));
+		}
+
+		Ok(config)
+	}
+}
+
+fn apply(config: &mut Config, eip: u32, enabled: bool) {
+	match eip {
+		7 => config.has_delegate_call = enabled,
+		140 => config.has_revert = enabled,
+		145 => config.has_bitwise_shifting = enabled,
+		170 => config.create_contract_limit = if enabled { Some(0x6000) } else { None },
+		211 => config.has_return_data = enabled,
+		214 => config.has_static_call = enabled,
+		1014 => config.has_create2 = enabled,
+		1052 => config.has_ext_code_hash = enabled,
+		1344 => config.has_chain_id = enabled,
+		2200 => {
+			config.sstore_gas_metering = enabled;
+			config.sstore_revert_under_stipend = enabled;
+		}
+		2929 => {
+			// Disabling restores the Istanbul costs.
+			let (cold, warm, account_cold, access) = if enabled {
+				(2100, 100, 2600, 0)
+			} else {
+				(0, 0, 0, 700)
+			};
+
+			config.increase_state_access_gas = enabled;
+			config.gas_sload_cold = cold;
+			config.gas_storage_read_warm = warm;
+			config.gas_account_access_cold = account_cold;
+			config.gas_sload = if enabled { warm } else { 800 };
+			config.gas_sstore_reset = 5000 - cold;
+			config.gas_ext_code = access;
+			config.gas_ext_code_hash = access;
+			config.gas_balance = access;
+			config.gas_call = access;
+		}
+		2930 => {
+			config.gas_access_list_address = if enabled { 2400 } else { 0 };
+			config.gas_access_list_storage_key = if enabled { 1900 } else { 0 };
+		}
+		3198 => config.has_base_fee = enabled,
+		3529 => {
+			config.decrease_clears_refund = enabled;
+			config.refund_sstore_clears = if enabled { 4800 } else { 15000 };
+			config.max_refund_quotient = if enabled { 5 } else { 2 };
+		}
+		3541 => config.disallow_executable_format = enabled,
+		3855 => config.has_push0 = enabled,
+		_ => (),
+	}
+}
@@ -0,0 +1,104 @@
+use crate::{ConfigError, ExitError, Machine, Opcode};
+
+/// Opcode added by a chain on top of the EVM instruction set.
+///
+/// Like `PUSH`, a custom opcode can be followed by immediate data, which is
+/// skipped by the jump destination analysis of `Valids`.
+#[derive(Clone, Copy, Debug)]
+pub struct CustomOpcode {
+	/// The opcode. It must not be defined by the EVM.
+	pub opcode: Opcode,
+	/// Gas charged before execution.
+	pub gas: u64,
+	/// Number of stack items popped.
+	pub inputs: usize,
+	/// Number of stack items pushed.
+	pub outputs: usize,
+	/// Number of bytes of immediate data following the opcode.
+	pub immediates: usize,
+	/// Execute the opcode. It is only called when the stack has `inputs`
+	/// items, and room for `outputs` items once they are popped. The machine
+	/// position is at the immediate data, which may be cut short by the end
+	/// of code, and is moved past it once the opcode succeeds.
+	pub eval: fn(&mut Machine) -> Result<(), ExitError>,
+}
+
+impl PartialEq for CustomOpcode {
+	fn eq(&self, other: &Self) -> bool {
+		// Handlers are compared by address.
+		self.opcode == other.opcode
+			&& self.gas == other.gas
+			&& self.inputs == other.inputs
+			&& self.outputs == other.outputs
+			&& self.immediates == other.immediates
+			&& self.eval as usize == other.eval as usize
+	}
+}
+
+impl Eq for CustomOpcode {}
+
+impl CustomOpcode {
+	/// Check the stack and execute the opcode, then skip its immediate data.
+	pub fn execute(&self, machine: &mut Machine) -> Result<(), ExitError> {
+		let stack = machine.stack();
+		if stack.len() < self.inputs {
+			return Err(ExitError::StackUnderflow);
+		}
+		if stack.len() - self.inputs + self.outputs > stack.limit() {
+			return Err(ExitError::StackOverflow);
+		}
+
+		(self.eval)(machine)?;
+		machine.advance(self.immediates);
+		Ok(())
+	}
+}
+
+/// Custom opcodes of a chain, executed by the runtime before falling back to
+/// `Handler::other`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct OpcodeRegistry(&'static [CustomOpcode]);
+
+impl OpcodeRegistry {
+	/// Registry without custom opcodes.
+	pub const fn empty() -> Self {
+		Self(&[])
+	}
+
+	/// Registry of the given opcodes. Opcodes defined by the EVM, or given
+	/// more than once, are rejected.
+	pub fn new(opcodes: &'static [CustomOpcode]) -> Result<Self, ConfigError> {
+		for (i, custom) in opcodes.iter().enumerate() {
+			if custom.opcode.name().is_some()
+				|| opcodes[..i]
+					.iter()
+					.any(|other| other.opcode == custom.opcode)
+			{
+				return Err(ConfigError::UnavailableOpcode(custom.opcode));
+			}
+		}
+
+		Ok(Self(opcodes))
+	}
+
+	/// Get a registered opcode.
+	pub fn get(&self, opcode: Opcode) -> Option<&'static CustomOpcode> {
+		self.0.iter().find(|custom| custom.opcode == opcode)
+	}
+
+	/// Length of the immediate data following an opcode, zero if it is not
+	/// registered.
+	pub fn immediates(&self, opcode: Opcode) -> usize {
+		self.get(opcode).map_or(0, |custom| custom.immediates)
+	}
+
+	/// Whether any registered opcode takes immediate data.
+	pub fn has_immediates(&self) -> bool {
+		self.0.iter().any(|custom| custom.immediates > 0)
+	}
+
+	/// Registered opcodes.
+	pub fn opcodes(&self) -> &'static [CustomOpcode] {
+		self.0
+	}
+}
@@ -10,7 +10,7 @@ macro_rules! try_or_fail {
 macro_rules! pop {
 	( $machine:expr, $( $x:ident ),* ) => (
 		$(
-			let $x = match $machine.machine.stack_mut().pop() {
+			let $x = match $machine.machine.stack_mut().pop_h256() {
 				Ok(value) => value,
 				Err(e) => return Control::Exit(e.into()),
 			};
@@ -22,7 +22,7 @@ macro_rules! pop_u256 {
 	( $machine:expr, $( $x:ident ),* ) => (
 		$(
 			let $x = match $machine.machine.stack_mut().pop() {
-				Ok(value) => U256::from_big_endian(&value[..]),
+				Ok(value) => value,
 				Err(e) => return Control::Exit(e.into()),
 			};
 		)*
@@ -32,7 +32,7 @@ macro_rules! pop_u256 {
 macro_rules! push {
 	( $machine:expr, $( $x:expr ),* ) => (
 		$(
-			match $machine.machine.stack_mut().push($x) {
+			match $machine.machine.stack_mut().push_h256($x) {
 				Ok(()) => (),
 				Err(e) => return Control::Exit(e.into()),
 			}
@@ -43,9 +43,7 @@ macro_rules! push {
 macro_rules! push_u256 {
 	( $machine:expr, $( $x:expr ),* ) => (
 		$(
-			let mut value = H256::default();
-			$x.to_big_endian(&mut value[..]);
-			match $machine.machine.stack_mut().push(value) {
+			match $machine.machine.stack_mut().push($x) {
 				Ok(()) => (),
 				Err(e) => return Control::Exit(e.into()),
 			}
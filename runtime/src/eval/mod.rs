@@ -12,7 +12,12 @@ pub enum Control<H: Handler> {
 }
 
 fn handle_other<H: Handler>(state: &mut Runtime, opcode: Opcode, handler: &mut H) -> Control<H> {
-	match handler.other(opcode, &mut state.machine) {
+	let result = match state.config.custom_opcodes.get(opcode) {
+		Some(custom) => custom.execute(&mut state.machine),
+		None => handler.other(opcode, &mut state.machine),
+	};
+
+	match result {
 		Ok(()) => Control::Continue,
 		Err(e) => Control::Exit(e.into()),
 	}
@@ -68,25 +68,19 @@ pub fn caller<H: Handler>(runtime: &mut Runtime) -> Control<H> {
 }
 
 pub fn callvalue<H: Handler>(runtime: &mut Runtime) -> Control<H> {
-	let mut ret = H256::default();
-	runtime.context.apparent_value.to_big_endian(&mut ret[..]);
-	push!(runtime, ret);
+	push_u256!(runtime, runtime.context.apparent_value);
 
 	Control::Continue
 }
 
 pub fn gasprice<H: Handler>(runtime: &mut Runtime, handler: &H) -> Control<H> {
-	let mut ret = H256::default();
-	handler.gas_price().to_big_endian(&mut ret[..]);
-	push!(runtime, ret);
+	push_u256!(runtime, handler.gas_price());
 
 	Control::Continue
 }
 
 pub fn base_fee<H: Handler>(runtime: &mut Runtime, handler: &H) -> Control<H> {
-	let mut ret = H256::default();
-	handler.block_base_fee_per_gas().to_big_endian(&mut ret[..]);
-	push!(runtime, ret);
+	push_u256!(runtime, handler.block_base_fee_per_gas());
 
 	Control::Continue
 }
@@ -241,7 +235,7 @@ pub fn log<H: Handler>(runtime: &mut Runtime, n: u8, handler: &mut H) -> Control
 
 	let mut topics = Vec::new();
 	for _ in 0..(n as usize) {
-		match runtime.machine.stack_mut().pop() {
+		match runtime.machine.stack_mut().pop_h256() {
 			Ok(value) => {
 				topics.push(value);
 			}
@@ -0,0 +1,137 @@
+use crate::{Context, ControlFlowGraph, Handler, Opcode, Valids};
+use alloc::{vec, vec::Vec};
+use core::cmp::min;
+use core::ops::Range;
+
+/// A run of opcodes whose gas cost is fully known before execution.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GasRun {
+	/// Position of the first opcode of the run.
+	pub start: usize,
+	/// Position right after the last opcode of the run.
+	pub end: usize,
+	/// Sum of the static costs of all opcodes in the run.
+	pub cost: u64,
+}
+
+/// Static gas costs of code, grouped so that they can be charged once at the
+/// entry of a run instead of once per opcode.
+///
+/// Runs never cross basic block boundaries. A run also ends right before any
+/// opcode without a static cost, since its cost depends on the state at the
+/// time it executes, and right after `GAS`, which observes the gas left.
+#[derive(Clone, Debug, Default)]
+pub struct GasBlocks {
+	runs: Vec<GasRun>,
+	/// Index of the run starting at each position, plus one, or zero.
+	run_starts: Vec<u32>,
+}
+
+impl GasBlocks {
+	/// Analyze given code bytes, using `static_cost` to get the static gas
+	/// cost of an opcode. `valids` must be the jump destination mapping of the
+	/// same code.
+	pub fn new<F: Fn(Opcode) -> Option<u64>>(code: &[u8], valids: &Valids, static_cost: F) -> Self {
+		Self::new_with_immediates(code, valids, static_cost, |_| 0)
+	}
+
+	/// Analyze given code bytes, where `immediates` gives the length of the
+	/// immediate data following opcodes other than `PUSH`, as for
+	/// `ControlFlowGraph::new_with_immediates`.
+	pub fn new_with_immediates<F, I>(
+		code: &[u8],
+		valids: &Valids,
+		static_cost: F,
+		immediates: I,
+	) -> Self
+	where
+		F: Fn(Opcode) -> Option<u64>,
+		I: Fn(Opcode) -> usize,
+	{
+		let cfg = ControlFlowGraph::new_with_immediates(code, valids, &immediates);
+		let mut runs = Vec::new();
+
+		for block in cfg.blocks() {
+			let mut run: Option<GasRun> = None;
+			let mut i = block.start;
+
+			while i < block.end {
+				let opcode = Opcode(code[i]);
+				let len = match opcode.is_push() {
+					Some(n) => n as usize,
+					None => immediates(opcode),
+				};
+				let next = min(i + 1 + len, block.end);
+
+				match static_cost(opcode) {
+					Some(cost) => {
+						let current = run.get_or_insert(GasRun {
+							start: i,
+							end: i,
+							cost: 0,
+						});
+						current.end = next;
+						current.cost = current.cost.saturating_add(cost);
+
+						if opcode == Opcode::GAS {
+							runs.extend(run.take());
+						}
+					}
+					None => runs.extend(run.take()),
+				}
+
+				i = next;
+			}
+
+			runs.extend(run);
+		}
+
+		let mut run_starts = vec![0; code.len()];
+		for (index, run) in runs.iter().enumerate() {
+			run_starts[run.start] = index as u32 + 1;
+		}
+
+		Self { runs, run_starts }
+	}
+
+	/// All runs, ordered by position.
+	pub fn runs(&self) -> &[GasRun] {
+		&self.runs
+	}
+
+	/// The run starting at the given position, if any.
+	pub fn run_at(&self, position: usize) -> Option<&GasRun> {
+		match self.run_starts.get(position) {
+			Some(&index) if index > 0 => Some(&self.runs[index as usize - 1]),
+			_ => None,
+		}
+	}
+
+	/// Precharge the run starting at `position` through the handler, and
+	/// record it in `precharged`. Returns whether the static cost of the
+	/// opcode at `position` has already been paid.
+	pub(crate) fn precharge<H: Handler>(
+		&self,
+		precharged: &mut Range<usize>,
+		position: usize,
+		context: &Context,
+		handler: &mut H,
+	) -> bool {
+		// Jumps can only land on the start of a run, so being strictly inside
+		// the last precharged run means execution went straight through it.
+		if position > precharged.start && position < precharged.end {
+			return true;
+		}
+
+		match self.run_at(position) {
+			Some(run) if handler.pre_validate_block(context, run.cost) => {
+				*precharged = run.start..run.end;
+				true
+			}
+			_ => {
+				*precharged = 0..0;
+				false
+			}
+		}
+	}
+}
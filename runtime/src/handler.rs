@@ -1,5 +1,7 @@
-use crate::{Capture, Context, CreateScheme, ExitError, ExitReason, Machine, Opcode, Stack};
-use alloc::vec::Vec;
+use crate::{
+	Capture, Context, CreateScheme, ExitError, ExitFatal, ExitReason, Machine, Opcode, Stack,
+};
+use alloc::{sync::Arc, vec::Vec};
 use primitive_types::{H160, H256, U256};
 
 /// Transfer from source to target, with given value.
@@ -32,7 +34,7 @@ pub trait Handler {
 	/// Get code hash of address.
 	fn code_hash(&self, address: H160) -> H256;
 	/// Get code of address.
-	fn code(&self, address: H160) -> Vec<u8>;
+	fn code(&self, address: H160) -> Arc<Vec<u8>>;
 	/// Get storage value of address at index.
 	fn storage(&self, address: H160, index: H256) -> H256;
 	/// Get original storage value of address at index.
@@ -114,6 +116,19 @@ pub trait Handler {
 		opcode: Opcode,
 		stack: &Stack,
 	) -> Result<(), ExitError>;
+	/// Pre-validation of a run of opcodes with only static gas cost, used
+	/// when `Config::precharge_block_gas` is enabled. Returning `false`, for
+	/// example when there is not enough gas left to pay for the whole run,
+	/// makes the runtime fall back to `pre_validate` for each opcode.
+	fn pre_validate_block(&mut self, _context: &Context, _static_gas: u64) -> bool {
+		false
+	}
+	/// Record an opcode about to be executed. Returning an error aborts the
+	/// execution, which is used to enforce `Config::step_limit` and to cancel
+	/// long running calls across call frames.
+	fn record_step(&mut self) -> Result<(), ExitFatal> {
+		Ok(())
+	}
 	/// Handle other unknown external opcodes.
 	fn other(&mut self, opcode: Opcode, _stack: &mut Machine) -> Result<(), ExitError> {
 		Err(ExitError::InvalidCode(opcode))
@@ -22,22 +22,43 @@ macro_rules! event {
 	($x:expr) => {};
 }
 
+mod config_builder;
 mod context;
+mod custom_opcode;
 mod eval;
+mod gas_blocks;
 mod handler;
 mod interrupt;
+mod snapshot;
+mod witness;
 
 pub use evm_core::*;
 
+pub use crate::config_builder::{ConfigBuilder, ConfigError, Fork};
 pub use crate::context::{CallScheme, Context, CreateScheme};
+pub use crate::custom_opcode::{CustomOpcode, OpcodeRegistry};
+pub use crate::gas_blocks::{GasBlocks, GasRun};
 pub use crate::handler::{Handler, Transfer};
 pub use crate::interrupt::{Resolve, ResolveCall, ResolveCreate};
+pub use crate::snapshot::RuntimeSnapshot;
+pub use crate::witness::{StepTransition, StepWitness};
 
-use alloc::rc::Rc;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::ops::Range;
 
 macro_rules! step {
 	( $self:expr, $handler:expr, $return:tt $($err:path)?; $($ok:path)? ) => ({
+		// Only opcodes in the code are counted, not the implicit `STOP` at the
+		// end of it.
+		if $self.machine.inspect().is_some() {
+			if let Err(e) = $self.record_step($handler) {
+				$self.machine.exit(e.clone().into());
+				$self.status = Err(e.into());
+			}
+		}
+
 		if let Some((opcode, stack)) = $self.machine.inspect() {
 			event!(Step {
 				context: &$self.context,
@@ -47,12 +68,24 @@ macro_rules! step {
 				memory: $self.machine.memory()
 			});
 
-			match $handler.pre_validate(&$self.context, opcode, stack) {
-				Ok(()) => (),
-				Err(e) => {
-					$self.machine.exit(e.clone().into());
-					$self.status = Err(e.into());
-				},
+			let precharged = match (&$self.gas_blocks, $self.machine.position()) {
+				(Some(gas_blocks), Ok(position)) => gas_blocks.precharge(
+					&mut $self.precharged,
+					*position,
+					&$self.context,
+					$handler,
+				),
+				_ => false,
+			};
+
+			if !precharged {
+				match $handler.pre_validate(&$self.context, opcode, stack) {
+					Ok(()) => (),
+					Err(e) => {
+						$self.machine.exit(e.clone().into());
+						$self.status = Err(e.into());
+					},
+				}
 			}
 		}
 
@@ -111,24 +144,114 @@ pub struct Runtime<'config> {
 	status: Result<(), ExitReason>,
 	return_data_buffer: Vec<u8>,
 	context: Context,
-	_config: &'config Config,
+	gas_blocks: Option<Arc<GasBlocks>>,
+	precharged: Range<usize>,
+	steps: u64,
+	config: &'config Config,
 }
 
 impl<'config> Runtime<'config> {
 	/// Create a new runtime with given code and data.
 	pub fn new(
-		code: Rc<Vec<u8>>,
-		data: Rc<Vec<u8>>,
+		code: Arc<Vec<u8>>,
+		data: Arc<Vec<u8>>,
+		context: Context,
+		config: &'config Config,
+	) -> Self {
+		let valids = Arc::new(Valids::new_with_immediates(&code[..], |opcode| {
+			config.custom_opcodes.immediates(opcode)
+		}));
+
+		Self::new_with_valids(code, valids, data, context, config)
+	}
+
+	/// Create a new runtime with given code, its already computed valid
+	/// mapping and data.
+	pub fn new_with_valids(
+		code: Arc<Vec<u8>>,
+		valids: Arc<Valids>,
+		data: Arc<Vec<u8>>,
 		context: Context,
 		config: &'config Config,
 	) -> Self {
 		Self {
-			machine: Machine::new(code, data, config.stack_limit, config.memory_limit),
+			machine: Machine::new_with_valids(
+				code,
+				valids,
+				data,
+				config.stack_limit,
+				config.memory_limit,
+			),
 			status: Ok(()),
 			return_data_buffer: Vec::new(),
 			context,
-			_config: config,
+			gas_blocks: None,
+			precharged: 0..0,
+			steps: 0,
+			config,
+		}
+	}
+
+	/// Take a snapshot of the complete runtime state.
+	pub fn snapshot(&self) -> RuntimeSnapshot {
+		RuntimeSnapshot {
+			machine: self.machine.snapshot(),
+			status: self.status.clone(),
+			return_data_buffer: self.return_data_buffer.clone(),
+			context: self.context.clone(),
+			precharged_start: self.precharged.start as u64,
+			precharged_end: self.precharged.end as u64,
+			steps: self.steps,
+		}
+	}
+
+	/// Restore a runtime from a snapshot. If the snapshot was taken with
+	/// block gas precharging, the same static gas analysis must be set again
+	/// through `set_gas_blocks` before resuming, so that runs which are
+	/// already paid for are not charged twice.
+	pub fn restore(snapshot: RuntimeSnapshot, config: &'config Config) -> Result<Self, ExitError> {
+		let to_usize = |value: u64| usize::try_from(value).map_err(|_| ExitError::InvalidRange);
+
+		let machine = Machine::restore_with_immediates(snapshot.machine, |opcode| {
+			config.custom_opcodes.immediates(opcode)
+		})?;
+
+		Ok(Self {
+			machine,
+			status: snapshot.status,
+			return_data_buffer: snapshot.return_data_buffer,
+			context: snapshot.context,
+			gas_blocks: None,
+			precharged: to_usize(snapshot.precharged_start)?..to_usize(snapshot.precharged_end)?,
+			steps: snapshot.steps,
+			config,
+		})
+	}
+
+	/// Set the static gas analysis of the code. It is only used when
+	/// `Config::precharge_block_gas` is enabled, in which case the static cost
+	/// of each run is charged through `Handler::pre_validate_block` on entry.
+	pub fn set_gas_blocks(&mut self, gas_blocks: Arc<GasBlocks>) {
+		if self.config.precharge_block_gas {
+			self.gas_blocks = Some(gas_blocks);
+		}
+	}
+
+	/// Count an executed opcode against the step limits.
+	fn record_step<H: Handler>(&mut self, handler: &mut H) -> Result<(), ExitFatal> {
+		self.steps += 1;
+		if let Some(limit) = self.config.call_step_limit {
+			if self.steps > limit {
+				return Err(ExitFatal::StepLimitReached);
+			}
 		}
+
+		handler.record_step()
+	}
+
+	/// Number of opcodes executed by the runtime.
+	pub fn steps(&self) -> u64 {
+		self.steps
 	}
 
 	/// Get a reference to the machine.
@@ -161,7 +284,7 @@ impl<'config> Runtime<'config> {
 }
 
 /// Runtime configuration.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Config {
 	/// Gas paid for extcode.
 	pub gas_ext_code: u64,
@@ -237,6 +360,8 @@ pub struct Config {
 	pub call_stipend: u64,
 	/// Has delegate call.
 	pub has_delegate_call: bool,
+	/// Has static call.
+	pub has_static_call: bool,
 	/// Has create2.
 	pub has_create2: bool,
 	/// Has revert.
@@ -253,8 +378,20 @@ pub struct Config {
 	pub has_ext_code_hash: bool,
 	/// Has ext block fee. See [EIP-3198](https://github.com/ethereum/EIPs/blob/master/EIPS/eip-3198.md)
 	pub has_base_fee: bool,
+	/// Has PUSH0. See [EIP-3855](https://eips.ethereum.org/EIPS/eip-3855)
+	pub has_push0: bool,
 	/// Whether the gasometer is running in estimate mode.
 	pub estimate: bool,
+	/// Charge the static gas of a run of opcodes at once when entering it,
+	/// instead of once per opcode. Gas usage and exit reasons are unchanged.
+	pub precharge_block_gas: bool,
+	/// Maximum number of opcodes executed by a transaction, across all its
+	/// call frames.
+	pub step_limit: Option<u64>,
+	/// Maximum number of opcodes executed by a single call frame.
+	pub call_step_limit: Option<u64>,
+	/// Opcodes added on top of the EVM instruction set.
+	pub custom_opcodes: OpcodeRegistry,
 }
 
 impl Config {
@@ -297,6 +434,7 @@ impl Config {
 			create_contract_limit: None,
 			call_stipend: 2300,
 			has_delegate_call: false,
+			has_static_call: false,
 			has_create2: false,
 			has_revert: false,
 			has_return_data: false,
@@ -305,7 +443,83 @@ impl Config {
 			has_self_balance: false,
 			has_ext_code_hash: false,
 			has_base_fee: false,
+			has_push0: false,
 			estimate: false,
+			precharge_block_gas: false,
+			step_limit: None,
+			call_step_limit: None,
+			custom_opcodes: OpcodeRegistry::empty(),
+		}
+	}
+
+	/// Homestead hard fork configuration.
+	pub const fn homestead() -> Config {
+		Config {
+			gas_transaction_create: 53000,
+			has_delegate_call: true,
+			..Self::frontier()
+		}
+	}
+
+	/// Tangerine Whistle hard fork configuration, see
+	/// [EIP-150](https://eips.ethereum.org/EIPS/eip-150).
+	pub const fn tangerine_whistle() -> Config {
+		Config {
+			gas_ext_code: 700,
+			gas_ext_code_hash: 700,
+			gas_balance: 400,
+			gas_sload: 200,
+			gas_suicide: 5000,
+			gas_suicide_new_account: 25000,
+			gas_call: 700,
+			err_on_call_with_more_gas: false,
+			call_l64_after_gas: true,
+			..Self::homestead()
+		}
+	}
+
+	/// Spurious Dragon hard fork configuration, see
+	/// [EIP-160](https://eips.ethereum.org/EIPS/eip-160),
+	/// [EIP-161](https://eips.ethereum.org/EIPS/eip-161) and
+	/// [EIP-170](https://eips.ethereum.org/EIPS/eip-170).
+	pub const fn spurious_dragon() -> Config {
+		Config {
+			gas_expbyte: 50,
+			empty_considered_exists: false,
+			create_increase_nonce: true,
+			create_contract_limit: Some(0x6000),
+			..Self::tangerine_whistle()
+		}
+	}
+
+	/// Byzantium hard fork configuration.
+	pub const fn byzantium() -> Config {
+		Config {
+			has_static_call: true,
+			has_revert: true,
+			has_return_data: true,
+			..Self::spurious_dragon()
+		}
+	}
+
+	/// Constantinople hard fork configuration, including the net gas metering
+	/// for `SSTORE` of [EIP-1283](https://eips.ethereum.org/EIPS/eip-1283).
+	pub const fn constantinople() -> Config {
+		Config {
+			sstore_gas_metering: true,
+			..Self::petersburg()
+		}
+	}
+
+	/// Petersburg hard fork configuration, which is Constantinople without
+	/// EIP-1283.
+	pub const fn petersburg() -> Config {
+		Config {
+			gas_ext_code_hash: 400,
+			has_create2: true,
+			has_bitwise_shifting: true,
+			has_ext_code_hash: true,
+			..Self::byzantium()
 		}
 	}
 
@@ -348,6 +562,7 @@ impl Config {
 			create_contract_limit: Some(0x6000),
 			call_stipend: 2300,
 			has_delegate_call: true,
+			has_static_call: true,
 			has_create2: true,
 			has_revert: true,
 			has_return_data: true,
@@ -356,10 +571,21 @@ impl Config {
 			has_self_balance: true,
 			has_ext_code_hash: true,
 			has_base_fee: false,
+			has_push0: false,
 			estimate: false,
+			precharge_block_gas: false,
+			step_limit: None,
+			call_step_limit: None,
+			custom_opcodes: OpcodeRegistry::empty(),
 		}
 	}
 
+	/// Muir Glacier hard fork configuration, which only delays the difficulty
+	/// bomb and is otherwise Istanbul.
+	pub const fn muir_glacier() -> Config {
+		Self::istanbul()
+	}
+
 	/// Berlin hard fork configuration.
 	pub const fn berlin() -> Config {
 		Self::config_with_derived_values(DerivedConfigInputs::berlin())
@@ -370,6 +596,41 @@ impl Config {
 		Self::config_with_derived_values(DerivedConfigInputs::london())
 	}
 
+	/// Ethereum Classic Atlantis hard fork configuration, see
+	/// [ECIP-1054](https://ecips.ethereumclassic.org/ECIPs/ecip-1054). Same
+	/// EVM rules as Byzantium.
+	pub const fn atlantis() -> Config {
+		Self::byzantium()
+	}
+
+	/// Ethereum Classic Agharta hard fork configuration, see
+	/// [ECIP-1056](https://ecips.ethereumclassic.org/ECIPs/ecip-1056). Same
+	/// EVM rules as Petersburg.
+	pub const fn agharta() -> Config {
+		Self::petersburg()
+	}
+
+	/// Ethereum Classic Phoenix hard fork configuration, see
+	/// [ECIP-1088](https://ecips.ethereumclassic.org/ECIPs/ecip-1088). Same
+	/// EVM rules as Istanbul.
+	pub const fn phoenix() -> Config {
+		Self::istanbul()
+	}
+
+	/// Ethereum Classic Magneto hard fork configuration, see
+	/// [ECIP-1103](https://ecips.ethereumclassic.org/ECIPs/ecip-1103). Same
+	/// EVM rules as Berlin.
+	pub const fn magneto() -> Config {
+		Self::berlin()
+	}
+
+	/// Ethereum Classic Mystique hard fork configuration, see
+	/// [ECIP-1104](https://ecips.ethereumclassic.org/ECIPs/ecip-1104). London
+	/// refunds and code prefix rules, without the base fee.
+	pub const fn mystique() -> Config {
+		Self::config_with_derived_values(DerivedConfigInputs::mystique())
+	}
+
 	const fn config_with_derived_values(inputs: DerivedConfigInputs) -> Config {
 		let DerivedConfigInputs {
 			gas_storage_read_warm,
@@ -429,6 +690,7 @@ impl Config {
 			create_contract_limit: Some(0x6000),
 			call_stipend: 2300,
 			has_delegate_call: true,
+			has_static_call: true,
 			has_create2: true,
 			has_revert: true,
 			has_return_data: true,
@@ -437,7 +699,12 @@ impl Config {
 			has_self_balance: true,
 			has_ext_code_hash: true,
 			has_base_fee,
+			has_push0: false,
 			estimate: false,
+			precharge_block_gas: false,
+			step_limit: None,
+			call_step_limit: None,
+			custom_opcodes: OpcodeRegistry::empty(),
 		}
 	}
 }
@@ -475,4 +742,15 @@ impl DerivedConfigInputs {
 			disallow_executable_format: true,
 		}
 	}
+
+	const fn mystique() -> Self {
+		Self {
+			gas_storage_read_warm: 100,
+			gas_sload_cold: 2100,
+			gas_access_list_storage_key: 1900,
+			decrease_clears_refund: true,
+			has_base_fee: false,
+			disallow_executable_format: true,
+		}
+	}
 }
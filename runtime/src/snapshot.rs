@@ -0,0 +1,29 @@
+use crate::{Context, ExitReason, MachineSnapshot};
+use alloc::vec::Vec;
+
+/// Complete state of a `Runtime` at a point of its execution.
+///
+/// Only the state of the current call frame is captured. Gas, storage and
+/// other state kept by the handler must be checkpointed separately.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+	feature = "with-codec",
+	derive(codec::Encode, codec::Decode, scale_info::TypeInfo)
+)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RuntimeSnapshot {
+	/// State of the underlying machine.
+	pub machine: MachineSnapshot,
+	/// Status of the runtime, or the reason it exited.
+	pub status: Result<(), ExitReason>,
+	/// Return data of the last call or create.
+	pub return_data_buffer: Vec<u8>,
+	/// Execution context.
+	pub context: Context,
+	/// Start of the code range whose static gas has already been charged.
+	pub precharged_start: u64,
+	/// End of the code range whose static gas has already been charged.
+	pub precharged_end: u64,
+	/// Number of opcodes executed so far.
+	pub steps: u64,
+}
@@ -0,0 +1,609 @@
+use crate::{
+	Capture, ExitError, ExitFatal, ExitReason, Handler, Machine, MachineSnapshot, Opcode, Runtime,
+	Valids,
+};
+use alloc::vec::Vec;
+use core::cmp::min;
+use core::convert::TryFrom;
+use core::ops::Range;
+use primitive_types::{H256, U256};
+
+/// The part of the state read by a single step of a machine, enough to
+/// re-execute it without the rest of the state.
+///
+/// Code, call data and memory are given as `(offset, bytes)` chunks covering
+/// exactly the ranges the opcode accesses. When a jump is taken, the code
+/// chunk starts at zero so that the validity of the destination can be
+/// recomputed. Gas is not part of the witness.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+	feature = "with-codec",
+	derive(codec::Encode, codec::Decode, scale_info::TypeInfo)
+)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StepWitness {
+	/// Program counter.
+	pub position: u64,
+	/// Length of the code.
+	pub code_len: u64,
+	/// Accessed code chunks.
+	pub code: Vec<(u64, Vec<u8>)>,
+	/// Length of the call data.
+	pub data_len: u64,
+	/// Accessed call data chunks.
+	pub data: Vec<(u64, Vec<u8>)>,
+	/// Stack length.
+	pub stack_len: u64,
+	/// Stack limit.
+	pub stack_limit: u64,
+	/// Stack items read by the opcode, from bottom to top.
+	pub stack: Vec<U256>,
+	/// Effective length of the memory.
+	pub memory_effective_len: U256,
+	/// Memory limit.
+	pub memory_limit: u64,
+	/// Accessed memory chunks.
+	pub memory: Vec<(u64, Vec<u8>)>,
+	/// Storage key and value read by `SLOAD`.
+	pub storage: Option<(H256, H256)>,
+}
+
+/// The effect of a single step, as computed from a `StepWitness`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+	feature = "with-codec",
+	derive(codec::Encode, codec::Decode, scale_info::TypeInfo)
+)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StepTransition {
+	/// Program counter after the step, or the reason the machine exited.
+	pub position: Result<u64, ExitReason>,
+	/// Stack length after the step.
+	pub stack_len: u64,
+	/// Stack items above the ones left untouched by the step, from bottom to
+	/// top.
+	pub stack: Vec<U256>,
+	/// Effective length of the memory after the step.
+	pub memory_effective_len: U256,
+	/// Accessed memory chunks after the step.
+	pub memory: Vec<(u64, Vec<u8>)>,
+	/// Storage key and value read by `SLOAD` or written by `SSTORE`.
+	pub storage: Option<(H256, H256)>,
+	/// Return value range set by `RETURN` or `REVERT`.
+	pub return_range: Option<(U256, U256)>,
+}
+
+impl StepWitness {
+	/// Collect the witness of the next step of the runtime. Returns `None` if
+	/// the runtime has exited, or if the next opcode needs state not covered
+	/// by the witness, such as calls, logs or the environment. Jumps are not
+	/// covered when custom opcodes take immediate data, as their destination
+	/// is checked without them.
+	pub fn new<H: Handler>(runtime: &Runtime, handler: &H) -> Option<Self> {
+		let machine = runtime.machine();
+		let position = *machine.position().as_ref().ok()?;
+		let opcode = opcode_at(machine.code(), position);
+		if matches!(opcode, Opcode::JUMP | Opcode::JUMPI)
+			&& runtime.config.custom_opcodes.has_immediates()
+		{
+			return None;
+		}
+
+		let stack = machine.stack().data();
+		let inputs = min(stack_inputs(opcode)?, stack.len());
+		let stack = stack[(stack.len() - inputs)..].to_vec();
+
+		let access = access(
+			opcode,
+			position,
+			&stack,
+			machine.code().len(),
+			machine.data().len(),
+			machine.memory().limit(),
+		);
+
+		let storage = if opcode == Opcode::SLOAD && inputs == 1 {
+			let key = machine.stack().peek_h256(0).ok()?;
+			Some((key, handler.storage(runtime.context().address, key)))
+		} else {
+			None
+		};
+
+		Some(Self {
+			position: position as u64,
+			code_len: machine.code().len() as u64,
+			code: chunks(&access.code, |range| machine.code()[range].to_vec()),
+			data_len: machine.data().len() as u64,
+			data: chunks(&access.data, |range| machine.data()[range].to_vec()),
+			stack_len: machine.stack().len() as u64,
+			stack_limit: machine.stack().limit() as u64,
+			stack,
+			memory_effective_len: machine.memory().effective_len(),
+			memory_limit: machine.memory().limit() as u64,
+			memory: chunks(&access.memory, |range| {
+				machine.memory().get(range.start, range.len())
+			}),
+			storage,
+		})
+	}
+
+	/// Re-execute the step from the witness alone. Returns `None` if the
+	/// witness does not cover exactly the state the step accesses.
+	///
+	/// Only the chunks of code, call data and memory, and the stack items read
+	/// by the opcode are held, so the cost of verification is bounded by the
+	/// size of the witness rather than by the lengths it states.
+	pub fn verify(&self) -> Option<StepTransition> {
+		let to_usize = |value: u64| usize::try_from(value).ok();
+
+		let position = to_usize(self.position)?;
+		let code_len = to_usize(self.code_len)?;
+		let data_len = to_usize(self.data_len)?;
+		let memory_limit = to_usize(self.memory_limit)?;
+
+		// The first code chunk holds the opcode and its immediate data.
+		let opcode = if position < code_len {
+			match self.code.first() {
+				Some((offset, bytes)) if *offset == self.position && !bytes.is_empty() => {
+					Opcode(bytes[0])
+				}
+				_ => return None,
+			}
+		} else {
+			Opcode::STOP
+		};
+
+		let stack_len = to_usize(self.stack_len)?;
+		let inputs = min(stack_inputs(opcode)?, stack_len);
+		if self.stack.len() != inputs {
+			return None;
+		}
+
+		// All chunks are checked against the stated lengths before use.
+		let access = access(
+			opcode,
+			position,
+			&self.stack,
+			code_len,
+			data_len,
+			memory_limit,
+		);
+		if !same_ranges(&access.code, &self.code)
+			|| !same_ranges(&access.data, &self.data)
+			|| !same_ranges(&access.memory, &self.memory)
+			|| self.storage.is_some() != (opcode == Opcode::SLOAD && inputs == 1)
+		{
+			return None;
+		}
+
+		// The machine only holds the stack items read by the opcode, with the
+		// limit lowered by the items below them, and the code from the
+		// program counter on.
+		let base = stack_len - inputs;
+		let mut machine = Machine::restore(MachineSnapshot {
+			data: Vec::new(),
+			code: self
+				.code
+				.first()
+				.map(|(_, bytes)| bytes.clone())
+				.unwrap_or_default(),
+			position: Ok(0),
+			return_start: U256::zero(),
+			return_end: U256::zero(),
+			memory: Vec::new(),
+			memory_effective_len: self.memory_effective_len,
+			memory_limit: self.memory_limit,
+			stack: self.stack.clone(),
+			stack_limit: self.stack_limit.checked_sub(base as u64)?,
+		})
+		.ok()?;
+
+		let mut memory = self.memory.clone();
+		let mut storage = None;
+		let next = match self.eval_chunks(opcode, position, &mut machine, &mut memory) {
+			Some(next) => next,
+			None => {
+				storage = match machine.step() {
+					Ok(()) | Err(Capture::Exit(_)) => None,
+					Err(Capture::Trap(Opcode::SLOAD)) => match machine.stack_mut().pop_h256() {
+						Ok(key) => {
+							let (witness_key, value) = self.storage?;
+							if key != witness_key {
+								return None;
+							}
+							machine.stack_mut().push_h256(value).ok()?;
+							Some((key, value))
+						}
+						Err(e) => {
+							machine.exit(e.into());
+							None
+						}
+					},
+					Err(Capture::Trap(Opcode::SSTORE)) => {
+						let key = machine.stack_mut().pop_h256();
+						let value = machine.stack_mut().pop_h256();
+						match (key, value) {
+							(Ok(key), Ok(value)) => Some((key, value)),
+							(Err(e), _) | (_, Err(e)) => {
+								machine.exit(e.into());
+								None
+							}
+						}
+					}
+					Err(Capture::Trap(_)) => return None,
+				};
+
+				machine.position().clone().map(|next| position + next)
+			}
+		};
+
+		let return_range = if opcode == Opcode::RETURN || opcode == Opcode::REVERT {
+			let range = machine.return_range();
+			Some((range.start, range.end))
+		} else {
+			None
+		};
+
+		Some(StepTransition {
+			position: next.map(|position| position as u64),
+			stack_len: (base + machine.stack().len()) as u64,
+			stack: machine.stack().data().clone(),
+			memory_effective_len: machine.memory().effective_len(),
+			memory,
+			storage,
+			return_range,
+		})
+	}
+
+	/// Execute an opcode reading code, call data or memory, or jumping, from
+	/// the witness chunks the way `Machine::step` does from the full buffers.
+	/// Memory writes go to the memory chunks. Returns the next program
+	/// counter or the exit reason, or `None` for other opcodes.
+	fn eval_chunks(
+		&self,
+		opcode: Opcode,
+		position: usize,
+		machine: &mut Machine,
+		memory: &mut [(u64, Vec<u8>)],
+	) -> Option<Result<usize, ExitReason>> {
+		let result = match opcode {
+			Opcode::PC => push(machine, U256::from(position)),
+			Opcode::CODESIZE => push(machine, U256::from(self.code_len)),
+			Opcode::CALLDATASIZE => push(machine, U256::from(self.data_len)),
+			Opcode::CALLDATALOAD => {
+				pop(machine).and_then(|_| push(machine, load(self.data.first())))
+			}
+			Opcode::CALLDATACOPY => self.copy(machine, memory, self.data.first()),
+			Opcode::CODECOPY => self.copy(machine, memory, self.code.get(1)),
+			Opcode::MLOAD => mload(machine, memory),
+			Opcode::MSTORE => self.mstore(machine, memory, 32),
+			Opcode::MSTORE8 => self.mstore(machine, memory, 1),
+			Opcode::JUMP | Opcode::JUMPI => return Some(self.jump(opcode, position, machine)),
+			_ => return None,
+		};
+
+		Some(result.map(|()| position + 1))
+	}
+
+	/// `CALLDATACOPY` or `CODECOPY` from the given source chunk.
+	fn copy(
+		&self,
+		machine: &mut Machine,
+		memory: &mut [(u64, Vec<u8>)],
+		source: Option<&(u64, Vec<u8>)>,
+	) -> Result<(), ExitReason> {
+		let memory_offset = pop(machine)?;
+		let source_offset = pop(machine)?;
+		let len = pop(machine)?;
+		machine.memory_mut().resize_offset(memory_offset, len)?;
+
+		// Nothing is copied from beyond `usize` offsets.
+		let source = match source_offset.checked_add(len) {
+			Some(end) if end <= U256::from(usize::MAX) => source,
+			_ => None,
+		};
+		write(
+			memory,
+			memory_offset,
+			len,
+			source.map(|(_, bytes)| &bytes[..]).unwrap_or_default(),
+			self.memory_limit,
+		)
+	}
+
+	/// `MSTORE` or `MSTORE8`, writing `len` bytes.
+	fn mstore(
+		&self,
+		machine: &mut Machine,
+		memory: &mut [(u64, Vec<u8>)],
+		len: usize,
+	) -> Result<(), ExitReason> {
+		let index = pop(machine)?;
+		let value = pop(machine)?;
+		machine.memory_mut().resize_offset(index, U256::from(len))?;
+		to_usize_or_fail(index, ExitFatal::NotSupported.into())?;
+
+		let mut bytes = [0; 32];
+		value.to_big_endian(&mut bytes);
+		write(
+			memory,
+			index,
+			U256::from(len),
+			&bytes[(32 - len)..],
+			self.memory_limit,
+		)
+	}
+
+	/// `JUMP` or `JUMPI`, checking the destination against the code chunk
+	/// preceding it.
+	fn jump(
+		&self,
+		opcode: Opcode,
+		position: usize,
+		machine: &mut Machine,
+	) -> Result<usize, ExitReason> {
+		let dest = pop(machine)?;
+		if opcode == Opcode::JUMPI && pop(machine)?.is_zero() {
+			return Ok(position + 1);
+		}
+
+		let dest = to_usize_or_fail(dest, ExitError::InvalidJump.into())?;
+		let valid = match self.code.get(1) {
+			Some((_, prefix)) => Valids::new(prefix).is_valid(dest),
+			None => false,
+		};
+		if !valid {
+			return Err(ExitError::InvalidJump.into());
+		}
+
+		Ok(dest)
+	}
+}
+
+fn mload(machine: &mut Machine, memory: &[(u64, Vec<u8>)]) -> Result<(), ExitReason> {
+	let index = pop(machine)?;
+	machine.memory_mut().resize_offset(index, U256::from(32))?;
+	to_usize_or_fail(index, ExitFatal::NotSupported.into())?;
+
+	push(machine, load(memory.first()))
+}
+
+/// Word starting with the bytes of a chunk, zero padded.
+fn load(chunk: Option<&(u64, Vec<u8>)>) -> U256 {
+	let mut word = [0; 32];
+	if let Some((_, bytes)) = chunk {
+		word[..bytes.len()].copy_from_slice(bytes);
+	}
+
+	U256::from_big_endian(&word)
+}
+
+fn pop(machine: &mut Machine) -> Result<U256, ExitReason> {
+	machine.stack_mut().pop().map_err(ExitReason::from)
+}
+
+fn push(machine: &mut Machine, value: U256) -> Result<(), ExitReason> {
+	machine.stack_mut().push(value).map_err(ExitReason::from)
+}
+
+fn to_usize_or_fail(value: U256, reason: ExitReason) -> Result<usize, ExitReason> {
+	if value > U256::from(usize::MAX) {
+		return Err(reason);
+	}
+
+	Ok(value.as_usize())
+}
+
+/// Write `source` followed by zeros to `len` bytes of memory at `offset`,
+/// failing like `Memory::copy_large` does. The written range is the memory
+/// chunk.
+fn write(
+	memory: &mut [(u64, Vec<u8>)],
+	offset: U256,
+	len: U256,
+	source: &[u8],
+	memory_limit: u64,
+) -> Result<(), ExitReason> {
+	if len.is_zero() {
+		return Ok(());
+	}
+
+	let offset = to_usize_or_fail(offset, ExitFatal::NotSupported.into())?;
+	let len = to_usize_or_fail(len, ExitFatal::NotSupported.into())?;
+	if offset
+		.checked_add(len)
+		.map(|end| end as u64 > memory_limit)
+		.unwrap_or(true)
+	{
+		return Err(ExitFatal::NotSupported.into());
+	}
+
+	let bytes = &mut memory.first_mut().expect("written range is accessed").1;
+	let copied = min(source.len(), len);
+	bytes[..copied].copy_from_slice(&source[..copied]);
+	for byte in &mut bytes[copied..] {
+		*byte = 0;
+	}
+
+	Ok(())
+}
+
+/// Ranges of code, call data and memory accessed by an opcode.
+#[derive(Default)]
+struct Access {
+	code: Vec<Range<usize>>,
+	data: Vec<Range<usize>>,
+	memory: Vec<Range<usize>>,
+}
+
+fn opcode_at(code: &[u8], position: usize) -> Opcode {
+	code.get(position)
+		.map(|v| Opcode(*v))
+		.unwrap_or(Opcode::STOP)
+}
+
+/// Number of stack items read by an opcode, or `None` if the opcode is not
+/// supported by the witness.
+fn stack_inputs(opcode: Opcode) -> Option<usize> {
+	let inputs = match opcode {
+		Opcode::STOP
+		| Opcode::CODESIZE
+		| Opcode::CALLDATASIZE
+		| Opcode::PC
+		| Opcode::MSIZE
+		| Opcode::JUMPDEST
+		| Opcode::INVALID => 0,
+		Opcode::ISZERO
+		| Opcode::NOT
+		| Opcode::CALLDATALOAD
+		| Opcode::POP
+		| Opcode::MLOAD
+		| Opcode::JUMP
+		| Opcode::SLOAD => 1,
+		Opcode::ADD
+		| Opcode::MUL
+		| Opcode::SUB
+		| Opcode::DIV
+		| Opcode::SDIV
+		| Opcode::MOD
+		| Opcode::SMOD
+		| Opcode::EXP
+		| Opcode::SIGNEXTEND
+		| Opcode::LT
+		| Opcode::GT
+		| Opcode::SLT
+		| Opcode::SGT
+		| Opcode::EQ
+		| Opcode::AND
+		| Opcode::OR
+		| Opcode::XOR
+		| Opcode::BYTE
+		| Opcode::SHL
+		| Opcode::SHR
+		| Opcode::SAR
+		| Opcode::MSTORE
+		| Opcode::MSTORE8
+		| Opcode::JUMPI
+		| Opcode::RETURN
+		| Opcode::REVERT
+		| Opcode::SSTORE => 2,
+		Opcode::ADDMOD | Opcode::MULMOD | Opcode::CODECOPY | Opcode::CALLDATACOPY => 3,
+		_ if opcode.is_push().is_some() => 0,
+		_ if (Opcode::DUP1.0..=Opcode::DUP16.0).contains(&opcode.0) => {
+			(opcode.0 - Opcode::DUP1.0 + 1) as usize
+		}
+		_ if (Opcode::SWAP1.0..=Opcode::SWAP16.0).contains(&opcode.0) => {
+			(opcode.0 - Opcode::SWAP1.0 + 2) as usize
+		}
+		_ => return None,
+	};
+
+	Some(inputs)
+}
+
+/// Compute the ranges accessed by `opcode`, given the stack items it reads
+/// from bottom to top. Missing items mean the step fails before accessing
+/// anything but the opcode itself.
+fn access(
+	opcode: Opcode,
+	position: usize,
+	stack: &[U256],
+	code_len: usize,
+	data_len: usize,
+	memory_limit: usize,
+) -> Access {
+	let input = |n: usize| stack.len().checked_sub(n + 1).map(|i| stack[i]);
+	let push = opcode.is_push().unwrap_or(0) as usize;
+
+	let mut access = Access::default();
+	access
+		.code
+		.extend(clip(U256::from(position), U256::from(1 + push), code_len));
+
+	match opcode {
+		Opcode::JUMP | Opcode::JUMPI => {
+			let taken = if opcode == Opcode::JUMP {
+				input(0).is_some()
+			} else {
+				input(1).map(|value| !value.is_zero()).unwrap_or(false)
+			};
+
+			// Validity of the destination depends on all code before it.
+			match input(0) {
+				Some(dest) if taken && dest < U256::from(code_len) => {
+					access.code.push(0..(dest.as_usize() + 1));
+				}
+				_ => (),
+			}
+		}
+		Opcode::CODECOPY => {
+			if let (Some(memory_offset), Some(code_offset), Some(len)) =
+				(input(0), input(1), input(2))
+			{
+				access.code.extend(clip(code_offset, len, code_len));
+				access.memory.extend(clip(memory_offset, len, memory_limit));
+			}
+		}
+		Opcode::CALLDATACOPY => {
+			if let (Some(memory_offset), Some(data_offset), Some(len)) =
+				(input(0), input(1), input(2))
+			{
+				access.data.extend(clip(data_offset, len, data_len));
+				access.memory.extend(clip(memory_offset, len, memory_limit));
+			}
+		}
+		Opcode::CALLDATALOAD => {
+			if let Some(offset) = input(0) {
+				access.data.extend(clip(offset, U256::from(32), data_len));
+			}
+		}
+		Opcode::MLOAD => {
+			if let Some(offset) = input(0) {
+				access
+					.memory
+					.extend(clip(offset, U256::from(32), memory_limit));
+			}
+		}
+		Opcode::MSTORE => {
+			if let (Some(offset), Some(_)) = (input(0), input(1)) {
+				access
+					.memory
+					.extend(clip(offset, U256::from(32), memory_limit));
+			}
+		}
+		Opcode::MSTORE8 => {
+			if let (Some(offset), Some(_)) = (input(0), input(1)) {
+				access
+					.memory
+					.extend(clip(offset, U256::one(), memory_limit));
+			}
+		}
+		_ => (),
+	}
+
+	access
+}
+
+/// Part of `offset..(offset + len)` below `limit`, if not empty.
+fn clip(offset: U256, len: U256, limit: usize) -> Option<Range<usize>> {
+	if len.is_zero() || offset >= U256::from(limit) {
+		return None;
+	}
+
+	let end = min(offset.saturating_add(len), U256::from(limit));
+	Some(offset.as_usize()..end.as_usize())
+}
+
+fn chunks<F: Fn(Range<usize>) -> Vec<u8>>(ranges: &[Range<usize>], get: F) -> Vec<(u64, Vec<u8>)> {
+	ranges
+		.iter()
+		.map(|range| (range.start as u64, get(range.clone())))
+		.collect()
+}
+
+fn same_ranges(ranges: &[Range<usize>], chunks: &[(u64, Vec<u8>)]) -> bool {
+	ranges.len() == chunks.len()
+		&& ranges.iter().zip(chunks).all(|(range, (offset, bytes))| {
+			range.start as u64 == *offset && range.len() == bytes.len()
+		})
+}
@@ -0,0 +1,119 @@
+//! Handler shared by the runtime tests.
+
+#![allow(dead_code)]
+
+use evm_runtime::{
+	Capture, Context, CreateScheme, ExitError, ExitReason, Handler, Opcode, Stack, Transfer,
+};
+use primitive_types::{H160, H256, U256};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+pub const ADDRESS: H160 = H160([0x20; 20]);
+
+/// Handler with storage only.
+#[derive(Default)]
+pub struct Storage(pub BTreeMap<H256, H256>);
+
+impl Handler for Storage {
+	type CreateInterrupt = Infallible;
+	type CreateFeedback = Infallible;
+	type CallInterrupt = Infallible;
+	type CallFeedback = Infallible;
+
+	fn balance(&self, _: H160) -> U256 {
+		unreachable!()
+	}
+	fn code_size(&self, _: H160) -> U256 {
+		unreachable!()
+	}
+	fn code_hash(&self, _: H160) -> H256 {
+		unreachable!()
+	}
+	fn code(&self, _: H160) -> Arc<Vec<u8>> {
+		unreachable!()
+	}
+	fn storage(&self, _: H160, index: H256) -> H256 {
+		self.0.get(&index).copied().unwrap_or_default()
+	}
+	fn original_storage(&self, address: H160, index: H256) -> H256 {
+		self.storage(address, index)
+	}
+	fn gas_left(&self) -> U256 {
+		unreachable!()
+	}
+	fn gas_price(&self) -> U256 {
+		unreachable!()
+	}
+	fn origin(&self) -> H160 {
+		unreachable!()
+	}
+	fn block_hash(&self, _: U256) -> H256 {
+		unreachable!()
+	}
+	fn block_number(&self) -> U256 {
+		unreachable!()
+	}
+	fn block_coinbase(&self) -> H160 {
+		unreachable!()
+	}
+	fn block_timestamp(&self) -> U256 {
+		unreachable!()
+	}
+	fn block_difficulty(&self) -> U256 {
+		unreachable!()
+	}
+	fn block_gas_limit(&self) -> U256 {
+		unreachable!()
+	}
+	fn block_base_fee_per_gas(&self) -> U256 {
+		unreachable!()
+	}
+	fn chain_id(&self) -> U256 {
+		unreachable!()
+	}
+	fn exists(&self, _: H160) -> bool {
+		unreachable!()
+	}
+	fn deleted(&self, _: H160) -> bool {
+		unreachable!()
+	}
+	fn is_cold(&self, _: H160, _: Option<H256>) -> bool {
+		false
+	}
+	fn set_storage(&mut self, _: H160, index: H256, value: H256) -> Result<(), ExitError> {
+		self.0.insert(index, value);
+		Ok(())
+	}
+	fn log(&mut self, _: H160, _: Vec<H256>, _: Vec<u8>) -> Result<(), ExitError> {
+		unreachable!()
+	}
+	fn mark_delete(&mut self, _: H160, _: H160) -> Result<(), ExitError> {
+		unreachable!()
+	}
+	fn create(
+		&mut self,
+		_: H160,
+		_: CreateScheme,
+		_: U256,
+		_: Vec<u8>,
+		_: Option<u64>,
+	) -> Capture<(ExitReason, Option<H160>, Vec<u8>), Infallible> {
+		unreachable!()
+	}
+	fn call(
+		&mut self,
+		_: H160,
+		_: Option<Transfer>,
+		_: Vec<u8>,
+		_: Option<u64>,
+		_: bool,
+		_: Context,
+	) -> Capture<(ExitReason, Vec<u8>), Infallible> {
+		unreachable!()
+	}
+	fn pre_validate(&mut self, _: &Context, _: Opcode, _: &Stack) -> Result<(), ExitError> {
+		Ok(())
+	}
+}
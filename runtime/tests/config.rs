@@ -0,0 +1,302 @@
+//! Each fork configuration is checked to be its predecessor with exactly the
+//! listed changes.
+
+use evm_runtime::{
+	Config, ConfigBuilder, ConfigError, CustomOpcode, ExitError, Fork, Machine, Opcode,
+	OpcodeRegistry,
+};
+
+#[test]
+fn homestead() {
+	assert_eq!(
+		Config::homestead(),
+		Config {
+			// EIP-2
+			gas_transaction_create: 53000,
+			// EIP-7
+			has_delegate_call: true,
+			..Config::frontier()
+		}
+	);
+}
+
+#[test]
+fn tangerine_whistle() {
+	assert_eq!(
+		Config::tangerine_whistle(),
+		Config {
+			// EIP-150
+			gas_ext_code: 700,
+			gas_ext_code_hash: 700,
+			gas_balance: 400,
+			gas_sload: 200,
+			gas_suicide: 5000,
+			gas_suicide_new_account: 25000,
+			gas_call: 700,
+			err_on_call_with_more_gas: false,
+			call_l64_after_gas: true,
+			..Config::homestead()
+		}
+	);
+}
+
+#[test]
+fn spurious_dragon() {
+	assert_eq!(
+		Config::spurious_dragon(),
+		Config {
+			// EIP-160
+			gas_expbyte: 50,
+			// EIP-161
+			empty_considered_exists: false,
+			create_increase_nonce: true,
+			// EIP-170
+			create_contract_limit: Some(0x6000),
+			..Config::tangerine_whistle()
+		}
+	);
+}
+
+#[test]
+fn byzantium() {
+	assert_eq!(
+		Config::byzantium(),
+		Config {
+			// EIP-214
+			has_static_call: true,
+			// EIP-140
+			has_revert: true,
+			// EIP-211
+			has_return_data: true,
+			..Config::spurious_dragon()
+		}
+	);
+}
+
+#[test]
+fn constantinople() {
+	assert_eq!(
+		Config::constantinople(),
+		Config {
+			// EIP-145
+			has_bitwise_shifting: true,
+			// EIP-1014
+			has_create2: true,
+			// EIP-1052
+			has_ext_code_hash: true,
+			gas_ext_code_hash: 400,
+			// EIP-1283
+			sstore_gas_metering: true,
+			..Config::byzantium()
+		}
+	);
+}
+
+#[test]
+fn petersburg() {
+	assert_eq!(
+		Config::petersburg(),
+		Config {
+			// EIP-1283 removed
+			sstore_gas_metering: false,
+			..Config::constantinople()
+		}
+	);
+}
+
+#[test]
+fn istanbul() {
+	assert_eq!(
+		Config::istanbul(),
+		Config {
+			// EIP-1884
+			gas_balance: 700,
+			gas_sload: 800,
+			gas_ext_code_hash: 700,
+			has_self_balance: true,
+			// EIP-1344
+			has_chain_id: true,
+			// EIP-2028
+			gas_transaction_non_zero_data: 16,
+			// EIP-2200
+			sstore_gas_metering: true,
+			sstore_revert_under_stipend: true,
+			..Config::petersburg()
+		}
+	);
+}
+
+#[test]
+fn muir_glacier() {
+	assert_eq!(Config::muir_glacier(), Config::istanbul());
+}
+
+#[test]
+fn etc_forks() {
+	assert_eq!(Config::atlantis(), Config::byzantium());
+	assert_eq!(Config::agharta(), Config::petersburg());
+	assert_eq!(Config::phoenix(), Config::istanbul());
+	assert_eq!(Config::magneto(), Config::berlin());
+}
+
+#[test]
+fn mystique() {
+	let config = Config::mystique();
+
+	// EIP-3529, with the London values.
+	assert!(config.decrease_clears_refund);
+	assert_eq!(config.refund_sstore_clears, 4800);
+	assert_eq!(config.max_refund_quotient, 5);
+
+	assert_eq!(
+		config,
+		Config {
+			// EIP-1559 and EIP-3198 are not part of ECIP-1104.
+			has_base_fee: false,
+			..Config::london()
+		}
+	);
+	assert_eq!(
+		config,
+		Config {
+			// EIP-3529
+			decrease_clears_refund: true,
+			refund_sstore_clears: 4800,
+			max_refund_quotient: 5,
+			// EIP-3541
+			disallow_executable_format: true,
+			..Config::magneto()
+		}
+	);
+}
+
+#[test]
+fn builder_forks() {
+	for fork in [
+		Fork::Frontier,
+		Fork::Homestead,
+		Fork::TangerineWhistle,
+		Fork::SpuriousDragon,
+		Fork::Byzantium,
+		Fork::Constantinople,
+		Fork::Petersburg,
+		Fork::Istanbul,
+		Fork::MuirGlacier,
+		Fork::Berlin,
+		Fork::London,
+	] {
+		assert_eq!(ConfigBuilder::new(fork).build(), Ok(fork.config()));
+	}
+
+	assert_eq!(
+		ConfigBuilder::new(Fork::Istanbul)
+			.enable(2929)
+			.enable(2930)
+			.build(),
+		Ok(Config::berlin())
+	);
+	assert_eq!(
+		ConfigBuilder::new(Fork::Berlin)
+			.enable(3198)
+			.enable(3529)
+			.enable(3541)
+			.build(),
+		Ok(Config::london())
+	);
+	assert_eq!(
+		ConfigBuilder::new(Fork::London)
+			.disable(3529)
+			.disable(3198)
+			.disable(3541)
+			.build(),
+		Ok(Config::berlin())
+	);
+	assert_eq!(
+		ConfigBuilder::new(Fork::Berlin)
+			.disable(2930)
+			.disable(2929)
+			.build(),
+		Ok(Config::istanbul())
+	);
+	assert_eq!(
+		ConfigBuilder::new(Fork::London).disable(3198).build(),
+		Ok(Config::mystique())
+	);
+}
+
+#[test]
+fn builder_eips() {
+	let config = ConfigBuilder::new(Fork::London)
+		.disable(3529)
+		.enable(3855)
+		.build()
+		.unwrap();
+	assert!(config.has_push0);
+	assert!(!config.decrease_clears_refund);
+	assert_eq!(config.refund_sstore_clears, 15000);
+	assert_eq!(config.max_refund_quotient, 2);
+	assert!(config.has_base_fee);
+
+	assert_eq!(
+		ConfigBuilder::new(Fork::London).disable(2929).build(),
+		Err(ConfigError::MissingDependency {
+			eip: 2930,
+			requires: 2929
+		})
+	);
+	assert_eq!(
+		ConfigBuilder::new(Fork::Istanbul).enable(3529).build(),
+		Err(ConfigError::MissingDependency {
+			eip: 3529,
+			requires: 2929
+		})
+	);
+	assert_eq!(
+		ConfigBuilder::new(Fork::London).enable(1559).build(),
+		Err(ConfigError::UnknownEip(1559))
+	);
+	assert!(ConfigBuilder::new(Fork::Byzantium)
+		.eips()
+		.unwrap()
+		.contains(&214));
+}
+
+fn custom_eval(_: &mut Machine) -> Result<(), ExitError> {
+	Ok(())
+}
+
+#[test]
+fn custom_opcodes() {
+	const CUSTOM: CustomOpcode = CustomOpcode {
+		opcode: Opcode(0x0c),
+		gas: 10,
+		inputs: 2,
+		outputs: 1,
+		immediates: 0,
+		eval: custom_eval,
+	};
+	static VALID: [CustomOpcode; 2] = [
+		CUSTOM,
+		CustomOpcode {
+			opcode: Opcode(0xe0),
+			..CUSTOM
+		},
+	];
+	static DEFINED: [CustomOpcode; 1] = [CustomOpcode {
+		opcode: Opcode::ADD,
+		..CUSTOM
+	}];
+	static DUPLICATE: [CustomOpcode; 2] = [CUSTOM, CUSTOM];
+
+	let registry = OpcodeRegistry::new(&VALID).unwrap();
+	assert_eq!(registry.get(Opcode(0x0c)), Some(&CUSTOM));
+	assert_eq!(registry.get(Opcode(0x0d)), None);
+	assert_eq!(
+		OpcodeRegistry::new(&DEFINED),
+		Err(ConfigError::UnavailableOpcode(Opcode::ADD))
+	);
+	assert_eq!(
+		OpcodeRegistry::new(&DUPLICATE),
+		Err(ConfigError::UnavailableOpcode(Opcode(0x0c)))
+	);
+}
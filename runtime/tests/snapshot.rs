@@ -0,0 +1,161 @@
+//! A runtime restored from a snapshot resumes exactly where the snapshotted
+//! runtime was, given the same handler state.
+
+mod common;
+
+use common::{Storage, ADDRESS};
+use evm_runtime::{
+	Capture, Config, Context, CustomOpcode, ExitError, ExitReason, ExitSucceed, Machine, Opcode,
+	OpcodeRegistry, Runtime,
+};
+use primitive_types::{H160, H256, U256};
+use std::sync::Arc;
+
+/// Add the byte of immediate data to the top of the stack.
+fn addi(machine: &mut Machine) -> Result<(), ExitError> {
+	let position = *machine
+		.position()
+		.as_ref()
+		.map_err(|_| ExitError::InvalidRange)?;
+	let immediate = machine.code().get(position).copied().unwrap_or(0);
+	let value = machine.stack_mut().pop()?;
+	machine.stack_mut().push(value + U256::from(immediate))
+}
+
+static OPCODES: [CustomOpcode; 1] = [CustomOpcode {
+	opcode: Opcode(0x0c),
+	gas: 5,
+	inputs: 1,
+	outputs: 1,
+	immediates: 1,
+	eval: addi,
+}];
+
+// PUSH1 2, ADDI 5, SSTORE it at slot 0, then SLOAD it and RETURN it.
+const ADD_AND_STORE: &str = "6002 0c05 600055 600054 600052 60206000f3";
+
+fn config() -> Config {
+	Config {
+		custom_opcodes: OpcodeRegistry::new(&OPCODES).unwrap(),
+		..Config::london()
+	}
+}
+
+fn runtime<'config>(code: &str, config: &'config Config) -> Runtime<'config> {
+	Runtime::new(
+		Arc::new(hex::decode(code.replace(' ', "")).unwrap()),
+		Arc::new(Vec::new()),
+		Context {
+			address: ADDRESS,
+			caller: H160::default(),
+			apparent_value: U256::zero(),
+		},
+		config,
+	)
+}
+
+/// Step a runtime at most `steps` times.
+fn step(runtime: &mut Runtime, storage: &mut Storage, steps: usize) {
+	for _ in 0..steps {
+		if runtime.step(storage).is_err() {
+			break;
+		}
+	}
+}
+
+/// Run a runtime until it exits.
+fn finish(runtime: &mut Runtime, storage: &mut Storage) -> ExitReason {
+	loop {
+		match runtime.step(storage) {
+			Ok(()) => (),
+			Err(Capture::Exit(reason)) => return reason,
+			Err(Capture::Trap(_)) => unreachable!(),
+		}
+	}
+}
+
+#[test]
+fn resumed_execution() {
+	let config = config();
+	let mut uninterrupted = runtime(ADD_AND_STORE, &config);
+	let mut expected_storage = Storage::default();
+	assert_eq!(
+		finish(&mut uninterrupted, &mut expected_storage),
+		ExitReason::Succeed(ExitSucceed::Returned)
+	);
+	assert_eq!(
+		uninterrupted.machine().return_value(),
+		H256::from_low_u64_be(7).as_bytes()
+	);
+
+	// Snapshot at each step, including after the exit.
+	for steps in 0..15 {
+		let mut interrupted = runtime(ADD_AND_STORE, &config);
+		let mut storage = Storage::default();
+		step(&mut interrupted, &mut storage, steps);
+
+		let snapshot = interrupted.snapshot();
+		let mut restored = Runtime::restore(snapshot.clone(), &config).unwrap();
+		assert_eq!(restored.snapshot(), snapshot);
+
+		assert_eq!(
+			finish(&mut restored, &mut storage),
+			ExitReason::Succeed(ExitSucceed::Returned)
+		);
+		assert_eq!(restored.snapshot(), uninterrupted.snapshot(), "{}", steps);
+		assert_eq!(storage.0, expected_storage.0);
+	}
+}
+
+#[test]
+fn restore_with_custom_immediates() {
+	// PUSH1 4, JUMP to the immediate data of ADDI, which is a JUMPDEST byte.
+	let code = "6004 56 0c5b 00";
+	let config = config();
+
+	let mut uninterrupted = runtime(code, &config);
+	assert_eq!(
+		finish(&mut uninterrupted, &mut Storage::default()),
+		ExitReason::Error(ExitError::InvalidJump)
+	);
+
+	let mut interrupted = runtime(code, &config);
+	step(&mut interrupted, &mut Storage::default(), 1);
+	let mut restored = Runtime::restore(interrupted.snapshot(), &config).unwrap();
+	assert_eq!(
+		finish(&mut restored, &mut Storage::default()),
+		ExitReason::Error(ExitError::InvalidJump)
+	);
+}
+
+#[cfg(any(feature = "with-serde", feature = "with-codec"))]
+fn snapshot() -> evm_runtime::RuntimeSnapshot {
+	let config = config();
+	let mut runtime = runtime(ADD_AND_STORE, &config);
+	step(&mut runtime, &mut Storage::default(), 4);
+	runtime.snapshot()
+}
+
+#[cfg(feature = "with-serde")]
+#[test]
+fn serde_round_trip() {
+	let snapshot = snapshot();
+	let json = serde_json::to_string(&snapshot).unwrap();
+	assert_eq!(
+		serde_json::from_str::<evm_runtime::RuntimeSnapshot>(&json).unwrap(),
+		snapshot
+	);
+}
+
+#[cfg(feature = "with-codec")]
+#[test]
+fn codec_round_trip() {
+	use codec::{Decode, Encode};
+
+	let snapshot = snapshot();
+	let encoded = snapshot.encode();
+	assert_eq!(
+		evm_runtime::RuntimeSnapshot::decode(&mut &encoded[..]).unwrap(),
+		snapshot
+	);
+}
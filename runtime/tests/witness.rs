@@ -0,0 +1,326 @@
+//! Each step of a runtime is checked to be reproduced by the verification
+//! of its witness, and tampered witnesses are checked to be rejected.
+
+mod common;
+
+use common::{Storage, ADDRESS};
+use evm_runtime::{
+	Capture, Config, Context, ExitError, ExitReason, Handler, Machine, Opcode, Runtime,
+	StepTransition, StepWitness,
+};
+use primitive_types::{H160, H256, U256};
+use std::sync::Arc;
+
+fn runtime<'config>(code: &str, data: &str, config: &'config Config) -> Runtime<'config> {
+	Runtime::new(
+		Arc::new(hex::decode(code).unwrap()),
+		Arc::new(hex::decode(data).unwrap()),
+		Context {
+			address: ADDRESS,
+			caller: H160::default(),
+			apparent_value: U256::zero(),
+		},
+		config,
+	)
+}
+
+/// Check that the transition verified from a witness matches the machine
+/// after the step.
+fn assert_transition(transition: &StepTransition, machine: &Machine, storage: &Storage) {
+	assert_eq!(
+		transition.position,
+		machine.position().clone().map(|position| position as u64)
+	);
+
+	let stack = machine.stack().data();
+	assert_eq!(transition.stack_len, stack.len() as u64);
+	assert_eq!(
+		transition.stack[..],
+		stack[(stack.len() - transition.stack.len())..]
+	);
+
+	assert_eq!(
+		transition.memory_effective_len,
+		machine.memory().effective_len()
+	);
+	for (offset, bytes) in &transition.memory {
+		assert_eq!(
+			bytes[..],
+			machine.memory().get(*offset as usize, bytes.len())[..]
+		);
+	}
+
+	if let Some((key, value)) = transition.storage {
+		assert_eq!(storage.storage(ADDRESS, key), value);
+	}
+	if let Some((start, end)) = transition.return_range {
+		let range = machine.return_range();
+		assert_eq!((start, end), (range.start, range.end));
+	}
+}
+
+/// Run code, verifying the witness of each step. Returns the witnessed
+/// opcodes and the exit reason.
+fn run(code: &str, data: &str, storage: &mut Storage) -> (Vec<Opcode>, ExitReason) {
+	let config = Config::london();
+	let mut runtime = runtime(code, data, &config);
+	let mut opcodes = Vec::new();
+
+	loop {
+		let witnessed = StepWitness::new(&runtime, &*storage).map(|witness| {
+			let opcode = runtime.machine().inspect().map(|(opcode, _)| opcode);
+			opcodes.push(opcode.unwrap_or(Opcode::STOP));
+			witness.verify().expect("witness is verified")
+		});
+
+		let exit = match runtime.step(storage) {
+			Ok(()) => None,
+			Err(Capture::Exit(reason)) => Some(reason),
+			Err(Capture::Trap(_)) => unreachable!(),
+		};
+		if let Some(transition) = witnessed {
+			assert_transition(&transition, runtime.machine(), storage);
+		}
+		if let Some(reason) = exit {
+			return (opcodes, reason);
+		}
+	}
+}
+
+/// Witness of the first step of the code at which `opcode` is next.
+fn witness_at(code: &str, data: &str, opcode: Opcode) -> StepWitness {
+	let config = Config::london();
+	let mut runtime = runtime(code, data, &config);
+	let mut storage = Storage::default();
+
+	loop {
+		if runtime.machine().inspect().map(|(next, _)| next) == Some(opcode) {
+			return StepWitness::new(&runtime, &storage).unwrap();
+		}
+		runtime
+			.step(&mut storage)
+			.unwrap_or_else(|_| panic!("no {:?}", opcode));
+	}
+}
+
+#[test]
+fn memory() {
+	// PUSH32, PUSH1 5, MSTORE, PUSH1 3, MLOAD, PUSH1 0xaa, PUSH1 0x40,
+	// MSTORE8, MSIZE, PUSH1 0xff, MLOAD, STOP
+	let code = format!("7f{}60055260035160aa6040535960ff5100", "11".repeat(32));
+	let (opcodes, reason) = run(&code, "", &mut Storage::default());
+	assert_eq!(
+		reason,
+		ExitReason::Succeed(evm_runtime::ExitSucceed::Stopped)
+	);
+	assert_eq!(
+		opcodes
+			.iter()
+			.filter(|opcode| [Opcode::MLOAD, Opcode::MSTORE, Opcode::MSTORE8].contains(opcode))
+			.count(),
+		4
+	);
+}
+
+#[test]
+fn call_data() {
+	// CALLDATACOPY 0x30 bytes from 4 to 0x10, CALLDATALOAD at 0x20 and past
+	// the end, CALLDATASIZE, and a copy starting past the end.
+	let code = "6030600460103760203560643536600860ff60403700";
+	let data = (0..40u8)
+		.map(|i| format!("{:02x}", i + 1))
+		.collect::<String>();
+	let (opcodes, _) = run(code, &data, &mut Storage::default());
+	assert_eq!(
+		opcodes
+			.iter()
+			.filter(|opcode| [Opcode::CALLDATACOPY, Opcode::CALLDATALOAD].contains(opcode))
+			.count(),
+		4
+	);
+}
+
+#[test]
+fn code_copy() {
+	// CODECOPY 32 bytes from 3 to 0, CODECOPY 16 bytes from 0xffff to 0x40,
+	// CODESIZE, PC, STOP
+	let code = "602060036000396010 61ffff 6040 39 38 58 00".replace(' ', "");
+	let (opcodes, _) = run(&code, "", &mut Storage::default());
+	assert!(opcodes.contains(&Opcode::CODECOPY));
+	assert!(opcodes.contains(&Opcode::CODESIZE));
+}
+
+#[test]
+fn storage() {
+	// SSTORE 0x2a at 1, SLOAD 1, SLOAD 2, STOP
+	let mut storage = Storage::default();
+	storage
+		.0
+		.insert(H256::from_low_u64_be(2), H256::from_low_u64_be(7));
+	let (opcodes, _) = run("602a6001556001546002540000", "", &mut storage);
+	assert_eq!(
+		opcodes,
+		[
+			Opcode::PUSH1,
+			Opcode::PUSH1,
+			Opcode::SSTORE,
+			Opcode::PUSH1,
+			Opcode::SLOAD,
+			Opcode::PUSH1,
+			Opcode::SLOAD,
+			Opcode::STOP,
+		]
+	);
+	assert_eq!(
+		storage.0[&H256::from_low_u64_be(1)],
+		H256::from_low_u64_be(0x2a)
+	);
+}
+
+#[test]
+fn jumps() {
+	// Count to 5 with JUMPI, then return the counter.
+	let (opcodes, reason) = run(
+		"60005b6001018060051160025760005260206000f3",
+		"",
+		&mut Storage::default(),
+	);
+	assert_eq!(
+		opcodes
+			.iter()
+			.filter(|opcode| **opcode == Opcode::JUMPI)
+			.count(),
+		5
+	);
+	assert!(reason.is_succeed());
+
+	// Jump to a JUMPDEST, into PUSH data, and past the end of the code.
+	let (_, reason) = run("600456005b00", "", &mut Storage::default());
+	assert!(reason.is_succeed());
+	let (_, reason) = run("600456605b00", "", &mut Storage::default());
+	assert_eq!(reason, ExitReason::Error(ExitError::InvalidJump));
+	let (_, reason) = run("600356", "", &mut Storage::default());
+	assert_eq!(reason, ExitReason::Error(ExitError::InvalidJump));
+}
+
+#[test]
+fn push_at_end_of_code() {
+	let (opcodes, _) = run("7faabb", "", &mut Storage::default());
+	assert_eq!(opcodes, [Opcode::PUSH32, Opcode::STOP]);
+
+	let witness = witness_at("7faabb", "", Opcode::PUSH32);
+	assert_eq!(witness.code, [(0, vec![0x7f, 0xaa, 0xbb])]);
+	// Missing immediate bytes are not padded.
+	assert_eq!(witness.verify().unwrap().stack, [U256::from(0xaabb)]);
+}
+
+#[test]
+fn stack_errors() {
+	// ADD on an empty stack.
+	let (_, reason) = run("01", "", &mut Storage::default());
+	assert_eq!(reason, ExitReason::Error(ExitError::StackUnderflow));
+
+	// DUP1 on a full stack.
+	let mut witness = witness_at("6001600101", "", Opcode::ADD);
+	witness.code = vec![(witness.position, vec![Opcode::DUP1.0])];
+	witness.stack_len = witness.stack_limit;
+	witness.stack = vec![U256::one()];
+	assert_eq!(
+		witness.verify().unwrap().position,
+		Err(ExitError::StackOverflow.into())
+	);
+}
+
+#[test]
+fn large_offsets_are_not_materialised() {
+	let far = 1u64 << 40;
+
+	// MSTORE at a far offset only holds its 32 bytes.
+	let mut witness = witness_at("602a60005200", "", Opcode::MSTORE);
+	witness.stack[1] = U256::from(far);
+	witness.memory = vec![(far, vec![0; 32])];
+	let transition = witness.verify().unwrap();
+	assert_eq!(
+		transition.memory,
+		[(far, H256::from_low_u64_be(0x2a).0.to_vec())]
+	);
+	assert_eq!(transition.memory_effective_len, U256::from(far + 32));
+
+	// CODESIZE and CALLDATASIZE of large code and data.
+	let mut witness = witness_at("3800", "", Opcode::CODESIZE);
+	witness.code_len = far;
+	assert_eq!(witness.verify().unwrap().stack, [U256::from(far)]);
+
+	let mut witness = witness_at("3600", "", Opcode::CALLDATASIZE);
+	witness.data_len = far;
+	assert_eq!(witness.verify().unwrap().stack, [U256::from(far)]);
+
+	// CALLDATALOAD far into large call data.
+	let mut witness = witness_at("60003500", "", Opcode::CALLDATALOAD);
+	witness.data_len = far + 1;
+	witness.stack = vec![U256::from(far)];
+	witness.data = vec![(far, vec![0xcc])];
+	assert_eq!(witness.verify().unwrap().stack, [U256::from(0xcc) << 248]);
+}
+
+#[test]
+fn tampered_witnesses_are_rejected() {
+	let code = format!("7f{}600552600351", "11".repeat(32));
+
+	// Chunk with a wrong offset, length or content count.
+	let witness = witness_at(&code, "", Opcode::MLOAD);
+	let mut tampered = witness.clone();
+	tampered.memory[0].0 += 1;
+	assert_eq!(tampered.verify(), None);
+	let mut tampered = witness.clone();
+	tampered.memory[0].1.pop();
+	assert_eq!(tampered.verify(), None);
+	let mut tampered = witness.clone();
+	tampered.memory.clear();
+	assert_eq!(tampered.verify(), None);
+	let mut tampered = witness.clone();
+	tampered.data.push((0, vec![0]));
+	assert_eq!(tampered.verify(), None);
+
+	// Stack items missing or in excess.
+	let mut tampered = witness.clone();
+	tampered.stack.push(U256::zero());
+	assert_eq!(tampered.verify(), None);
+	let mut tampered = witness.clone();
+	tampered.stack.clear();
+	assert_eq!(tampered.verify(), None);
+
+	// Stack longer than its limit.
+	let mut tampered = witness.clone();
+	tampered.stack_len = tampered.stack_limit + 1;
+	assert_eq!(tampered.verify(), None);
+
+	// Opcode not at the program counter, or beyond the stated code length.
+	let mut tampered = witness.clone();
+	tampered.code[0].0 -= 1;
+	assert_eq!(tampered.verify(), None);
+	let mut tampered = witness.clone();
+	tampered.code_len = tampered.position;
+	assert_eq!(tampered.verify(), None);
+
+	// Memory chunk past the memory limit.
+	let mut tampered = witness;
+	tampered.memory_limit = 3;
+	assert_eq!(tampered.verify(), None);
+
+	// Storage missing, or for another key.
+	let witness = witness_at("600154", "", Opcode::SLOAD);
+	let mut tampered = witness.clone();
+	tampered.storage = None;
+	assert_eq!(tampered.verify(), None);
+	let mut tampered = witness;
+	tampered.storage = Some((H256::from_low_u64_be(2), H256::zero()));
+	assert_eq!(tampered.verify(), None);
+
+	// Code before a jump destination truncated.
+	let witness = witness_at("600456005b00", "", Opcode::JUMP);
+	assert_eq!(witness.code.len(), 2);
+	let mut tampered = witness;
+	tampered.code[1].1.pop();
+	assert_eq!(tampered.verify(), None);
+}
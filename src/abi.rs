@@ -0,0 +1,1074 @@
+//! Minimal Solidity ABI encoding and decoding, for function calls, return
+//! data and logs.
+
+use crate::backend::Log;
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{cmp::max, fmt, iter};
+use primitive_types::{H160, H256, U256};
+use sha3::{Digest, Keccak256};
+
+/// ABI error.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AbiError {
+	/// The signature is malformed.
+	InvalidSignature,
+	/// A type of the signature is not a valid ABI type.
+	InvalidType,
+	/// A value does not match the type it is encoded as.
+	TypeMismatch,
+	/// The encoded data is malformed.
+	InvalidData,
+	/// The selector or event topic does not match.
+	InvalidSelector,
+}
+
+/// ABI type.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AbiType {
+	/// `address`.
+	Address,
+	/// `bool`.
+	Bool,
+	/// `uintN`, with its size in bits.
+	Uint(usize),
+	/// `intN`, with its size in bits.
+	Int(usize),
+	/// `bytesN`, with its size in bytes.
+	FixedBytes(usize),
+	/// `bytes`.
+	Bytes,
+	/// `string`.
+	String,
+	/// `T[]`.
+	Array(Box<AbiType>),
+	/// `T[N]`.
+	FixedArray(Box<AbiType>, usize),
+	/// `(T1,T2,...)`.
+	Tuple(Vec<AbiType>),
+}
+
+impl AbiType {
+	/// Parse a type, such as `uint256`, `bytes32[]` or `(address,bool)[2]`.
+	pub fn parse(ty: &str) -> Result<Self, AbiError> {
+		let ty = ty.trim();
+
+		if let Some(rest) = ty.strip_suffix(']') {
+			let open = rest.rfind('[').ok_or(AbiError::InvalidType)?;
+			let inner = Box::new(Self::parse(&rest[..open])?);
+			return match &rest[open + 1..] {
+				"" => Ok(Self::Array(inner)),
+				len => Ok(Self::FixedArray(
+					inner,
+					len.parse().map_err(|_| AbiError::InvalidType)?,
+				)),
+			};
+		}
+
+		if let Some(inner) = ty.strip_prefix('(').and_then(|ty| ty.strip_suffix(')')) {
+			return split_params(inner)?
+				.into_iter()
+				.map(Self::parse)
+				.collect::<Result<_, _>>()
+				.map(Self::Tuple);
+		}
+
+		let size = |prefix: &str, default: usize| -> Option<usize> {
+			match ty.strip_prefix(prefix)? {
+				"" => Some(default),
+				size => size.parse().ok(),
+			}
+		};
+
+		match ty {
+			"address" => Ok(Self::Address),
+			"bool" => Ok(Self::Bool),
+			"string" => Ok(Self::String),
+			"bytes" => Ok(Self::Bytes),
+			_ => {
+				if let Some(bits) = size("uint", 256) {
+					if bits > 0 && bits <= 256 && bits % 8 == 0 {
+						return Ok(Self::Uint(bits));
+					}
+				} else if let Some(bits) = size("int", 256) {
+					if bits > 0 && bits <= 256 && bits % 8 == 0 {
+						return Ok(Self::Int(bits));
+					}
+				} else if let Some(len) = ty.strip_prefix("bytes").and_then(|l| l.parse().ok()) {
+					if len > 0 && len <= 32 {
+						return Ok(Self::FixedBytes(len));
+					}
+				}
+
+				Err(AbiError::InvalidType)
+			}
+		}
+	}
+
+	/// Whether the type is encoded out of place.
+	pub fn is_dynamic(&self) -> bool {
+		match self {
+			Self::Bytes | Self::String | Self::Array(_) => true,
+			Self::FixedArray(ty, _) => ty.is_dynamic(),
+			Self::Tuple(types) => types.iter().any(|ty| ty.is_dynamic()),
+			_ => false,
+		}
+	}
+
+	fn head_size(&self) -> usize {
+		match self {
+			_ if self.is_dynamic() => 32,
+			Self::FixedArray(ty, len) => ty.head_size().saturating_mul(*len),
+			Self::Tuple(types) => types.iter().map(|ty| ty.head_size()).sum(),
+			_ => 32,
+		}
+	}
+}
+
+impl fmt::Display for AbiType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Address => write!(f, "address"),
+			Self::Bool => write!(f, "bool"),
+			Self::Uint(bits) => write!(f, "uint{}", bits),
+			Self::Int(bits) => write!(f, "int{}", bits),
+			Self::FixedBytes(len) => write!(f, "bytes{}", len),
+			Self::Bytes => write!(f, "bytes"),
+			Self::String => write!(f, "string"),
+			Self::Array(ty) => write!(f, "{}[]", ty),
+			Self::FixedArray(ty, len) => write!(f, "{}[{}]", ty, len),
+			Self::Tuple(types) => {
+				write!(f, "(")?;
+				write_types(f, types)?;
+				write!(f, ")")
+			}
+		}
+	}
+}
+
+fn write_types(f: &mut fmt::Formatter<'_>, types: &[AbiType]) -> fmt::Result {
+	for (i, ty) in types.iter().enumerate() {
+		if i > 0 {
+			write!(f, ",")?;
+		}
+		write!(f, "{}", ty)?;
+	}
+	Ok(())
+}
+
+/// ABI value.
+///
+/// Integers are stored as 256-bit words, in two's complement for `Int`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AbiValue {
+	/// `address`.
+	Address(H160),
+	/// `bool`.
+	Bool(bool),
+	/// `uintN`, with its size in bits.
+	Uint(U256, usize),
+	/// `intN`, with its size in bits.
+	Int(U256, usize),
+	/// `bytesN`.
+	FixedBytes(Vec<u8>),
+	/// `bytes`.
+	Bytes(Vec<u8>),
+	/// `string`.
+	String(String),
+	/// `T[]`.
+	Array(Vec<AbiValue>),
+	/// `T[N]`.
+	FixedArray(Vec<AbiValue>),
+	/// `(T1,T2,...)`.
+	Tuple(Vec<AbiValue>),
+}
+
+impl AbiValue {
+	/// Whether the value can be encoded as the given type.
+	pub fn matches(&self, ty: &AbiType) -> bool {
+		match (self, ty) {
+			(Self::Address(_), AbiType::Address) => true,
+			(Self::Bool(_), AbiType::Bool) => true,
+			(Self::Uint(value, bits), AbiType::Uint(size)) => {
+				bits == size && (*size == 256 || *value >> *size == U256::zero())
+			}
+			(Self::Int(value, bits), AbiType::Int(size)) => bits == size && fits_int(*value, *size),
+			(Self::FixedBytes(value), AbiType::FixedBytes(len)) => value.len() == *len,
+			(Self::Bytes(_), AbiType::Bytes) => true,
+			(Self::String(_), AbiType::String) => true,
+			(Self::Array(values), AbiType::Array(ty)) => values.iter().all(|v| v.matches(ty)),
+			(Self::FixedArray(values), AbiType::FixedArray(ty, len)) => {
+				values.len() == *len && values.iter().all(|v| v.matches(ty))
+			}
+			(Self::Tuple(values), AbiType::Tuple(types)) => {
+				values.len() == types.len() && values.iter().zip(types).all(|(v, ty)| v.matches(ty))
+			}
+			_ => false,
+		}
+	}
+
+	fn is_dynamic(&self) -> bool {
+		match self {
+			Self::Bytes(_) | Self::String(_) | Self::Array(_) => true,
+			Self::FixedArray(values) | Self::Tuple(values) => values.iter().any(|v| v.is_dynamic()),
+			_ => false,
+		}
+	}
+}
+
+fn fits_int(value: U256, bits: usize) -> bool {
+	if bits == 256 {
+		return true;
+	}
+
+	// All bits above the sign bit must be equal to it.
+	let high = value >> (bits - 1);
+	high == U256::zero() || high == U256::MAX >> (bits - 1)
+}
+
+/// ABI encode values, as the arguments of a function call.
+pub fn encode(values: &[AbiValue]) -> Vec<u8> {
+	let mut out = Vec::new();
+	encode_sequence(values, &mut out);
+	out
+}
+
+fn encode_sequence(values: &[AbiValue], out: &mut Vec<u8>) {
+	let head_size: usize = values
+		.iter()
+		.map(|value| match value {
+			_ if value.is_dynamic() => 32,
+			AbiValue::FixedArray(values) | AbiValue::Tuple(values) => encode(values).len(),
+			_ => 32,
+		})
+		.sum();
+
+	let mut tail = Vec::new();
+	for value in values {
+		if value.is_dynamic() {
+			push_word(out, U256::from(head_size + tail.len()));
+			encode_value(value, &mut tail);
+		} else {
+			encode_value(value, out);
+		}
+	}
+	out.extend_from_slice(&tail);
+}
+
+fn encode_value(value: &AbiValue, out: &mut Vec<u8>) {
+	match value {
+		AbiValue::Address(address) => push_word(out, U256::from_big_endian(&address[..])),
+		AbiValue::Bool(value) => push_word(out, U256::from(*value as u8)),
+		AbiValue::Uint(value, _) | AbiValue::Int(value, _) => push_word(out, *value),
+		AbiValue::FixedBytes(value) => push_padded(out, value),
+		AbiValue::Bytes(value) => {
+			push_word(out, U256::from(value.len()));
+			push_padded(out, value);
+		}
+		AbiValue::String(value) => {
+			push_word(out, U256::from(value.len()));
+			push_padded(out, value.as_bytes());
+		}
+		AbiValue::Array(values) => {
+			push_word(out, U256::from(values.len()));
+			encode_sequence(values, out);
+		}
+		AbiValue::FixedArray(values) | AbiValue::Tuple(values) => encode_sequence(values, out),
+	}
+}
+
+fn push_word(out: &mut Vec<u8>, value: U256) {
+	let mut word = [0u8; 32];
+	value.to_big_endian(&mut word);
+	out.extend_from_slice(&word);
+}
+
+fn push_padded(out: &mut Vec<u8>, value: &[u8]) {
+	out.extend_from_slice(value);
+	let padding = (32 - value.len() % 32) % 32;
+	out.resize(out.len() + padding, 0);
+}
+
+/// ABI decode values of the given types.
+pub fn decode(types: &[AbiType], data: &[u8]) -> Result<Vec<AbiValue>, AbiError> {
+	decode_sequence(types.iter(), data)
+}
+
+fn decode_sequence<'a, I: Iterator<Item = &'a AbiType>>(
+	types: I,
+	data: &[u8],
+) -> Result<Vec<AbiValue>, AbiError> {
+	let mut values = Vec::new();
+	let mut position = 0;
+	for ty in types {
+		if ty.is_dynamic() {
+			let offset = read_usize(data, position)?;
+			values.push(decode_value(
+				ty,
+				data.get(offset..).ok_or(AbiError::InvalidData)?,
+			)?);
+		} else {
+			values.push(decode_value(
+				ty,
+				data.get(position..).ok_or(AbiError::InvalidData)?,
+			)?);
+		}
+		position = position
+			.checked_add(ty.head_size())
+			.ok_or(AbiError::InvalidData)?;
+	}
+
+	Ok(values)
+}
+
+fn decode_value(ty: &AbiType, data: &[u8]) -> Result<AbiValue, AbiError> {
+	match ty {
+		AbiType::Address => {
+			let word = read_word(data, 0)?;
+			if word[..12].iter().any(|b| *b != 0) {
+				return Err(AbiError::InvalidData);
+			}
+			Ok(AbiValue::Address(H160::from_slice(&word[12..])))
+		}
+		AbiType::Bool => match U256::from_big_endian(read_word(data, 0)?) {
+			value if value == U256::zero() => Ok(AbiValue::Bool(false)),
+			value if value == U256::one() => Ok(AbiValue::Bool(true)),
+			_ => Err(AbiError::InvalidData),
+		},
+		AbiType::Uint(bits) => {
+			let value = AbiValue::Uint(U256::from_big_endian(read_word(data, 0)?), *bits);
+			if value.matches(ty) {
+				Ok(value)
+			} else {
+				Err(AbiError::InvalidData)
+			}
+		}
+		AbiType::Int(bits) => {
+			let value = AbiValue::Int(U256::from_big_endian(read_word(data, 0)?), *bits);
+			if value.matches(ty) {
+				Ok(value)
+			} else {
+				Err(AbiError::InvalidData)
+			}
+		}
+		AbiType::FixedBytes(len) => Ok(AbiValue::FixedBytes(read_word(data, 0)?[..*len].to_vec())),
+		AbiType::Bytes => Ok(AbiValue::Bytes(read_bytes(data)?.to_vec())),
+		AbiType::String => String::from_utf8(read_bytes(data)?.to_vec())
+			.map(AbiValue::String)
+			.map_err(|_| AbiError::InvalidData),
+		AbiType::Array(ty) => {
+			let len = read_usize(data, 0)?;
+			let data = &data[32..];
+			// Reject lengths the data cannot hold before allocating.
+			if len
+				.checked_mul(max(ty.head_size(), 1))
+				.ok_or(AbiError::InvalidData)?
+				> data.len()
+			{
+				return Err(AbiError::InvalidData);
+			}
+			decode_sequence(iter::repeat(&**ty).take(len), data).map(AbiValue::Array)
+		}
+		AbiType::FixedArray(ty, len) => {
+			decode_sequence(iter::repeat(&**ty).take(*len), data).map(AbiValue::FixedArray)
+		}
+		AbiType::Tuple(types) => decode_sequence(types.iter(), data).map(AbiValue::Tuple),
+	}
+}
+
+fn read_word(data: &[u8], position: usize) -> Result<&[u8], AbiError> {
+	position
+		.checked_add(32)
+		.and_then(|end| data.get(position..end))
+		.ok_or(AbiError::InvalidData)
+}
+
+fn read_usize(data: &[u8], position: usize) -> Result<usize, AbiError> {
+	let value = U256::from_big_endian(read_word(data, position)?);
+	if value > U256::from(usize::MAX) {
+		return Err(AbiError::InvalidData);
+	}
+	Ok(value.as_usize())
+}
+
+fn read_bytes(data: &[u8]) -> Result<&[u8], AbiError> {
+	let len = read_usize(data, 0)?;
+	len.checked_add(32)
+		.and_then(|end| data.get(32..end))
+		.ok_or(AbiError::InvalidData)
+}
+
+/// Solidity function.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Function {
+	/// Name of the function.
+	pub name: String,
+	/// Types of the arguments.
+	pub inputs: Vec<AbiType>,
+	/// Types of the return values.
+	pub outputs: Vec<AbiType>,
+}
+
+impl Function {
+	/// Parse a function signature, such as `transfer(address,uint256)`, with
+	/// optional return types as `balanceOf(address)(uint256)` or
+	/// `balanceOf(address) returns (uint256)`. Parameter names are ignored.
+	pub fn parse(signature: &str) -> Result<Self, AbiError> {
+		let (name, inputs, rest) = split_signature(signature)?;
+		let rest = rest.strip_prefix("returns").unwrap_or(rest).trim_start();
+
+		let outputs = if rest.is_empty() {
+			Vec::new()
+		} else {
+			let (name, outputs, rest) = split_signature(rest)?;
+			if !name.is_empty() || !rest.is_empty() {
+				return Err(AbiError::InvalidSignature);
+			}
+			parse_params(outputs)?
+				.into_iter()
+				.map(|(ty, _)| ty)
+				.collect()
+		};
+
+		Ok(Self {
+			name: String::from(name),
+			inputs: parse_params(inputs)?
+				.into_iter()
+				.map(|(ty, _)| ty)
+				.collect(),
+			outputs,
+		})
+	}
+
+	/// Canonical signature, such as `transfer(address,uint256)`.
+	pub fn signature(&self) -> String {
+		alloc::format!("{}({})", self.name, Types(&self.inputs))
+	}
+
+	/// Four bytes selector of the function.
+	pub fn selector(&self) -> [u8; 4] {
+		let hash = Keccak256::digest(self.signature().as_bytes());
+		[hash[0], hash[1], hash[2], hash[3]]
+	}
+
+	/// Encode a call of the function with the given arguments.
+	pub fn encode_input(&self, args: &[AbiValue]) -> Result<Vec<u8>, AbiError> {
+		if args.len() != self.inputs.len()
+			|| !args
+				.iter()
+				.zip(&self.inputs)
+				.all(|(arg, ty)| arg.matches(ty))
+		{
+			return Err(AbiError::TypeMismatch);
+		}
+
+		let mut out = self.selector().to_vec();
+		encode_sequence(args, &mut out);
+		Ok(out)
+	}
+
+	/// Decode the arguments of a call of the function.
+	pub fn decode_input(&self, input: &[u8]) -> Result<Vec<AbiValue>, AbiError> {
+		if input.len() < 4 || input[..4] != self.selector() {
+			return Err(AbiError::InvalidSelector);
+		}
+		decode(&self.inputs, &input[4..])
+	}
+
+	/// Decode the return data of the function.
+	pub fn decode_output(&self, output: &[u8]) -> Result<Vec<AbiValue>, AbiError> {
+		decode(&self.outputs, output)
+	}
+}
+
+/// Parameter of a Solidity event.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EventParam {
+	/// Type of the parameter.
+	pub ty: AbiType,
+	/// Whether the parameter is stored in a topic.
+	pub indexed: bool,
+}
+
+/// Solidity event.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Event {
+	/// Name of the event.
+	pub name: String,
+	/// Parameters of the event.
+	pub inputs: Vec<EventParam>,
+	/// Whether the event is anonymous, without its signature as first topic.
+	pub anonymous: bool,
+}
+
+impl Event {
+	/// Parse an event signature, such as
+	/// `Transfer(address indexed from, address indexed to, uint256 value)`,
+	/// optionally followed by `anonymous`.
+	pub fn parse(signature: &str) -> Result<Self, AbiError> {
+		let (name, inputs, rest) = split_signature(signature)?;
+		let anonymous = match rest {
+			"" => false,
+			"anonymous" => true,
+			_ => return Err(AbiError::InvalidSignature),
+		};
+
+		Ok(Self {
+			name: String::from(name),
+			inputs: parse_params(inputs)?
+				.into_iter()
+				.map(|(ty, indexed)| EventParam { ty, indexed })
+				.collect(),
+			anonymous,
+		})
+	}
+
+	/// Canonical signature, such as `Transfer(address,address,uint256)`.
+	pub fn signature(&self) -> String {
+		let types: Vec<AbiType> = self.inputs.iter().map(|p| p.ty.clone()).collect();
+		alloc::format!("{}({})", self.name, Types(&types))
+	}
+
+	/// Topic of the event signature.
+	pub fn topic(&self) -> H256 {
+		H256::from_slice(Keccak256::digest(self.signature().as_bytes()).as_slice())
+	}
+
+	/// Decode the parameters of a log of the event, in declaration order.
+	///
+	/// Indexed parameters of dynamic, array or tuple types are stored as the
+	/// hash of their encoding, and are returned as `bytes32`.
+	pub fn decode_log(&self, log: &Log) -> Result<Vec<AbiValue>, AbiError> {
+		let mut topics = log.topics.iter();
+		if !self.anonymous && topics.next() != Some(&self.topic()) {
+			return Err(AbiError::InvalidSelector);
+		}
+
+		let indexed = self.inputs.iter().filter(|p| p.indexed).count();
+		if topics.len() != indexed {
+			return Err(AbiError::InvalidData);
+		}
+
+		let data_types: Vec<AbiType> = self
+			.inputs
+			.iter()
+			.filter(|p| !p.indexed)
+			.map(|p| p.ty.clone())
+			.collect();
+		let mut data = decode(&data_types, &log.data)?.into_iter();
+
+		self.inputs
+			.iter()
+			.map(|param| {
+				if !param.indexed {
+					return data.next().ok_or(AbiError::InvalidData);
+				}
+
+				let topic = topics.next().ok_or(AbiError::InvalidData)?;
+				match param.ty {
+					AbiType::Bytes
+					| AbiType::String
+					| AbiType::Array(_)
+					| AbiType::FixedArray(_, _)
+					| AbiType::Tuple(_) => Ok(AbiValue::FixedBytes(topic[..].to_vec())),
+					_ => decode_value(&param.ty, &topic[..]),
+				}
+			})
+			.collect()
+	}
+}
+
+struct Types<'a>(&'a [AbiType]);
+
+impl<'a> fmt::Display for Types<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write_types(f, self.0)
+	}
+}
+
+/// Split `name(params) rest` into its parts.
+fn split_signature(signature: &str) -> Result<(&str, &str, &str), AbiError> {
+	let signature = signature.trim();
+	let open = signature.find('(').ok_or(AbiError::InvalidSignature)?;
+	let close = matching_paren(signature, open)?;
+
+	let name = signature[..open].trim();
+	if !name
+		.bytes()
+		.all(|c| c.is_ascii_alphanumeric() || c == b'_' || c == b'$')
+	{
+		return Err(AbiError::InvalidSignature);
+	}
+
+	Ok((
+		name,
+		&signature[open + 1..close],
+		signature[close + 1..].trim(),
+	))
+}
+
+fn matching_paren(s: &str, open: usize) -> Result<usize, AbiError> {
+	let mut depth = 0;
+	for (i, c) in s[open..].char_indices() {
+		match c {
+			'(' => depth += 1,
+			')' => {
+				depth -= 1;
+				if depth == 0 {
+					return Ok(open + i);
+				}
+			}
+			_ => (),
+		}
+	}
+	Err(AbiError::InvalidSignature)
+}
+
+/// Split a parameter list at its top level commas.
+fn split_params(params: &str) -> Result<Vec<&str>, AbiError> {
+	if params.trim().is_empty() {
+		return Ok(Vec::new());
+	}
+
+	let mut out = Vec::new();
+	let mut depth = 0usize;
+	let mut start = 0;
+	for (i, c) in params.char_indices() {
+		match c {
+			'(' => depth += 1,
+			')' => depth = depth.checked_sub(1).ok_or(AbiError::InvalidSignature)?,
+			',' if depth == 0 => {
+				out.push(&params[start..i]);
+				start = i + 1;
+			}
+			_ => (),
+		}
+	}
+	out.push(&params[start..]);
+
+	Ok(out)
+}
+
+/// Parse parameters as `type [indexed] [name]`, returning their types and
+/// whether they are indexed.
+fn parse_params(params: &str) -> Result<Vec<(AbiType, bool)>, AbiError> {
+	split_params(params)?
+		.into_iter()
+		.map(|param| {
+			let param = param.trim();
+			let end = if param.starts_with('(') {
+				let close = matching_paren(param, 0)?;
+				close + param[close..].find(' ').unwrap_or(param.len() - close)
+			} else {
+				param.find(' ').unwrap_or(param.len())
+			};
+
+			let ty = AbiType::parse(&param[..end])?;
+			let indexed = param[end..]
+				.split_whitespace()
+				.any(|word| word == "indexed");
+			Ok((ty, indexed))
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Decode hex, ignoring whitespace.
+	fn hex(data: &str) -> Vec<u8> {
+		hex::decode(data.split_whitespace().collect::<String>()).unwrap()
+	}
+
+	fn uint(value: u64) -> AbiValue {
+		AbiValue::Uint(U256::from(value), 256)
+	}
+
+	fn string(value: &str) -> AbiValue {
+		AbiValue::String(String::from(value))
+	}
+
+	/// Check both encoding and decoding of a call.
+	fn assert_call(signature: &str, args: &[AbiValue], expected: &str) {
+		let function = Function::parse(signature).unwrap();
+		let expected = hex(expected);
+		assert_eq!(function.encode_input(args).unwrap(), expected);
+		assert_eq!(function.decode_input(&expected).unwrap(), args);
+	}
+
+	#[test]
+	fn parse_types() {
+		for ty in [
+			"address",
+			"bool",
+			"uint8",
+			"int256",
+			"bytes1",
+			"bytes32",
+			"bytes",
+			"string",
+			"uint256[]",
+			"bytes32[3][]",
+			"(address,(bool,string)[])[2]",
+		] {
+			assert_eq!(AbiType::parse(ty).unwrap().to_string(), ty);
+		}
+		assert_eq!(AbiType::parse("uint").unwrap(), AbiType::Uint(256));
+		assert_eq!(AbiType::parse("int").unwrap(), AbiType::Int(256));
+
+		for ty in [
+			"uint7", "uint0", "uint264", "int9", "bytes0", "bytes33", "foo", "uint[x]", "uint]",
+		] {
+			assert_eq!(AbiType::parse(ty), Err(AbiError::InvalidType), "{}", ty);
+		}
+	}
+
+	#[test]
+	fn selectors() {
+		let transfer = Function::parse("transfer(address to, uint256 amount)").unwrap();
+		assert_eq!(transfer.signature(), "transfer(address,uint256)");
+		assert_eq!(transfer.selector(), [0xa9, 0x05, 0x9c, 0xbb]);
+
+		for signature in [
+			"balanceOf(address)(uint256)",
+			"balanceOf(address owner) returns (uint256 balance)",
+		] {
+			let function = Function::parse(signature).unwrap();
+			assert_eq!(function.selector(), [0x70, 0xa0, 0x82, 0x31]);
+			assert_eq!(function.outputs, [AbiType::Uint(256)]);
+		}
+
+		assert_eq!(
+			Function::parse("f((uint256,address)[],bool)")
+				.unwrap()
+				.signature(),
+			"f((uint256,address)[],bool)"
+		);
+		for signature in ["f(", "f(uint256", "f-g()", "f()(uint256) x", "f() y"] {
+			assert_eq!(
+				Function::parse(signature),
+				Err(AbiError::InvalidSignature),
+				"{}",
+				signature
+			);
+		}
+	}
+
+	#[test]
+	fn static_types() {
+		assert_call(
+			"baz(uint32,bool)",
+			&[AbiValue::Uint(U256::from(69), 32), AbiValue::Bool(true)],
+			"cdcd77c0
+			0000000000000000000000000000000000000000000000000000000000000045
+			0000000000000000000000000000000000000000000000000000000000000001",
+		);
+		assert_call(
+			"bar(bytes3[2])",
+			&[AbiValue::FixedArray(vec![
+				AbiValue::FixedBytes(b"abc".to_vec()),
+				AbiValue::FixedBytes(b"def".to_vec()),
+			])],
+			"fce353f6
+			6162630000000000000000000000000000000000000000000000000000000000
+			6465660000000000000000000000000000000000000000000000000000000000",
+		);
+		assert_call(
+			"f(address)",
+			&[AbiValue::Address(H160::repeat_byte(0x11))],
+			"fc68521a
+			0000000000000000000000001111111111111111111111111111111111111111",
+		);
+	}
+
+	#[test]
+	fn dynamic_types() {
+		assert_call(
+			"sam(bytes,bool,uint256[])",
+			&[
+				AbiValue::Bytes(b"dave".to_vec()),
+				AbiValue::Bool(true),
+				AbiValue::Array(vec![uint(1), uint(2), uint(3)]),
+			],
+			"a5643bf2
+			0000000000000000000000000000000000000000000000000000000000000060
+			0000000000000000000000000000000000000000000000000000000000000001
+			00000000000000000000000000000000000000000000000000000000000000a0
+			0000000000000000000000000000000000000000000000000000000000000004
+			6461766500000000000000000000000000000000000000000000000000000000
+			0000000000000000000000000000000000000000000000000000000000000003
+			0000000000000000000000000000000000000000000000000000000000000001
+			0000000000000000000000000000000000000000000000000000000000000002
+			0000000000000000000000000000000000000000000000000000000000000003",
+		);
+		assert_call(
+			"f(uint256,uint32[],bytes10,bytes)",
+			&[
+				uint(0x123),
+				AbiValue::Array(vec![
+					AbiValue::Uint(U256::from(0x456), 32),
+					AbiValue::Uint(U256::from(0x789), 32),
+				]),
+				AbiValue::FixedBytes(b"1234567890".to_vec()),
+				AbiValue::Bytes(b"Hello, world!".to_vec()),
+			],
+			"8be65246
+			0000000000000000000000000000000000000000000000000000000000000123
+			0000000000000000000000000000000000000000000000000000000000000080
+			3132333435363738393000000000000000000000000000000000000000000000
+			00000000000000000000000000000000000000000000000000000000000000e0
+			0000000000000000000000000000000000000000000000000000000000000002
+			0000000000000000000000000000000000000000000000000000000000000456
+			0000000000000000000000000000000000000000000000000000000000000789
+			000000000000000000000000000000000000000000000000000000000000000d
+			48656c6c6f2c20776f726c642100000000000000000000000000000000000000",
+		);
+	}
+
+	#[test]
+	fn nested_arrays_and_tuples() {
+		assert_call(
+			"g(uint256[][],string[])",
+			&[
+				AbiValue::Array(vec![
+					AbiValue::Array(vec![uint(1), uint(2)]),
+					AbiValue::Array(vec![uint(3)]),
+				]),
+				AbiValue::Array(vec![string("one"), string("two"), string("three")]),
+			],
+			"2289b18c
+			0000000000000000000000000000000000000000000000000000000000000040
+			0000000000000000000000000000000000000000000000000000000000000140
+			0000000000000000000000000000000000000000000000000000000000000002
+			0000000000000000000000000000000000000000000000000000000000000040
+			00000000000000000000000000000000000000000000000000000000000000a0
+			0000000000000000000000000000000000000000000000000000000000000002
+			0000000000000000000000000000000000000000000000000000000000000001
+			0000000000000000000000000000000000000000000000000000000000000002
+			0000000000000000000000000000000000000000000000000000000000000001
+			0000000000000000000000000000000000000000000000000000000000000003
+			0000000000000000000000000000000000000000000000000000000000000003
+			0000000000000000000000000000000000000000000000000000000000000060
+			00000000000000000000000000000000000000000000000000000000000000a0
+			00000000000000000000000000000000000000000000000000000000000000e0
+			0000000000000000000000000000000000000000000000000000000000000003
+			6f6e650000000000000000000000000000000000000000000000000000000000
+			0000000000000000000000000000000000000000000000000000000000000003
+			74776f0000000000000000000000000000000000000000000000000000000000
+			0000000000000000000000000000000000000000000000000000000000000005
+			7468726565000000000000000000000000000000000000000000000000000000",
+		);
+
+		// A static tuple is encoded in place, a dynamic one out of place.
+		let types = [
+			AbiType::parse("(uint256,bool)").unwrap(),
+			AbiType::parse("(uint256,string)[2]").unwrap(),
+		];
+		let values = [
+			AbiValue::Tuple(vec![uint(1), AbiValue::Bool(true)]),
+			AbiValue::FixedArray(vec![
+				AbiValue::Tuple(vec![uint(2), string("a")]),
+				AbiValue::Tuple(vec![uint(3), string("b")]),
+			]),
+		];
+		let encoded = hex(
+			"0000000000000000000000000000000000000000000000000000000000000001
+			0000000000000000000000000000000000000000000000000000000000000001
+			0000000000000000000000000000000000000000000000000000000000000060
+			0000000000000000000000000000000000000000000000000000000000000040
+			00000000000000000000000000000000000000000000000000000000000000c0
+			0000000000000000000000000000000000000000000000000000000000000002
+			0000000000000000000000000000000000000000000000000000000000000040
+			0000000000000000000000000000000000000000000000000000000000000001
+			6100000000000000000000000000000000000000000000000000000000000000
+			0000000000000000000000000000000000000000000000000000000000000003
+			0000000000000000000000000000000000000000000000000000000000000040
+			0000000000000000000000000000000000000000000000000000000000000001
+			6200000000000000000000000000000000000000000000000000000000000000",
+		);
+		assert_eq!(encode(&values), encoded);
+		assert_eq!(decode(&types, &encoded).unwrap(), values);
+	}
+
+	#[test]
+	fn signed_integers_and_fixed_bytes() {
+		let minus_one = AbiValue::Int(U256::MAX, 8);
+		let minus_two = AbiValue::Int(U256::MAX - 1, 16);
+		let max = AbiValue::Int(U256::from(0x7f), 8);
+		let min = AbiValue::Int(U256::MAX - 0x7f, 8);
+		let f = Function::parse("f(int8,int16,int8,int8)").unwrap();
+
+		let args = [minus_one, minus_two, max, min];
+		let input = f.encode_input(&args).unwrap();
+		assert_eq!(
+			input[4..],
+			hex(
+				"ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff
+				fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffe
+				000000000000000000000000000000000000000000000000000000000000007f
+				ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff80"
+			)[..]
+		);
+		assert_eq!(f.decode_input(&input).unwrap(), args);
+
+		// Values not sign extended from their size.
+		let f = Function::parse("f(int8)").unwrap();
+		for value in [U256::from(0x80), U256::from(0xff), U256::MAX - 0x80] {
+			assert_eq!(
+				f.encode_input(&[AbiValue::Int(value, 8)]),
+				Err(AbiError::TypeMismatch)
+			);
+			let mut input = f.selector().to_vec();
+			push_word(&mut input, value);
+			assert_eq!(f.decode_input(&input), Err(AbiError::InvalidData));
+		}
+
+		// Unsigned values over their size.
+		let f = Function::parse("f(uint8)").unwrap();
+		assert_eq!(
+			f.encode_input(&[AbiValue::Uint(U256::from(0x100), 8)]),
+			Err(AbiError::TypeMismatch)
+		);
+
+		// bytesN values must have N bytes.
+		let f = Function::parse("f(bytes2)").unwrap();
+		assert_eq!(
+			f.encode_input(&[AbiValue::FixedBytes(vec![1])]),
+			Err(AbiError::TypeMismatch)
+		);
+		assert_eq!(
+			f.decode_input(&f.encode_input(&[AbiValue::FixedBytes(vec![1, 2])]).unwrap())
+				.unwrap(),
+			[AbiValue::FixedBytes(vec![1, 2])]
+		);
+	}
+
+	#[test]
+	fn events() {
+		let transfer =
+			Event::parse("Transfer(address indexed from, address indexed to, uint256 value)")
+				.unwrap();
+		assert_eq!(transfer.signature(), "Transfer(address,address,uint256)");
+		assert_eq!(
+			transfer.topic(),
+			H256::from_slice(&hex(
+				"ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+			))
+		);
+
+		let from = H160::repeat_byte(1);
+		let to = H160::repeat_byte(2);
+		let log = Log {
+			address: H160::zero(),
+			topics: vec![transfer.topic(), from.into(), to.into()],
+			data: encode(&[uint(100)]),
+		};
+		assert_eq!(
+			transfer.decode_log(&log).unwrap(),
+			[AbiValue::Address(from), AbiValue::Address(to), uint(100)]
+		);
+
+		// Indexed dynamic values are hashed, and returned as the hash.
+		let event =
+			Event::parse("Named(string indexed name, uint256[] indexed ids, bytes data)").unwrap();
+		let name = H256::from_slice(&Keccak256::digest(b"alice"));
+		let ids = H256::repeat_byte(3);
+		let log = Log {
+			address: H160::zero(),
+			topics: vec![event.topic(), name, ids],
+			data: encode(&[AbiValue::Bytes(vec![1, 2, 3])]),
+		};
+		assert_eq!(
+			event.decode_log(&log).unwrap(),
+			[
+				AbiValue::FixedBytes(name[..].to_vec()),
+				AbiValue::FixedBytes(ids[..].to_vec()),
+				AbiValue::Bytes(vec![1, 2, 3]),
+			]
+		);
+
+		// Anonymous events have no signature topic.
+		let anonymous = Event::parse("Anon(uint256 indexed a) anonymous").unwrap();
+		let log = Log {
+			address: H160::zero(),
+			topics: vec![H256::from_low_u64_be(7)],
+			data: Vec::new(),
+		};
+		assert_eq!(anonymous.decode_log(&log).unwrap(), [uint(7)]);
+
+		// Wrong signature topic, and wrong number of topics.
+		let mut log = Log {
+			address: H160::zero(),
+			topics: vec![anonymous.topic(), from.into(), to.into()],
+			data: encode(&[uint(100)]),
+		};
+		assert_eq!(transfer.decode_log(&log), Err(AbiError::InvalidSelector));
+		log.topics = vec![transfer.topic(), from.into()];
+		assert_eq!(transfer.decode_log(&log), Err(AbiError::InvalidData));
+	}
+
+	#[test]
+	fn malformed_data() {
+		let types = [AbiType::Bytes];
+		let valid = encode(&[AbiValue::Bytes(vec![1, 2, 3])]);
+		assert_eq!(
+			decode(&types, &valid).unwrap(),
+			[AbiValue::Bytes(vec![1, 2, 3])]
+		);
+
+		let with_word = |index: usize, value: U256| {
+			let mut data = valid.clone();
+			value.to_big_endian(&mut data[index * 32..index * 32 + 32]);
+			data
+		};
+		for data in [
+			// Truncated.
+			Vec::new(),
+			valid[..63].to_vec(),
+			// Offset past the end, or overflowing.
+			with_word(0, U256::from(0x60)),
+			with_word(0, U256::from(usize::MAX)),
+			with_word(0, U256::MAX),
+			// Length past the end, or overflowing.
+			with_word(1, U256::from(33)),
+			with_word(1, U256::from(usize::MAX)),
+			with_word(1, U256::MAX),
+		] {
+			assert_eq!(decode(&types, &data), Err(AbiError::InvalidData));
+		}
+
+		// Array lengths are checked against the data before allocating.
+		let types = [AbiType::parse("uint256[]").unwrap()];
+		let mut data = encode(&[AbiValue::Array(vec![uint(1)])]);
+		assert_eq!(decode(&types, &data).unwrap().len(), 1);
+		for len in [U256::from(2), U256::from(u64::MAX), U256::MAX] {
+			len.to_big_endian(&mut data[32..64]);
+			assert_eq!(decode(&types, &data), Err(AbiError::InvalidData));
+		}
+
+		// Values outside of their type.
+		let dirty_address = U256::one() << 160;
+		for (ty, word) in [
+			(AbiType::Bool, U256::from(2)),
+			(AbiType::Address, dirty_address),
+			(AbiType::Uint(8), U256::from(0x100)),
+		] {
+			let mut data = Vec::new();
+			push_word(&mut data, word);
+			assert_eq!(decode(&[ty], &data), Err(AbiError::InvalidData));
+		}
+
+		// Invalid UTF-8.
+		let mut data = encode(&[string("a")]);
+		data[64] = 0xff;
+		assert_eq!(
+			decode(&[AbiType::String], &data),
+			Err(AbiError::InvalidData)
+		);
+
+		// Wrong selector.
+		let f = Function::parse("f(uint256)").unwrap();
+		assert_eq!(f.decode_input(&[0; 3]), Err(AbiError::InvalidSelector));
+		assert_eq!(f.decode_input(&[0; 36]), Err(AbiError::InvalidSelector));
+	}
+}
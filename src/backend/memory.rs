@@ -1,5 +1,6 @@
 use super::{Apply, ApplyBackend, Backend, Basic, Log};
 use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use primitive_types::{H160, H256, U256};
 
@@ -47,8 +48,27 @@ pub struct MemoryAccount {
 	pub balance: U256,
 	/// Full account storage.
 	pub storage: BTreeMap<H256, H256>,
-	/// Account code.
-	pub code: Vec<u8>,
+	/// Account code, shared with the executors reading it.
+	#[cfg_attr(feature = "with-serde", serde(with = "shared_code"))]
+	pub code: Arc<Vec<u8>>,
+}
+
+/// Serialization of shared code as plain bytes, so that serde does not need
+/// its `rc` feature.
+#[cfg(feature = "with-serde")]
+mod shared_code {
+	use alloc::{sync::Arc, vec::Vec};
+	use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+	pub fn serialize<S: Serializer>(code: &Arc<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+		code.as_ref().serialize(serializer)
+	}
+
+	pub fn deserialize<'de, D: Deserializer<'de>>(
+		deserializer: D,
+	) -> Result<Arc<Vec<u8>>, D::Error> {
+		Vec::deserialize(deserializer).map(Arc::new)
+	}
 }
 
 /// Memory backend, storing all state values in a `BTreeMap` in memory.
@@ -135,7 +155,7 @@ impl<'vicinity> Backend for MemoryBackend<'vicinity> {
 			.unwrap_or_default()
 	}
 
-	fn code(&self, address: H160) -> Vec<u8> {
+	fn code(&self, address: H160) -> Arc<Vec<u8>> {
 		self.state
 			.get(&address)
 			.map(|v| v.code.clone())
@@ -149,6 +169,20 @@ impl<'vicinity> Backend for MemoryBackend<'vicinity> {
 			.unwrap_or_default()
 	}
 
+	fn storage_count(&self, address: H160) -> Option<usize> {
+		Some(
+			self.state
+				.get(&address)
+				.map(|v| {
+					v.storage
+						.values()
+						.filter(|value| **value != H256::default())
+						.count()
+				})
+				.unwrap_or_default(),
+		)
+	}
+
 	fn original_storage(&self, address: H160, index: H256) -> Option<H256> {
 		Some(self.storage(address, index))
 	}
@@ -175,7 +209,7 @@ impl<'vicinity> ApplyBackend for MemoryBackend<'vicinity> {
 						account.balance = basic.balance;
 						account.nonce = basic.nonce;
 						if let Some(code) = code {
-							account.code = code;
+							account.code = Arc::new(code);
 						}
 
 						if reset_storage {
@@ -6,8 +6,9 @@ mod memory;
 
 pub use self::memory::{MemoryAccount, MemoryBackend, MemoryVicinity};
 
-use alloc::vec::Vec;
+use alloc::{sync::Arc, vec::Vec};
 use primitive_types::{H160, H256, U256};
+use sha3::{Digest, Keccak256};
 
 /// Basic account information.
 #[derive(Clone, Eq, PartialEq, Debug, Default)]
@@ -77,10 +78,26 @@ pub trait Backend {
 	fn exists(&self, address: H160) -> bool;
 	/// Get basic account information.
 	fn basic(&self, address: H160) -> Basic;
-	/// Get account code.
-	fn code(&self, address: H160) -> Vec<u8>;
+	/// Get account code. The code is shared, so that it is not copied for
+	/// each call.
+	fn code(&self, address: H160) -> Arc<Vec<u8>>;
+	/// Get account code size. Backends that store code sizes should
+	/// override this to avoid loading the code.
+	fn code_size(&self, address: H160) -> usize {
+		self.code(address).len()
+	}
+	/// Get account code hash. Backends that store code hashes should
+	/// override this to avoid hashing the code on each call.
+	fn code_hash(&self, address: H160) -> H256 {
+		H256::from_slice(Keccak256::digest(&self.code(address)[..]).as_slice())
+	}
 	/// Get storage value of address at index.
 	fn storage(&self, address: H160, index: H256) -> H256;
+	/// Get the number of non-zero storage slots of an account, if known. It
+	/// is only used to count the slots wiped when storage is reset.
+	fn storage_count(&self, _address: H160) -> Option<usize> {
+		None
+	}
 	/// Get original storage value of address at index, if available.
 	fn original_storage(&self, address: H160, index: H256) -> Option<H256>;
 }
@@ -0,0 +1,434 @@
+//! Fork schedule of a chain, mapping block numbers and timestamps to
+//! configurations.
+
+#[cfg(feature = "with-serde")]
+use crate::backend::MemoryAccount;
+use crate::Config;
+#[cfg(feature = "with-serde")]
+use crate::{ConfigBuilder, Fork};
+#[cfg(feature = "with-serde")]
+use alloc::collections::BTreeMap;
+use alloc::{string::String, vec::Vec};
+use primitive_types::U256;
+#[cfg(feature = "with-serde")]
+use primitive_types::{H160, H256};
+
+/// Constructor of the configuration of a fork.
+#[cfg(feature = "with-serde")]
+type ForkConstructor = fn() -> Config;
+
+/// Errors when loading a chain specification.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ChainSpecError {
+	/// The genesis is not valid JSON, or has a malformed field.
+	InvalidGenesis,
+	/// The genesis activates a fork without a configuration.
+	UnsupportedFork(String),
+	/// The fork activates before the last scheduled fork.
+	ForkOutOfOrder(ForkCondition),
+}
+
+/// Activation condition of a fork. Block conditions are ordered before
+/// timestamp conditions, as forks activated by timestamp follow the merge.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum ForkCondition {
+	/// Active from the given block number.
+	Block(u64),
+	/// Active from the given block timestamp.
+	Timestamp(u64),
+}
+
+impl ForkCondition {
+	/// Whether the fork is active at the given block.
+	pub fn is_active(&self, number: U256, timestamp: U256) -> bool {
+		match self {
+			Self::Block(block) => number >= U256::from(*block),
+			Self::Timestamp(time) => timestamp >= U256::from(*time),
+		}
+	}
+}
+
+/// Fork schedule of a chain.
+#[derive(Clone, Debug)]
+pub struct ChainSpec {
+	chain_id: U256,
+	forks: Vec<(ForkCondition, Config)>,
+}
+
+impl ChainSpec {
+	/// Create a chain with the given configuration from genesis.
+	pub fn new(chain_id: U256, genesis: Config) -> Self {
+		Self {
+			chain_id,
+			forks: alloc::vec![(ForkCondition::Block(0), genesis)],
+		}
+	}
+
+	/// Schedule a fork. Forks must be added in activation order. A fork
+	/// activating with the last scheduled one takes precedence over it.
+	pub fn add_fork(
+		&mut self,
+		condition: ForkCondition,
+		config: Config,
+	) -> Result<(), ChainSpecError> {
+		match self.forks.last() {
+			Some((last, _)) if condition < *last => Err(ChainSpecError::ForkOutOfOrder(condition)),
+			_ => {
+				self.forks.push((condition, config));
+				Ok(())
+			}
+		}
+	}
+
+	/// Chain id.
+	pub fn chain_id(&self) -> U256 {
+		self.chain_id
+	}
+
+	/// Scheduled forks, in activation order.
+	pub fn forks(&self) -> &[(ForkCondition, Config)] {
+		&self.forks
+	}
+
+	/// Configuration of the block with the given number and timestamp.
+	pub fn config_at(&self, number: U256, timestamp: U256) -> &Config {
+		self.forks
+			.iter()
+			.rev()
+			.find(|(condition, _)| condition.is_active(number, timestamp))
+			.map(|(_, config)| config)
+			.unwrap_or(&self.forks[0].1)
+	}
+
+	/// Load the fork schedule and the initial accounts from a geth
+	/// `genesis.json`. Forks not changing the EVM, such as difficulty bomb
+	/// delays, keep the configuration of the previous fork.
+	#[cfg(feature = "with-serde")]
+	pub fn from_genesis_json(
+		genesis: &str,
+	) -> Result<(Self, BTreeMap<H160, MemoryAccount>), ChainSpecError> {
+		let genesis: serde_json::Value =
+			serde_json::from_str(genesis).map_err(|_| ChainSpecError::InvalidGenesis)?;
+		let config = genesis
+			.get("config")
+			.ok_or(ChainSpecError::InvalidGenesis)?;
+
+		let chain_id = config
+			.get("chainId")
+			.map(parse_u256)
+			.transpose()?
+			.unwrap_or_else(U256::one);
+		let mut spec = Self::new(chain_id, Config::frontier());
+
+		let block_forks: [(&str, ForkConstructor); 13] = [
+			("homesteadBlock", Config::homestead),
+			("eip150Block", Config::tangerine_whistle),
+			("eip158Block", Config::spurious_dragon),
+			("byzantiumBlock", Config::byzantium),
+			("constantinopleBlock", Config::constantinople),
+			("petersburgBlock", Config::petersburg),
+			("istanbulBlock", Config::istanbul),
+			("muirGlacierBlock", Config::muir_glacier),
+			("berlinBlock", Config::berlin),
+			("londonBlock", Config::london),
+			("arrowGlacierBlock", Config::london),
+			("grayGlacierBlock", Config::london),
+			("mergeNetsplitBlock", Config::london),
+		];
+		for (field, fork) in block_forks {
+			if let Some(block) = config.get(field) {
+				let block = parse_u256(block)?;
+				if block > U256::from(u64::MAX) {
+					return Err(ChainSpecError::InvalidGenesis);
+				}
+				spec.add_fork(ForkCondition::Block(block.as_u64()), fork())?;
+			}
+		}
+
+		if let Some(time) = config.get("shanghaiTime") {
+			let time = parse_u256(time)?;
+			if time > U256::from(u64::MAX) {
+				return Err(ChainSpecError::InvalidGenesis);
+			}
+			spec.add_fork(ForkCondition::Timestamp(time.as_u64()), shanghai())?;
+		}
+		for field in ["cancunTime", "pragueTime"] {
+			if config.get(field).is_some() {
+				return Err(ChainSpecError::UnsupportedFork(String::from(field)));
+			}
+		}
+
+		let mut state = BTreeMap::new();
+		if let Some(alloc) = genesis.get("alloc") {
+			let alloc = alloc.as_object().ok_or(ChainSpecError::InvalidGenesis)?;
+			for (address, account) in alloc {
+				let address = H160::from_slice(&parse_bytes(address, 20)?);
+				state.insert(address, parse_account(account)?);
+			}
+		}
+
+		Ok((spec, state))
+	}
+}
+
+/// Shanghai configuration: London with the `PUSH0` of EIP-3855. The warm
+/// coinbase of EIP-3651 and the init code limit of EIP-3860 are not part of
+/// `Config`.
+#[cfg(feature = "with-serde")]
+fn shanghai() -> Config {
+	ConfigBuilder::new(Fork::London)
+		.enable(3855)
+		.build()
+		.expect("EIP-3855 has no dependencies")
+}
+
+#[cfg(feature = "with-serde")]
+fn parse_account(account: &serde_json::Value) -> Result<MemoryAccount, ChainSpecError> {
+	let field = |name: &str| account.get(name).filter(|value| !value.is_null());
+
+	let mut storage = BTreeMap::new();
+	if let Some(entries) = field("storage") {
+		let entries = entries.as_object().ok_or(ChainSpecError::InvalidGenesis)?;
+		for (index, value) in entries {
+			let value = value.as_str().ok_or(ChainSpecError::InvalidGenesis)?;
+			storage.insert(
+				H256::from_slice(&parse_bytes(index, 32)?),
+				H256::from_slice(&parse_bytes(value, 32)?),
+			);
+		}
+	}
+
+	Ok(MemoryAccount {
+		nonce: field("nonce")
+			.map(parse_u256)
+			.transpose()?
+			.unwrap_or_default(),
+		balance: field("balance")
+			.map(parse_u256)
+			.transpose()?
+			.unwrap_or_default(),
+		storage,
+		code: match field("code") {
+			Some(code) => {
+				let code = code.as_str().ok_or(ChainSpecError::InvalidGenesis)?;
+				decode_hex(code)?.into()
+			}
+			None => Default::default(),
+		},
+	})
+}
+
+/// Parse a number given as a JSON number, or a hex or decimal string.
+#[cfg(feature = "with-serde")]
+fn parse_u256(value: &serde_json::Value) -> Result<U256, ChainSpecError> {
+	match value {
+		serde_json::Value::Number(number) => number
+			.as_u64()
+			.map(U256::from)
+			.ok_or(ChainSpecError::InvalidGenesis),
+		serde_json::Value::String(string) => match string.strip_prefix("0x") {
+			Some(hex) => U256::from_str_radix(hex, 16).ok(),
+			None => U256::from_dec_str(string).ok(),
+		}
+		.ok_or(ChainSpecError::InvalidGenesis),
+		_ => Err(ChainSpecError::InvalidGenesis),
+	}
+}
+
+/// Parse hex data of at most `len` bytes, left padded with zeros.
+#[cfg(feature = "with-serde")]
+fn parse_bytes(value: &str, len: usize) -> Result<Vec<u8>, ChainSpecError> {
+	let bytes = decode_hex(value)?;
+	if bytes.len() > len {
+		return Err(ChainSpecError::InvalidGenesis);
+	}
+
+	let mut padded = alloc::vec![0; len - bytes.len()];
+	padded.extend_from_slice(&bytes);
+	Ok(padded)
+}
+
+#[cfg(feature = "with-serde")]
+fn decode_hex(value: &str) -> Result<Vec<u8>, ChainSpecError> {
+	let value = value.strip_prefix("0x").unwrap_or(value).as_bytes();
+	let nibble = |c: u8| match c {
+		b'0'..=b'9' => Ok(c - b'0'),
+		b'a'..=b'f' => Ok(c - b'a' + 10),
+		b'A'..=b'F' => Ok(c - b'A' + 10),
+		_ => Err(ChainSpecError::InvalidGenesis),
+	};
+
+	// Odd length values, such as `0x0`, are left padded.
+	let (first, rest) = value.split_at(value.len() % 2);
+	let mut bytes = Vec::with_capacity(value.len() / 2 + 1);
+	for c in first {
+		bytes.push(nibble(*c)?);
+	}
+	for pair in rest.chunks(2) {
+		bytes.push(nibble(pair[0])? << 4 | nibble(pair[1])?);
+	}
+	Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn at(spec: &ChainSpec, number: u64, timestamp: u64) -> &Config {
+		spec.config_at(U256::from(number), U256::from(timestamp))
+	}
+
+	#[test]
+	fn config_at_fork_boundaries() {
+		let mut spec = ChainSpec::new(U256::one(), Config::istanbul());
+		spec.add_fork(ForkCondition::Block(10), Config::berlin())
+			.unwrap();
+		spec.add_fork(ForkCondition::Block(20), Config::london())
+			.unwrap();
+		spec.add_fork(ForkCondition::Timestamp(1_000), Config::mystique())
+			.unwrap();
+
+		assert_eq!(at(&spec, 9, 0), &Config::istanbul());
+		assert_eq!(at(&spec, 10, 0), &Config::berlin());
+		assert_eq!(at(&spec, 19, 999), &Config::berlin());
+		assert_eq!(at(&spec, 20, 999), &Config::london());
+		assert_eq!(at(&spec, 20, 1_000), &Config::mystique());
+	}
+
+	#[test]
+	fn forks_out_of_order() {
+		let mut spec = ChainSpec::new(U256::one(), Config::istanbul());
+		spec.add_fork(ForkCondition::Block(10), Config::berlin())
+			.unwrap();
+		assert_eq!(
+			spec.add_fork(ForkCondition::Block(5), Config::london()),
+			Err(ChainSpecError::ForkOutOfOrder(ForkCondition::Block(5)))
+		);
+
+		// Forks at the same block are applied in order.
+		spec.add_fork(ForkCondition::Block(10), Config::london())
+			.unwrap();
+		assert_eq!(at(&spec, 10, 0), &Config::london());
+
+		spec.add_fork(ForkCondition::Timestamp(1_000), Config::mystique())
+			.unwrap();
+		assert_eq!(
+			spec.add_fork(ForkCondition::Block(20), Config::london()),
+			Err(ChainSpecError::ForkOutOfOrder(ForkCondition::Block(20)))
+		);
+		assert_eq!(spec.forks().len(), 4);
+	}
+
+	/// Configuration of the Sepolia genesis of geth, with part of its alloc.
+	#[cfg(feature = "with-serde")]
+	const SEPOLIA: &str = r#"{
+		"config": {
+			"chainId": 11155111,
+			"homesteadBlock": 0,
+			"daoForkSupport": true,
+			"eip150Block": 0,
+			"eip155Block": 0,
+			"eip158Block": 0,
+			"byzantiumBlock": 0,
+			"constantinopleBlock": 0,
+			"petersburgBlock": 0,
+			"istanbulBlock": 0,
+			"muirGlacierBlock": 0,
+			"berlinBlock": 0,
+			"londonBlock": 1735371,
+			"mergeNetsplitBlock": 1735371,
+			"shanghaiTime": 1677557088,
+			"terminalTotalDifficulty": 17000000000000000,
+			"terminalTotalDifficultyPassed": true,
+			"ethash": {}
+		},
+		"nonce": "0x0",
+		"timestamp": "0x6159af19",
+		"gasLimit": "0x1c9c380",
+		"difficulty": "0x20000",
+		"alloc": {
+			"a2A6d93439144FFE4D27c9E088dCD8b783946263": {
+				"balance": "0xD3C21BCECCEDA1000000"
+			},
+			"0x0000000000000000000000000000000000000001": {
+				"balance": "0x1",
+				"nonce": "0x0",
+				"code": "0x600",
+				"storage": {
+					"0x0": "0x1",
+					"0x0000000000000000000000000000000000000000000000000000000000000002": "0xabc"
+				}
+			}
+		}
+	}"#;
+
+	#[cfg(feature = "with-serde")]
+	#[test]
+	fn sepolia_genesis() {
+		let (spec, state) = ChainSpec::from_genesis_json(SEPOLIA).unwrap();
+		assert_eq!(spec.chain_id(), U256::from(11155111));
+
+		let london = 1735371;
+		let shanghai = 1677557088;
+		assert_eq!(at(&spec, 0, 0), &Config::berlin());
+		assert_eq!(at(&spec, london - 1, 0), &Config::berlin());
+		assert_eq!(at(&spec, london, shanghai - 1), &Config::london());
+		let config = at(&spec, london, shanghai);
+		assert!(config.has_push0);
+		assert_eq!(
+			config,
+			&Config {
+				has_push0: true,
+				..Config::london()
+			}
+		);
+
+		assert_eq!(state.len(), 2);
+		let funded =
+			H160::from_slice(&decode_hex("a2a6d93439144ffe4d27c9e088dcd8b783946263").unwrap());
+		assert_eq!(
+			state[&funded].balance,
+			U256::from_dec_str("1000000000000000000000000").unwrap()
+		);
+		assert!(state[&funded].code.is_empty());
+
+		let account = &state[&H160::from_low_u64_be(1)];
+		assert_eq!(account.balance, U256::one());
+		assert_eq!(account.nonce, U256::zero());
+		assert_eq!(&account.code[..], &[0x06, 0x00]);
+		assert_eq!(account.storage[&H256::zero()], H256::from_low_u64_be(1));
+		assert_eq!(
+			account.storage[&H256::from_low_u64_be(2)],
+			H256::from_low_u64_be(0xabc)
+		);
+	}
+
+	#[cfg(feature = "with-serde")]
+	#[test]
+	fn unsupported_and_invalid_genesis() {
+		let genesis = |config: &str| alloc::format!(r#"{{"config": {{{}}}}}"#, config);
+		let parse = |config: &str| ChainSpec::from_genesis_json(&genesis(config)).map(|_| ());
+
+		assert_eq!(
+			parse(r#""shanghaiTime": 10, "cancunTime": 20"#),
+			Err(ChainSpecError::UnsupportedFork(String::from("cancunTime")))
+		);
+		assert_eq!(
+			parse(r#""berlinBlock": 10, "londonBlock": 5"#),
+			Err(ChainSpecError::ForkOutOfOrder(ForkCondition::Block(5)))
+		);
+		assert_eq!(
+			parse(r#""londonBlock": "0x1g""#),
+			Err(ChainSpecError::InvalidGenesis)
+		);
+		assert_eq!(
+			ChainSpec::from_genesis_json("{}").map(|_| ()),
+			Err(ChainSpecError::InvalidGenesis)
+		);
+
+		let (spec, state) = ChainSpec::from_genesis_json(&genesis(r#""chainId": "0x3d""#)).unwrap();
+		assert_eq!(spec.chain_id(), U256::from(61));
+		assert_eq!(spec.forks().len(), 1);
+		assert!(state.is_empty());
+	}
+}
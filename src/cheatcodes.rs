@@ -0,0 +1,272 @@
+//! Cheatcode precompile letting test contracts manipulate their environment,
+//! with the interface of Foundry's `Vm` contract.
+
+use crate::abi::{AbiValue, Function};
+use crate::executor::stack::{
+	PrecompileFailure, PrecompileHandle, PrecompileOutput, PrecompileResult, PrecompileSet,
+};
+use crate::revert::ERROR_SELECTOR;
+use crate::{ExitReason, ExitRevert, ExitSucceed};
+use alloc::{string::String, vec::Vec};
+use core::cell::RefCell;
+use primitive_types::{H160, H256};
+
+/// Address of the cheatcode precompile, `address(bytes20(uint160(uint256(keccak256("hevm cheat code")))))`.
+pub const CHEATCODE_ADDRESS: H160 = H160([
+	0x71, 0x09, 0x70, 0x9e, 0xcf, 0xa9, 0x1a, 0x80, 0x62, 0x6f, 0xf3, 0x98, 0x9d, 0x68, 0xf6, 0x7f,
+	0x5b, 0x1d, 0xd1, 0x2d,
+]);
+
+const WARP: &str = "warp(uint256)";
+const ROLL: &str = "roll(uint256)";
+const DEAL: &str = "deal(address,uint256)";
+const STORE: &str = "store(address,bytes32,bytes32)";
+const LOAD: &str = "load(address,bytes32)(bytes32)";
+const ETCH: &str = "etch(address,bytes)";
+const PRANK: &str = "prank(address)";
+const START_PRANK: &str = "startPrank(address)";
+const STOP_PRANK: &str = "stopPrank()";
+const EXPECT_REVERT: &str = "expectRevert()";
+const EXPECT_REVERT_DATA: &str = "expectRevert(bytes)";
+
+const CHEATCODES: [&str; 11] = [
+	WARP,
+	ROLL,
+	DEAL,
+	STORE,
+	LOAD,
+	ETCH,
+	PRANK,
+	START_PRANK,
+	STOP_PRANK,
+	EXPECT_REVERT,
+	EXPECT_REVERT_DATA,
+];
+
+struct Prank {
+	caller: H160,
+	sender: H160,
+	persistent: bool,
+}
+
+struct ExpectedRevert {
+	caller: H160,
+	data: Option<Vec<u8>>,
+}
+
+#[derive(Default)]
+struct State {
+	prank: Option<Prank>,
+	expected_revert: Option<ExpectedRevert>,
+}
+
+/// Precompile set adding cheatcodes at `CHEATCODE_ADDRESS` to an inner
+/// precompile set.
+///
+/// Supported cheatcodes are `warp`, `roll`, `deal`, `store`, `load`, `etch`,
+/// `prank`, `startPrank`, `stopPrank` and `expectRevert`. Block overrides
+/// last until the end of the transaction. Pranks and expected reverts apply
+/// to the following calls made by the contract that set them, which are
+/// executed as a sub-call of the precompile set, and are charged the gas of
+/// that extra call. Value sent with a pranked call is paid by the original
+/// caller.
+///
+/// Cheatcodes other than pranks and expected reverts need unmetered state
+/// access, through `PrecompileHandle::state_access`, which `StackExecutor`
+/// only gives once enabled with `StackExecutor::set_state_access`.
+/// `TestChain` enables it.
+pub struct Cheatcodes<P = ()> {
+	inner: P,
+	state: RefCell<State>,
+}
+
+impl<P: PrecompileSet> Cheatcodes<P> {
+	/// Add cheatcodes to the inner precompile set.
+	pub fn new(inner: P) -> Self {
+		Self {
+			inner,
+			state: Default::default(),
+		}
+	}
+
+	/// Inner precompile set.
+	pub fn inner(&self) -> &P {
+		&self.inner
+	}
+
+	fn cheatcode(&self, handle: &mut impl PrecompileHandle) -> PrecompileResult {
+		let input = handle.input().to_vec();
+		let caller = handle.context().caller;
+
+		let (signature, args) = CHEATCODES
+			.iter()
+			.filter_map(|signature| Function::parse(signature).ok().map(|f| (*signature, f)))
+			.find(|(_, function)| input.get(..4) == Some(&function.selector()[..]))
+			.ok_or_else(|| revert("unknown cheatcode"))
+			.and_then(|(signature, function)| {
+				function
+					.decode_input(&input)
+					.map(|args| (signature, args))
+					.map_err(|_| revert("invalid cheatcode arguments"))
+			})?;
+
+		let mut output = Vec::new();
+		match (signature, &args[..]) {
+			(PRANK, [AbiValue::Address(sender)]) | (START_PRANK, [AbiValue::Address(sender)]) => {
+				self.state.borrow_mut().prank = Some(Prank {
+					caller,
+					sender: *sender,
+					persistent: signature == START_PRANK,
+				});
+			}
+			(STOP_PRANK, []) => self.state.borrow_mut().prank = None,
+			(EXPECT_REVERT, []) => {
+				self.state.borrow_mut().expected_revert =
+					Some(ExpectedRevert { caller, data: None });
+			}
+			(EXPECT_REVERT_DATA, [AbiValue::Bytes(data)]) => {
+				self.state.borrow_mut().expected_revert = Some(ExpectedRevert {
+					caller,
+					data: Some(data.clone()),
+				});
+			}
+			_ => {
+				let state = handle
+					.state_access()
+					.ok_or_else(|| revert("cheatcodes need state access"))?;
+
+				match (signature, &args[..]) {
+					(WARP, [AbiValue::Uint(timestamp, _)]) => state.set_block_timestamp(*timestamp),
+					(ROLL, [AbiValue::Uint(number, _)]) => state.set_block_number(*number),
+					(DEAL, [AbiValue::Address(address), AbiValue::Uint(balance, _)]) => {
+						state.set_balance(*address, *balance)
+					}
+					(
+						STORE,
+						[AbiValue::Address(address), AbiValue::FixedBytes(index), AbiValue::FixedBytes(value)],
+					) => state.set_storage(
+						*address,
+						H256::from_slice(index),
+						H256::from_slice(value),
+					),
+					(LOAD, [AbiValue::Address(address), AbiValue::FixedBytes(index)]) => {
+						output = state
+							.storage(*address, H256::from_slice(index))
+							.as_bytes()
+							.to_vec();
+					}
+					(ETCH, [AbiValue::Address(address), AbiValue::Bytes(code)]) => {
+						state.set_code(*address, code.clone())
+					}
+					_ => return Err(revert("invalid cheatcode arguments")),
+				}
+			}
+		}
+
+		Ok(PrecompileOutput {
+			exit_status: ExitSucceed::Returned,
+			output,
+		})
+	}
+
+	/// Take the prank and expected revert applying to a call from `caller`.
+	fn take_overrides(&self, caller: H160) -> (Option<H160>, Option<ExpectedRevert>) {
+		let mut state = self.state.borrow_mut();
+
+		let sender = match &state.prank {
+			Some(prank) if prank.caller == caller && prank.sender != caller => {
+				let sender = prank.sender;
+				if !prank.persistent {
+					state.prank = None;
+				}
+				Some(sender)
+			}
+			_ => None,
+		};
+
+		let expected_revert = match &state.expected_revert {
+			Some(expected) if expected.caller == caller => state.expected_revert.take(),
+			_ => None,
+		};
+
+		(sender, expected_revert)
+	}
+
+	/// Execute the call of `handle` again as a sub-call, with the overrides.
+	fn intercept(
+		&self,
+		handle: &mut impl PrecompileHandle,
+		sender: Option<H160>,
+		expected_revert: Option<ExpectedRevert>,
+	) -> PrecompileResult {
+		let mut context = handle.context().clone();
+		if let Some(sender) = sender {
+			context.caller = sender;
+		}
+
+		let (reason, output) = handle.call(
+			handle.code_address(),
+			None,
+			handle.input().to_vec(),
+			None,
+			handle.is_static(),
+			&context,
+		);
+
+		match expected_revert {
+			Some(expected) => match reason {
+				ExitReason::Revert(_)
+					if expected.data.is_none() || expected.data.as_ref() == Some(&output) =>
+				{
+					Ok(PrecompileOutput {
+						exit_status: ExitSucceed::Returned,
+						output,
+					})
+				}
+				ExitReason::Revert(_) => Err(revert("call reverted with unexpected data")),
+				_ => Err(revert("call did not revert as expected")),
+			},
+			None => match reason {
+				ExitReason::Succeed(exit_status) => Ok(PrecompileOutput {
+					exit_status,
+					output,
+				}),
+				ExitReason::Revert(exit_status) => Err(PrecompileFailure::Revert {
+					exit_status,
+					output,
+				}),
+				ExitReason::Error(exit_status) => Err(PrecompileFailure::Error { exit_status }),
+				ExitReason::Fatal(exit_status) => Err(PrecompileFailure::Fatal { exit_status }),
+			},
+		}
+	}
+}
+
+impl<P: PrecompileSet> PrecompileSet for Cheatcodes<P> {
+	fn execute(&self, handle: &mut impl PrecompileHandle) -> Option<PrecompileResult> {
+		if handle.code_address() == CHEATCODE_ADDRESS {
+			return Some(self.cheatcode(handle));
+		}
+
+		match self.take_overrides(handle.context().caller) {
+			(None, None) => self.inner.execute(handle),
+			(sender, expected_revert) => Some(self.intercept(handle, sender, expected_revert)),
+		}
+	}
+
+	fn is_precompile(&self, address: H160) -> bool {
+		address == CHEATCODE_ADDRESS || self.inner.is_precompile(address)
+	}
+}
+
+fn revert(message: &str) -> PrecompileFailure {
+	let mut output = ERROR_SELECTOR.to_vec();
+	output.extend_from_slice(&crate::abi::encode(&[AbiValue::String(String::from(
+		message,
+	))]));
+
+	PrecompileFailure::Revert {
+		exit_status: ExitRevert::Reverted,
+		output,
+	}
+}
@@ -0,0 +1,221 @@
+use crate::{GasBlocks, Opcode, Valids};
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use core::ops::DerefMut;
+use primitive_types::H256;
+
+/// Lock of the cache entries. Without `std` there are no threads to share
+/// the cache with, and a `RefCell` is enough.
+#[cfg(feature = "std")]
+type Lock<T> = std::sync::Mutex<T>;
+#[cfg(not(feature = "std"))]
+type Lock<T> = core::cell::RefCell<T>;
+
+/// Contract code together with the analysis needed to execute it.
+#[derive(Clone, Debug)]
+pub struct AnalysedCode {
+	/// Code bytes.
+	pub code: Arc<Vec<u8>>,
+	/// Valid jump destinations of the code.
+	pub valids: Arc<Valids>,
+	/// Static gas analysis of the code, only computed when block gas
+	/// precharging is enabled.
+	pub gas_blocks: Option<Arc<GasBlocks>>,
+}
+
+impl AnalysedCode {
+	/// Analyse given code bytes.
+	pub fn new(code: Arc<Vec<u8>>) -> Self {
+		Self::new_with_immediates(code, |_| 0)
+	}
+
+	/// Analyse given code bytes, where `immediates` gives the length of the
+	/// immediate data following opcodes other than `PUSH`.
+	pub fn new_with_immediates<F: Fn(Opcode) -> usize>(code: Arc<Vec<u8>>, immediates: F) -> Self {
+		let valids = Arc::new(Valids::new_with_immediates(&code, immediates));
+
+		Self {
+			code,
+			valids,
+			gas_blocks: None,
+		}
+	}
+}
+
+#[derive(Default)]
+struct CodeCacheInner {
+	tick: u64,
+	entries: BTreeMap<H256, (u64, AnalysedCode)>,
+	recency: BTreeMap<u64, H256>,
+}
+
+/// Bounded cache of analysed code keyed by code hash, evicting the least
+/// recently used entry when full.
+///
+/// The cache can be shared by several executors, including executors on
+/// other threads, so that hot contracts are only analysed once across calls
+/// and transactions. Executors sharing it must have the same custom opcodes,
+/// which the analysis depends on.
+pub struct CodeCache {
+	capacity: usize,
+	inner: Lock<CodeCacheInner>,
+}
+
+impl CodeCache {
+	/// Create a new cache holding at most `capacity` entries.
+	pub fn new(capacity: usize) -> Self {
+		Self {
+			capacity,
+			inner: Lock::new(CodeCacheInner::default()),
+		}
+	}
+
+	fn inner(&self) -> impl DerefMut<Target = CodeCacheInner> + '_ {
+		// The entries are always consistent between operations, so a cache
+		// poisoned by a panicking thread can still be used.
+		#[cfg(feature = "std")]
+		{
+			self.inner
+				.lock()
+				.unwrap_or_else(|poisoned| poisoned.into_inner())
+		}
+		#[cfg(not(feature = "std"))]
+		{
+			self.inner.borrow_mut()
+		}
+	}
+
+	/// Maximum number of entries in the cache.
+	pub fn capacity(&self) -> usize {
+		self.capacity
+	}
+
+	/// Number of entries in the cache.
+	pub fn len(&self) -> usize {
+		self.inner().entries.len()
+	}
+
+	/// Returns true if the cache is empty.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Get the analysed code for given code hash, marking it as recently
+	/// used.
+	pub fn get(&self, code_hash: H256) -> Option<AnalysedCode> {
+		let mut inner = self.inner();
+		inner.tick += 1;
+		let tick = inner.tick;
+
+		let (used, analysed) = inner.entries.get_mut(&code_hash)?;
+		let last_used = core::mem::replace(used, tick);
+		let analysed = analysed.clone();
+
+		inner.recency.remove(&last_used);
+		inner.recency.insert(tick, code_hash);
+
+		Some(analysed)
+	}
+
+	/// Insert analysed code for given code hash, evicting the least
+	/// recently used entry if the cache is full.
+	pub fn insert(&self, code_hash: H256, analysed: AnalysedCode) {
+		if self.capacity == 0 {
+			return;
+		}
+
+		let mut inner = self.inner();
+		inner.tick += 1;
+		let tick = inner.tick;
+
+		if let Some((last_used, _)) = inner.entries.insert(code_hash, (tick, analysed)) {
+			inner.recency.remove(&last_used);
+		}
+		inner.recency.insert(tick, code_hash);
+
+		while inner.entries.len() > self.capacity {
+			let (&oldest, &evicted) = match inner.recency.iter().next() {
+				Some(entry) => entry,
+				None => break,
+			};
+			inner.recency.remove(&oldest);
+			inner.entries.remove(&evicted);
+		}
+	}
+
+	/// Remove all entries.
+	pub fn clear(&self) {
+		let mut inner = self.inner();
+		inner.entries.clear();
+		inner.recency.clear();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn analysed(code: u8) -> AnalysedCode {
+		AnalysedCode::new(Arc::new(alloc::vec![code]))
+	}
+
+	fn hash(n: u64) -> H256 {
+		H256::from_low_u64_be(n)
+	}
+
+	fn cached(cache: &CodeCache) -> Vec<H256> {
+		cache.inner().entries.keys().copied().collect()
+	}
+
+	#[test]
+	fn capacity_is_bounded() {
+		let cache = CodeCache::new(2);
+		for n in 0..5 {
+			cache.insert(hash(n), analysed(n as u8));
+			assert!(cache.len() <= 2);
+		}
+		assert_eq!(cached(&cache), [hash(3), hash(4)]);
+
+		let cache = CodeCache::new(0);
+		cache.insert(hash(0), analysed(0));
+		assert!(cache.is_empty());
+		assert!(cache.get(hash(0)).is_none());
+	}
+
+	#[test]
+	fn evicts_least_recently_used() {
+		let cache = CodeCache::new(3);
+		cache.insert(hash(1), analysed(1));
+		cache.insert(hash(2), analysed(2));
+		cache.insert(hash(3), analysed(3));
+
+		// 1 is used again, and 2 is replaced, leaving 3 as the oldest.
+		assert!(cache.get(hash(1)).is_some());
+		cache.insert(hash(2), analysed(2));
+		cache.insert(hash(4), analysed(4));
+		assert_eq!(cached(&cache), [hash(1), hash(2), hash(4)]);
+
+		cache.insert(hash(5), analysed(5));
+		assert_eq!(cached(&cache), [hash(2), hash(4), hash(5)]);
+
+		// Misses do not change the order.
+		assert!(cache.get(hash(1)).is_none());
+		cache.insert(hash(6), analysed(6));
+		assert_eq!(cached(&cache), [hash(4), hash(5), hash(6)]);
+		assert_eq!(cache.inner().recency.len(), 3);
+	}
+
+	#[test]
+	fn get_returns_inserted_analysis() {
+		let cache = CodeCache::new(1);
+		let inserted = analysed(0x5b);
+		cache.insert(hash(1), inserted.clone());
+
+		let found = cache.get(hash(1)).unwrap();
+		assert!(Arc::ptr_eq(&found.code, &inserted.code));
+		assert!(Arc::ptr_eq(&found.valids, &inserted.valids));
+
+		cache.clear();
+		assert!(cache.is_empty());
+		assert!(cache.get(hash(1)).is_none());
+	}
+}
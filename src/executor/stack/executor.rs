@@ -1,15 +1,23 @@
-use crate::backend::Backend;
-use crate::gasometer::{self, Gasometer, StorageTarget};
+use crate::backend::{Backend, Log};
+use crate::executor::stack::code_cache::{AnalysedCode, CodeCache};
+use crate::gasometer::{
+	self, GasCost, GasSchedule, Gasometer, ResourceMeter, ResourceUsage, StorageTarget,
+};
+use crate::revert::RevertReason;
 use crate::{
-	Capture, Config, Context, CreateScheme, ExitError, ExitReason, ExitSucceed, Handler, Opcode,
-	Runtime, Stack, Transfer,
+	Capture, Config, Context, CreateScheme, ExitError, ExitReason, ExitSucceed, GasBlocks, Handler,
+	Opcode, Runtime, Stack, Transfer,
 };
 use alloc::{
+	boxed::Box,
 	collections::{BTreeMap, BTreeSet},
-	rc::Rc,
+	sync::Arc,
 	vec::Vec,
 };
-use core::{cmp::min, convert::Infallible};
+use core::{
+	cmp::{max, min},
+	convert::Infallible,
+};
 use evm_core::{ExitFatal, ExitRevert};
 use primitive_types::{H160, H256, U256};
 use sha3::{Digest, Keccak256};
@@ -20,6 +28,7 @@ macro_rules! emit_exit {
 		event!(Exit {
 			reason: &reason,
 			return_value: &Vec::new(),
+			revert: &RevertReason::from_exit(&reason, &[]),
 		});
 		reason
 	}};
@@ -29,11 +38,40 @@ macro_rules! emit_exit {
 		event!(Exit {
 			reason: &reason,
 			return_value: &return_value,
+			revert: &RevertReason::from_exit(&reason, &return_value),
 		});
 		(reason, return_value)
 	}};
 }
 
+/// Outcome of a transaction executed by `StackExecutor`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TransactionOutcome {
+	/// Exit reason.
+	pub reason: ExitReason,
+	/// Return data, or revert data.
+	pub output: Vec<u8>,
+	/// Address of the created contract, for successful creations.
+	pub address: Option<H160>,
+	/// Gas used, before refunds.
+	pub used_gas_before_refund: u64,
+	/// Gas used, after refunds.
+	pub used_gas: u64,
+	/// Gas refunded.
+	pub refunded_gas: u64,
+	/// Logs emitted by the transaction.
+	pub logs: Vec<Log>,
+	/// Resources used, if resource metering is enabled.
+	pub resources: Option<ResourceUsage>,
+}
+
+impl TransactionOutcome {
+	/// Decoded revert reason, if the transaction reverted.
+	pub fn revert_reason(&self) -> Option<RevertReason> {
+		RevertReason::from_exit(&self.reason, &self.output)
+	}
+}
+
 pub enum StackExitKind {
 	Succeeded,
 	Reverted,
@@ -76,20 +114,32 @@ pub struct StackSubstateMetadata<'config> {
 	is_static: bool,
 	depth: Option<usize>,
 	accessed: Option<Accessed>,
+	resources: Option<ResourceMeter>,
 }
 
 impl<'config> StackSubstateMetadata<'config> {
 	pub fn new(gas_limit: u64, config: &'config Config) -> Self {
+		Self::new_with_schedule(gas_limit, config, &gasometer::Ethereum)
+	}
+
+	/// Create the metadata of a transaction, charged with the given gas
+	/// schedule. Sub-calls use the same schedule.
+	pub fn new_with_schedule(
+		gas_limit: u64,
+		config: &'config Config,
+		schedule: &'config dyn GasSchedule,
+	) -> Self {
 		let accessed = if config.increase_state_access_gas {
 			Some(Accessed::default())
 		} else {
 			None
 		};
 		Self {
-			gasometer: Gasometer::new(gas_limit, config),
+			gasometer: Gasometer::new_with_schedule(gas_limit, config, schedule),
 			is_static: false,
 			depth: None,
 			accessed,
+			resources: None,
 		}
 	}
 
@@ -98,6 +148,12 @@ impl<'config> StackSubstateMetadata<'config> {
 		self.gasometer
 			.record_refund(other.gasometer.refunded_gas())?;
 
+		if let (Some(other_resources), Some(self_resources)) =
+			(other.resources, self.resources.as_mut())
+		{
+			self_resources.swallow_commit(other_resources);
+		}
+
 		if let (Some(mut other_accessed), Some(self_accessed)) =
 			(other.accessed, self.accessed.as_mut())
 		{
@@ -115,22 +171,39 @@ impl<'config> StackSubstateMetadata<'config> {
 	pub fn swallow_revert(&mut self, other: Self) -> Result<(), ExitError> {
 		self.gasometer.record_stipend(other.gasometer.gas())?;
 
+		if let (Some(other_resources), Some(self_resources)) =
+			(other.resources, self.resources.as_mut())
+		{
+			self_resources.swallow_revert(other_resources);
+		}
+
 		Ok(())
 	}
 
-	pub fn swallow_discard(&mut self, _other: Self) -> Result<(), ExitError> {
+	pub fn swallow_discard(&mut self, other: Self) -> Result<(), ExitError> {
+		if let (Some(other_resources), Some(self_resources)) =
+			(other.resources, self.resources.as_mut())
+		{
+			self_resources.swallow_revert(other_resources);
+		}
+
 		Ok(())
 	}
 
 	pub fn spit_child(&self, gas_limit: u64, is_static: bool) -> Self {
 		Self {
-			gasometer: Gasometer::new(gas_limit, self.gasometer.config()),
+			gasometer: Gasometer::new_with_schedule(
+				gas_limit,
+				self.gasometer.config(),
+				self.gasometer.schedule(),
+			),
 			is_static: is_static || self.is_static,
 			depth: match self.depth {
 				None => Some(0),
 				Some(n) => Some(n + 1),
 			},
 			accessed: self.accessed.as_ref().map(|_| Accessed::default()),
+			resources: self.resources.as_ref().map(ResourceMeter::spit_child),
 		}
 	}
 
@@ -142,6 +215,33 @@ impl<'config> StackSubstateMetadata<'config> {
 		&mut self.gasometer
 	}
 
+	/// Meter resource usage alongside gas, with the given limits.
+	pub fn set_resource_limits(&mut self, limits: ResourceUsage) {
+		self.resources = Some(ResourceMeter::new(limits));
+	}
+
+	/// Resource meter, if resource metering is enabled.
+	pub fn resources(&self) -> Option<&ResourceMeter> {
+		self.resources.as_ref()
+	}
+
+	/// Record resource usage, if resource metering is enabled.
+	pub fn record_resources(&mut self, usage: ResourceUsage) -> Result<(), ExitError> {
+		match &mut self.resources {
+			Some(resources) => resources.record(usage),
+			None => Ok(()),
+		}
+	}
+
+	/// Record resource usage of an opcode with the given dynamic cost,
+	/// charged `gas`, if resource metering is enabled.
+	pub fn record_cost_resources(&mut self, cost: GasCost, gas: u64) -> Result<(), ExitError> {
+		match &mut self.resources {
+			Some(resources) => resources.record_cost(cost, gas),
+			None => Ok(()),
+		}
+	}
+
 	pub fn is_static(&self) -> bool {
 		self.is_static
 	}
@@ -204,11 +304,17 @@ pub trait StackState<'config>: Backend {
 	fn set_storage(&mut self, address: H160, key: H256, value: H256);
 	fn reset_storage(&mut self, address: H160);
 	fn log(&mut self, address: H160, topics: Vec<H256>, data: Vec<u8>);
+	fn logs(&self) -> &[Log];
 	fn set_deleted(&mut self, address: H160);
 	fn set_code(&mut self, address: H160, code: Vec<u8>);
 	fn transfer(&mut self, transfer: Transfer) -> Result<(), ExitError>;
 	fn reset_balance(&mut self, address: H160);
+	fn deposit(&mut self, address: H160, value: U256);
 	fn touch(&mut self, address: H160);
+
+	/// Called by the executor at the start of each transaction, before it
+	/// changes any state.
+	fn start_transaction(&mut self) {}
 }
 
 /// Data returned by a precompile on success.
@@ -276,6 +382,60 @@ pub trait PrecompileHandle {
 
 	/// Retreive the gas limit of this call.
 	fn gas_limit(&self) -> Option<u64>;
+
+	/// Unmetered access to the state, if the handle provides it.
+	fn state_access(&mut self) -> Option<&mut dyn StateAccess> {
+		None
+	}
+
+	/// Metered access to the state, if the handle provides it.
+	fn as_stateful(&mut self) -> Option<&mut dyn StatefulPrecompileHandle> {
+		None
+	}
+}
+
+/// Handle giving precompiles access to the state, charging gas as the
+/// equivalent opcodes would.
+pub trait StatefulPrecompileHandle: PrecompileHandle {
+	/// Get storage value of the current address at index, charged as `SLOAD`.
+	fn storage(&mut self, index: H256) -> Result<H256, ExitError>;
+
+	/// Set storage value of the current address at index, charged as `SSTORE`.
+	/// Fails in a static call.
+	fn set_storage(&mut self, index: H256, value: H256) -> Result<(), ExitError>;
+
+	/// Get balance of address, charged as `BALANCE`.
+	fn balance(&mut self, address: H160) -> Result<U256, ExitError>;
+
+	/// Transfer value from the current address to target, charged as a value
+	/// transferring `CALL` without gas. The target is touched, even by a zero
+	/// value transfer. Fails in a static call.
+	fn transfer(&mut self, target: H160, value: U256) -> Result<(), ExitError>;
+
+	/// Get code of address, charged as `EXTCODECOPY` of the whole code.
+	fn code(&mut self, address: H160) -> Result<Arc<Vec<u8>>, ExitError>;
+}
+
+/// Unmetered access to the state and block environment of an executor,
+/// bypassing gas accounting and static checks. Meant for test environments
+/// such as cheatcodes.
+pub trait StateAccess {
+	/// Get storage value of address at index.
+	fn storage(&self, address: H160, index: H256) -> H256;
+	/// Set storage value of address at index.
+	fn set_storage(&mut self, address: H160, index: H256, value: H256);
+	/// Get balance of address.
+	fn balance(&self, address: H160) -> U256;
+	/// Set balance of address.
+	fn set_balance(&mut self, address: H160, balance: U256);
+	/// Get code of address.
+	fn code(&self, address: H160) -> Arc<Vec<u8>>;
+	/// Set code of address.
+	fn set_code(&mut self, address: H160, code: Vec<u8>);
+	/// Override the block number of the environment.
+	fn set_block_number(&mut self, number: U256);
+	/// Override the block timestamp of the environment.
+	fn set_block_timestamp(&mut self, timestamp: U256);
 }
 
 /// A precompile result.
@@ -348,6 +508,14 @@ pub struct StackExecutor<'config, 'precompiles, S, P> {
 	config: &'config Config,
 	state: S,
 	precompile_set: &'precompiles P,
+	code_cache: Option<Arc<CodeCache>>,
+	steps: u64,
+	/// Used and refunded gas of the gasometer before the current transaction.
+	gas_before_transaction: (u64, i64),
+	interrupt: Option<(u64, Box<dyn Fn() -> bool + Send>)>,
+	block_number: Option<U256>,
+	block_timestamp: Option<U256>,
+	state_access: bool,
 }
 
 impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
@@ -373,9 +541,38 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
 			config,
 			state,
 			precompile_set,
+			code_cache: None,
+			steps: 0,
+			gas_before_transaction: (0, 0),
+			interrupt: None,
+			block_number: None,
+			block_timestamp: None,
+			state_access: false,
 		}
 	}
 
+	/// Use the given cache for analysed code of called contracts. The cache
+	/// can be shared with other executors using the same config and gas
+	/// schedule.
+	pub fn set_code_cache(&mut self, code_cache: Arc<CodeCache>) {
+		self.code_cache = Some(code_cache);
+	}
+
+	/// Poll `interrupt` every `interval` executed opcodes, and abort the
+	/// transaction with `ExitFatal::Interrupted` once it returns true. All
+	/// state changes of the aborted transaction are discarded. The callback
+	/// is `Send`, so that an executor can be moved to another thread.
+	pub fn set_interrupt<F: Fn() -> bool + Send + 'static>(&mut self, interval: u64, interrupt: F) {
+		self.interrupt = Some((max(interval, 1), Box::new(interrupt)));
+	}
+
+	/// Give precompiles unmetered access to the state, through
+	/// `PrecompileHandle::state_access`. Disabled by default, as it lets
+	/// precompiles bypass gas accounting and static checks.
+	pub fn set_state_access(&mut self, enabled: bool) {
+		self.state_access = enabled;
+	}
+
 	pub fn state(&self) -> &S {
 		&self.state
 	}
@@ -390,6 +587,10 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
 
 	/// Create a substate executor from the current executor.
 	pub fn enter_substate(&mut self, gas_limit: u64, is_static: bool) {
+		event!(Enter {
+			gas_limit,
+			is_static
+		});
 		self.state.enter(gas_limit, is_static);
 	}
 
@@ -410,6 +611,72 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
 		}
 	}
 
+	/// Analyse code for execution. Static gas analysis is only done if block
+	/// gas precharging is enabled.
+	fn analyse(&self, mut analysed: AnalysedCode) -> AnalysedCode {
+		if self.config.precharge_block_gas && analysed.gas_blocks.is_none() {
+			let schedule = self.state.metadata().gasometer.schedule();
+			let gas_blocks = GasBlocks::new_with_immediates(
+				&analysed.code,
+				&analysed.valids,
+				|opcode| schedule.static_opcode_cost(opcode),
+				|opcode| self.config.custom_opcodes.immediates(opcode),
+			);
+			analysed.gas_blocks = Some(Arc::new(gas_blocks));
+		}
+
+		analysed
+	}
+
+	/// Analyse code bytes, skipping the immediate data of custom opcodes.
+	fn analyse_code(&self, code: Arc<Vec<u8>>) -> AnalysedCode {
+		let analysed = AnalysedCode::new_with_immediates(code, |opcode| {
+			self.config.custom_opcodes.immediates(opcode)
+		});
+		self.analyse(analysed)
+	}
+
+	/// Get the analysed code of an address, going through the code cache if
+	/// one is set.
+	fn analysed_code(&self, address: H160) -> AnalysedCode {
+		let code_cache = match &self.code_cache {
+			Some(code_cache) => code_cache,
+			None => return self.analyse_code(self.code(address)),
+		};
+
+		let code_hash = self.state.code_hash(address);
+		match code_cache.get(code_hash) {
+			Some(analysed) if analysed.gas_blocks.is_some() || !self.config.precharge_block_gas => {
+				analysed
+			}
+			cached => {
+				let analysed = match cached {
+					Some(analysed) => self.analyse(analysed),
+					None => self.analyse_code(self.code(address)),
+				};
+				code_cache.insert(code_hash, analysed.clone());
+				analysed
+			}
+		}
+	}
+
+	/// Create a runtime for analysed code.
+	fn runtime(
+		&self,
+		analysed: AnalysedCode,
+		data: Arc<Vec<u8>>,
+		context: Context,
+	) -> Runtime<'config> {
+		let mut runtime =
+			Runtime::new_with_valids(analysed.code, analysed.valids, data, context, self.config);
+
+		if let Some(gas_blocks) = analysed.gas_blocks {
+			runtime.set_gas_blocks(gas_blocks);
+		}
+
+		runtime
+	}
+
 	/// Get remaining gas.
 	pub fn gas(&self) -> u64 {
 		self.state.metadata().gasometer.gas()
@@ -421,8 +688,12 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
 		access_list: &[(H160, Vec<H256>)],
 	) -> Result<(), ExitError> {
 		let transaction_cost = gasometer::create_transaction_cost(init_code, access_list);
-		let gasometer = &mut self.state.metadata_mut().gasometer;
-		gasometer.record_transaction(transaction_cost)
+		let metadata = self.state.metadata_mut();
+		metadata.gasometer.record_transaction(transaction_cost)?;
+		metadata.record_resources(ResourceUsage {
+			calldata_bytes: init_code.len() as u64,
+			..Default::default()
+		})
 	}
 
 	/// Execute a `CREATE` transaction.
@@ -433,7 +704,9 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
 		init_code: Vec<u8>,
 		gas_limit: u64,
 		access_list: Vec<(H160, Vec<H256>)>, // See EIP-2930
-	) -> (ExitReason, Vec<u8>) {
+	) -> TransactionOutcome {
+		self.start_transaction();
+		let logs = self.state.logs().len();
 		event!(TransactCreate {
 			caller,
 			value,
@@ -443,7 +716,8 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
 		});
 
 		if let Err(e) = self.record_create_transaction_cost(&init_code, &access_list) {
-			return emit_exit!(e.into(), Vec::new());
+			let (reason, output) = emit_exit!(e.into(), Vec::new());
+			return self.outcome(reason, output, None, logs);
 		}
 		self.initialize_with_access_list(access_list);
 
@@ -455,7 +729,10 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
 			Some(gas_limit),
 			false,
 		) {
-			Capture::Exit((s, _, v)) => emit_exit!(s, v),
+			Capture::Exit((s, address, v)) => {
+				let (reason, output) = emit_exit!(s, v);
+				self.outcome(reason, output, address, logs)
+			}
 			Capture::Trap(_) => unreachable!(),
 		}
 	}
@@ -469,7 +746,9 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
 		salt: H256,
 		gas_limit: u64,
 		access_list: Vec<(H160, Vec<H256>)>, // See EIP-2930
-	) -> (ExitReason, Vec<u8>) {
+	) -> TransactionOutcome {
+		self.start_transaction();
+		let logs = self.state.logs().len();
 		let code_hash = H256::from_slice(Keccak256::digest(&init_code).as_slice());
 		event!(TransactCreate2 {
 			caller,
@@ -485,7 +764,8 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
 		});
 
 		if let Err(e) = self.record_create_transaction_cost(&init_code, &access_list) {
-			return emit_exit!(e.into(), Vec::new());
+			let (reason, output) = emit_exit!(e.into(), Vec::new());
+			return self.outcome(reason, output, None, logs);
 		}
 		self.initialize_with_access_list(access_list);
 
@@ -501,7 +781,10 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
 			Some(gas_limit),
 			false,
 		) {
-			Capture::Exit((s, _, v)) => emit_exit!(s, v),
+			Capture::Exit((s, address, v)) => {
+				let (reason, output) = emit_exit!(s, v);
+				self.outcome(reason, output, address, logs)
+			}
 			Capture::Trap(_) => unreachable!(),
 		}
 	}
@@ -512,6 +795,10 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
 	/// Takes in an additional `access_list` parameter for EIP-2930 which was
 	/// introduced in the Ethereum Berlin hard fork. If you do not wish to use
 	/// this functionality, just pass in an empty vector.
+	///
+	/// Like the other `transact_*` methods, it starts with all addresses and
+	/// slots cold, except the caller, the called address and those of the
+	/// access list.
 	pub fn transact_call(
 		&mut self,
 		caller: H160,
@@ -520,7 +807,9 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
 		data: Vec<u8>,
 		gas_limit: u64,
 		access_list: Vec<(H160, Vec<H256>)>,
-	) -> (ExitReason, Vec<u8>) {
+	) -> TransactionOutcome {
+		self.start_transaction();
+		let logs = self.state.logs().len();
 		event!(TransactCall {
 			caller,
 			address,
@@ -530,10 +819,21 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
 		});
 
 		let transaction_cost = gasometer::call_transaction_cost(&data, &access_list);
-		let gasometer = &mut self.state.metadata_mut().gasometer;
-		match gasometer.record_transaction(transaction_cost) {
+		let metadata = self.state.metadata_mut();
+		match metadata
+			.gasometer
+			.record_transaction(transaction_cost)
+			.and_then(|()| {
+				metadata.record_resources(ResourceUsage {
+					calldata_bytes: data.len() as u64,
+					..Default::default()
+				})
+			}) {
 			Ok(()) => (),
-			Err(e) => return emit_exit!(e.into(), Vec::new()),
+			Err(e) => {
+				let (reason, output) = emit_exit!(e.into(), Vec::new());
+				return self.outcome(reason, output, None, logs);
+			}
 		}
 
 		// Initialize initial addresses for EIP-2929
@@ -566,11 +866,70 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
 			false,
 			context,
 		) {
-			Capture::Exit((s, v)) => emit_exit!(s, v),
+			Capture::Exit((s, v)) => {
+				let (reason, output) = emit_exit!(s, v);
+				self.outcome(reason, output, None, logs)
+			}
 			Capture::Trap(_) => unreachable!(),
 		}
 	}
 
+	/// Reset the state kept by the executor for the current transaction.
+	///
+	/// Accessed addresses and slots are reset too, as EIP-2929 makes them
+	/// cold again at the start of each transaction. This also drops any
+	/// added to the substate before the transaction, which must be given in
+	/// its access list instead.
+	fn start_transaction(&mut self) {
+		let metadata = self.state.metadata_mut();
+		self.gas_before_transaction = (
+			metadata.gasometer.total_used_gas(),
+			metadata.gasometer.refunded_gas(),
+		);
+		if let Some(accessed) = metadata.accessed.as_mut() {
+			*accessed = Accessed::default();
+		}
+		self.steps = 0;
+		self.block_number = None;
+		self.block_timestamp = None;
+		self.state.start_transaction();
+	}
+
+	/// Build the outcome of a transaction, with the logs emitted after the
+	/// first `logs` ones. Gas is counted from the start of the transaction,
+	/// so that an executor can be reused.
+	fn outcome(
+		&self,
+		reason: ExitReason,
+		output: Vec<u8>,
+		address: Option<H160>,
+		logs: usize,
+	) -> TransactionOutcome {
+		let gasometer = &self.state.metadata().gasometer;
+		let (used_before, refunded_before) = self.gas_before_transaction;
+		let used_gas_before_refund = gasometer.total_used_gas() - used_before;
+		let refunded_gas = min(
+			used_gas_before_refund / self.config.max_refund_quotient,
+			max(gasometer.refunded_gas() - refunded_before, 0) as u64,
+		);
+
+		TransactionOutcome {
+			reason,
+			output,
+			address,
+			used_gas_before_refund,
+			used_gas: used_gas_before_refund - refunded_gas,
+			refunded_gas,
+			logs: self.state.logs()[logs..].to_vec(),
+			resources: self.state.metadata().resources().map(ResourceMeter::used),
+		}
+	}
+
+	/// Get the number of opcodes executed by the last transaction.
+	pub fn steps(&self) -> u64 {
+		self.steps
+	}
+
 	/// Get used gas for the current executor, given the price.
 	pub fn used_gas(&self) -> u64 {
 		self.state.metadata().gasometer.total_used_gas()
@@ -738,12 +1097,8 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
 			self.state.inc_nonce(address);
 		}
 
-		let mut runtime = Runtime::new(
-			Rc::new(init_code),
-			Rc::new(Vec::new()),
-			context,
-			self.config,
-		);
+		let init_code = self.analyse_code(Arc::new(init_code));
+		let mut runtime = self.runtime(init_code, Arc::new(Vec::new()), context);
 
 		let reason = self.execute(&mut runtime);
 		log::debug!(target: "evm", "Create execution using address {}: {:?}", address, reason);
@@ -771,12 +1126,13 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
 					}
 				}
 
-				match self
-					.state
-					.metadata_mut()
-					.gasometer
-					.record_deposit(out.len())
-				{
+				let metadata = self.state.metadata_mut();
+				match metadata.gasometer.record_deposit(out.len()).and_then(|()| {
+					metadata.record_resources(ResourceUsage {
+						new_state_bytes: out.len() as u64,
+						..Default::default()
+					})
+				}) {
 					Ok(()) => {
 						let e = self.exit_substate(StackExitKind::Succeeded);
 						self.state.set_code(address, out);
@@ -868,7 +1224,7 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
 			}
 		}
 
-		let code = self.code(code_address);
+		let code = self.analysed_code(code_address);
 
 		self.enter_substate(gas_limit, is_static);
 		self.state.touch(context.address);
@@ -925,7 +1281,7 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
 			};
 		}
 
-		let mut runtime = Runtime::new(Rc::new(code), Rc::new(input), context, self.config);
+		let mut runtime = self.runtime(code, Arc::new(input), context);
 
 		let reason = self.execute(&mut runtime);
 		log::debug!(target: "evm", "Call execution using address {}: {:?}", code_address, reason);
@@ -965,7 +1321,7 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet> Handler
 	}
 
 	fn code_size(&self, address: H160) -> U256 {
-		U256::from(self.state.code(address).len())
+		U256::from(self.state.code_size(address))
 	}
 
 	fn code_hash(&self, address: H160) -> H256 {
@@ -973,10 +1329,10 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet> Handler
 			return H256::default();
 		}
 
-		H256::from_slice(Keccak256::digest(&self.state.code(address)).as_slice())
+		self.state.code_hash(address)
 	}
 
-	fn code(&self, address: H160) -> Vec<u8> {
+	fn code(&self, address: H160) -> Arc<Vec<u8>> {
 		self.state.code(address)
 	}
 
@@ -1019,13 +1375,15 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet> Handler
 		self.state.block_hash(number)
 	}
 	fn block_number(&self) -> U256 {
-		self.state.block_number()
+		self.block_number
+			.unwrap_or_else(|| self.state.block_number())
 	}
 	fn block_coinbase(&self) -> H160 {
 		self.state.block_coinbase()
 	}
 	fn block_timestamp(&self) -> U256 {
-		self.state.block_timestamp()
+		self.block_timestamp
+			.unwrap_or_else(|| self.state.block_timestamp())
 	}
 	fn block_difficulty(&self) -> U256 {
 		self.state.block_difficulty()
@@ -1163,8 +1521,14 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet> Handler
 	) -> Result<(), ExitError> {
 		// log::trace!(target: "evm", "Running opcode: {:?}, Pre gas-left: {:?}", opcode, gasometer.gas());
 
-		if let Some(cost) = gasometer::static_opcode_cost(opcode) {
-			self.state.metadata_mut().gasometer.record_cost(cost)?;
+		let schedule = self.state.metadata().gasometer.schedule();
+		if let Some(cost) = schedule.static_opcode_cost(opcode) {
+			let metadata = self.state.metadata_mut();
+			metadata.gasometer.record_cost(cost)?;
+			metadata.record_resources(ResourceUsage {
+				compute: cost,
+				..Default::default()
+			})?;
 		} else {
 			let is_static = self.state.metadata().is_static;
 			let (gas_cost, target, memory_cost) = gasometer::dynamic_opcode_cost(
@@ -1176,9 +1540,14 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet> Handler
 				self,
 			)?;
 
-			let gasometer = &mut self.state.metadata_mut().gasometer;
+			let metadata = self.state.metadata_mut();
+			let used_gas = metadata.gasometer.total_used_gas();
 
-			gasometer.record_dynamic_cost(gas_cost, memory_cost)?;
+			metadata
+				.gasometer
+				.record_dynamic_cost(gas_cost, memory_cost)?;
+			let gas = metadata.gasometer.total_used_gas() - used_gas;
+			metadata.record_cost_resources(gas_cost, gas)?;
 			match target {
 				StorageTarget::Address(address) => {
 					self.state.metadata_mut().access_address(address)
@@ -1192,6 +1561,37 @@ impl<'config, 'precompiles, S: StackState<'config>, P: PrecompileSet> Handler
 
 		Ok(())
 	}
+
+	fn record_step(&mut self) -> Result<(), ExitFatal> {
+		self.steps += 1;
+		if let Some(limit) = self.config.step_limit {
+			if self.steps > limit {
+				return Err(ExitFatal::StepLimitReached);
+			}
+		}
+
+		match &self.interrupt {
+			Some((interval, interrupt)) if self.steps % interval == 0 && interrupt() => {
+				Err(ExitFatal::Interrupted)
+			}
+			_ => Ok(()),
+		}
+	}
+
+	#[inline]
+	fn pre_validate_block(&mut self, _context: &Context, static_gas: u64) -> bool {
+		let metadata = self.state.metadata_mut();
+		let compute = ResourceUsage {
+			compute: static_gas,
+			..Default::default()
+		};
+
+		// Resources are only recorded once the gas is known to be enough, so
+		// that falling back to `pre_validate` does not record them twice.
+		metadata.gasometer.gas() >= static_gas
+			&& metadata.record_resources(compute).is_ok()
+			&& metadata.gasometer.record_cost(static_gas).is_ok()
+	}
 }
 
 struct StackExecutorHandle<'inner, 'config, 'precompiles, S, P> {
@@ -1270,11 +1670,12 @@ impl<'inner, 'config, 'precompiles, S: StackState<'config>, P: PrecompileSet> Pr
 
 	/// Record cost to the Runtime gasometer.
 	fn record_cost(&mut self, cost: u64) -> Result<(), ExitError> {
-		self.executor
-			.state
-			.metadata_mut()
-			.gasometer
-			.record_cost(cost)
+		let metadata = self.executor.state.metadata_mut();
+		metadata.gasometer.record_cost(cost)?;
+		metadata.record_resources(ResourceUsage {
+			compute: cost,
+			..Default::default()
+		})
 	}
 
 	/// Retreive the remaining gas.
@@ -1284,6 +1685,13 @@ impl<'inner, 'config, 'precompiles, S: StackState<'config>, P: PrecompileSet> Pr
 
 	/// Record a log.
 	fn log(&mut self, address: H160, topics: Vec<H256>, data: Vec<u8>) -> Result<(), ExitError> {
+		self.executor
+			.state
+			.metadata_mut()
+			.record_resources(ResourceUsage {
+				log_bytes: (data.len() + 32 * topics.len()) as u64,
+				..Default::default()
+			})?;
 		Handler::log(self.executor, address, topics, data)
 	}
 
@@ -1311,4 +1719,150 @@ impl<'inner, 'config, 'precompiles, S: StackState<'config>, P: PrecompileSet> Pr
 	fn gas_limit(&self) -> Option<u64> {
 		self.gas_limit
 	}
+
+	/// Unmetered access to the state of the executor, if enabled with
+	/// `StackExecutor::set_state_access`.
+	fn state_access(&mut self) -> Option<&mut dyn StateAccess> {
+		if self.executor.state_access {
+			Some(self)
+		} else {
+			None
+		}
+	}
+
+	/// Metered access to the state of the executor.
+	fn as_stateful(&mut self) -> Option<&mut dyn StatefulPrecompileHandle> {
+		Some(self)
+	}
+}
+
+impl<'inner, 'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
+	StatefulPrecompileHandle for StackExecutorHandle<'inner, 'config, 'precompiles, S, P>
+{
+	fn storage(&mut self, index: H256) -> Result<H256, ExitError> {
+		let address = self.context.address;
+		let target_is_cold = self.executor.is_cold(address, Some(index));
+		self.record_access(
+			GasCost::SLoad { target_is_cold },
+			StorageTarget::Slot(address, index),
+		)?;
+
+		Ok(self.executor.state.storage(address, index))
+	}
+
+	fn set_storage(&mut self, index: H256, value: H256) -> Result<(), ExitError> {
+		if self.is_static {
+			return Err(ExitError::InvalidCode(Opcode::SSTORE));
+		}
+
+		let address = self.context.address;
+		let cost = GasCost::SStore {
+			original: Handler::original_storage(self.executor, address, index),
+			current: self.executor.state.storage(address, index),
+			new: value,
+			target_is_cold: self.executor.is_cold(address, Some(index)),
+		};
+		self.record_access(cost, StorageTarget::Slot(address, index))?;
+
+		self.executor.state.set_storage(address, index, value);
+		Ok(())
+	}
+
+	fn balance(&mut self, address: H160) -> Result<U256, ExitError> {
+		let target_is_cold = self.executor.is_cold(address, None);
+		self.record_access(
+			GasCost::Balance { target_is_cold },
+			StorageTarget::Address(address),
+		)?;
+
+		Ok(self.executor.state.basic(address).balance)
+	}
+
+	fn transfer(&mut self, target: H160, value: U256) -> Result<(), ExitError> {
+		if self.is_static {
+			return Err(ExitError::InvalidCode(Opcode::CALL));
+		}
+
+		let cost = GasCost::Call {
+			value,
+			gas: U256::zero(),
+			target_is_cold: self.executor.is_cold(target, None),
+			target_exists: self.executor.exists(target),
+		};
+		self.record_access(cost, StorageTarget::Address(target))?;
+
+		self.executor.state.transfer(Transfer {
+			source: self.context.address,
+			target,
+			value,
+		})?;
+		self.executor.state.touch(target);
+		Ok(())
+	}
+
+	fn code(&mut self, address: H160) -> Result<Arc<Vec<u8>>, ExitError> {
+		let cost = GasCost::ExtCodeCopy {
+			target_is_cold: self.executor.is_cold(address, None),
+			len: U256::from(self.executor.state.code_size(address)),
+		};
+		self.record_access(cost, StorageTarget::Address(address))?;
+
+		Ok(self.executor.state.code(address))
+	}
+}
+
+impl<'inner, 'config, 'precompiles, S: StackState<'config>, P: PrecompileSet>
+	StackExecutorHandle<'inner, 'config, 'precompiles, S, P>
+{
+	fn record_access(&mut self, cost: GasCost, target: StorageTarget) -> Result<(), ExitError> {
+		let metadata = self.executor.state.metadata_mut();
+		let used_gas = metadata.gasometer.total_used_gas();
+		metadata.gasometer.record_dynamic_cost(cost, None)?;
+		let gas = metadata.gasometer.total_used_gas() - used_gas;
+		metadata.record_cost_resources(cost, gas)?;
+		match target {
+			StorageTarget::Address(address) => metadata.access_address(address),
+			StorageTarget::Slot(address, index) => metadata.access_storage(address, index),
+			StorageTarget::None => (),
+		}
+
+		Ok(())
+	}
+}
+
+impl<'inner, 'config, 'precompiles, S: StackState<'config>, P: PrecompileSet> StateAccess
+	for StackExecutorHandle<'inner, 'config, 'precompiles, S, P>
+{
+	fn storage(&self, address: H160, index: H256) -> H256 {
+		self.executor.state.storage(address, index)
+	}
+
+	fn set_storage(&mut self, address: H160, index: H256, value: H256) {
+		self.executor.state.set_storage(address, index, value)
+	}
+
+	fn balance(&self, address: H160) -> U256 {
+		self.executor.state.basic(address).balance
+	}
+
+	fn set_balance(&mut self, address: H160, balance: U256) {
+		self.executor.state.reset_balance(address);
+		self.executor.state.deposit(address, balance);
+	}
+
+	fn code(&self, address: H160) -> Arc<Vec<u8>> {
+		self.executor.state.code(address)
+	}
+
+	fn set_code(&mut self, address: H160, code: Vec<u8>) {
+		self.executor.state.set_code(address, code)
+	}
+
+	fn set_block_number(&mut self, number: U256) {
+		self.executor.block_number = Some(number);
+	}
+
+	fn set_block_timestamp(&mut self, timestamp: U256) {
+		self.executor.block_timestamp = Some(timestamp);
+	}
 }
@@ -4,18 +4,105 @@ use crate::{ExitError, Transfer};
 use alloc::{
 	boxed::Box,
 	collections::{BTreeMap, BTreeSet},
+	sync::Arc,
 	vec::Vec,
 };
-use core::mem;
+use core::{cell::RefCell, mem, ops::RangeInclusive};
 use primitive_types::{H160, H256, U256};
+use sha3::{Digest, Keccak256};
 
 #[derive(Clone, Debug)]
 pub struct MemoryStackAccount {
 	pub basic: Basic,
-	pub code: Option<Vec<u8>>,
+	pub code: Option<Arc<Vec<u8>>>,
 	pub reset: bool,
 }
 
+/// State created and cleared by a transaction, compared to the state at its
+/// start.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StateGrowth {
+	/// Accounts that did not exist or were empty, and are not empty.
+	pub new_accounts: u64,
+	/// Storage slots that were zero, and are set to non-zero. Slots of
+	/// accounts reset by the transaction are zero before they are set.
+	pub new_storage_slots: u64,
+	/// Storage slots that were non-zero, and are set to zero or wiped by a
+	/// reset. `None` if a reset wiped slots that the backend cannot count.
+	pub cleared_storage_slots: Option<u64>,
+	/// Bytes of code set by the transaction, including code replacing the
+	/// code of an existing account.
+	pub new_code_bytes: u64,
+}
+
+impl Default for StateGrowth {
+	fn default() -> Self {
+		Self {
+			new_accounts: 0,
+			new_storage_slots: 0,
+			cleared_storage_slots: Some(0),
+			new_code_bytes: 0,
+		}
+	}
+}
+
+/// Accounts and storage of the top-level substate at the start of the
+/// transaction, on top of the backend.
+#[derive(Clone, Debug, Default)]
+struct TransactionOrigin {
+	accounts: BTreeMap<H160, MemoryStackAccount>,
+	storages: BTreeMap<(H160, H256), H256>,
+}
+
+impl TransactionOrigin {
+	fn exists<B: Backend>(&self, address: H160, backend: &B) -> bool {
+		match self.accounts.get(&address) {
+			Some(account) if !is_empty(account) => true,
+			_ => backend.exists(address),
+		}
+	}
+
+	fn storage<B: Backend>(&self, address: H160, index: H256, backend: &B) -> H256 {
+		if let Some(value) = self.storages.get(&(address, index)) {
+			return *value;
+		}
+
+		match self.accounts.get(&address) {
+			Some(account) if account.reset => H256::default(),
+			_ => backend.storage(address, index),
+		}
+	}
+
+	/// Number of non-zero storage slots of an account that is not reset, if
+	/// the backend can count them.
+	fn storage_count<B: Backend>(&self, address: H160, backend: &B) -> Option<u64> {
+		let mut count = backend.storage_count(address)? as u64;
+		for ((_, index), value) in self.storages.range(storage_range(address)) {
+			if backend.storage(address, *index) != H256::default() {
+				count -= 1;
+			}
+			if *value != H256::default() {
+				count += 1;
+			}
+		}
+		Some(count)
+	}
+}
+
+fn is_empty(account: &MemoryStackAccount) -> bool {
+	account.basic.nonce == U256::zero()
+		&& account.basic.balance == U256::zero()
+		&& account
+			.code
+			.as_ref()
+			.map(|code| code.is_empty())
+			.unwrap_or(true)
+}
+
+fn storage_range(address: H160) -> RangeInclusive<(H160, H256)> {
+	(address, H256::zero())..=(address, H256::repeat_byte(0xff))
+}
+
 #[derive(Clone, Debug)]
 pub struct MemoryStackSubstate<'config> {
 	metadata: StackSubstateMetadata<'config>,
@@ -96,7 +183,9 @@ impl<'config> MemoryStackSubstate<'config> {
 				Apply::Modify {
 					address,
 					basic: account.basic.clone(),
-					code: account.code.clone(),
+					code: account.code.take().map(|code| {
+						Arc::try_unwrap(code).unwrap_or_else(|code| code.as_ref().clone())
+					}),
 					storage,
 					reset_storage: account.reset,
 				}
@@ -112,6 +201,78 @@ impl<'config> MemoryStackSubstate<'config> {
 		(applies, self.logs)
 	}
 
+	fn transaction_origin(&self) -> TransactionOrigin {
+		assert!(self.parent.is_none());
+
+		TransactionOrigin {
+			accounts: self.accounts.clone(),
+			storages: self.storages.clone(),
+		}
+	}
+
+	/// State growth since `origin`. Deleted accounts are not counted. Panic
+	/// if the executor is not in the top-level substate.
+	fn state_growth<B: Backend>(&self, origin: &TransactionOrigin, backend: &B) -> StateGrowth {
+		assert!(self.parent.is_none());
+
+		let mut growth = StateGrowth::default();
+
+		let mut addresses = BTreeSet::new();
+
+		for address in self.accounts.keys() {
+			addresses.insert(*address);
+		}
+
+		for (address, _) in self.storages.keys() {
+			addresses.insert(*address);
+		}
+
+		for address in addresses {
+			if self.deletes.contains(&address) {
+				continue;
+			}
+
+			let original = origin.accounts.get(&address);
+			let mut reset = false;
+			if let Some(account) = self.accounts.get(&address) {
+				if !is_empty(account) && !origin.exists(address, backend) {
+					growth.new_accounts += 1;
+				}
+
+				if let Some(code) = &account.code {
+					if original.and_then(|original| original.code.as_ref()) != Some(code) {
+						growth.new_code_bytes += code.len() as u64;
+					}
+				}
+
+				reset = account.reset && !original.map(|original| original.reset).unwrap_or(false);
+			}
+
+			if reset {
+				growth.cleared_storage_slots = growth
+					.cleared_storage_slots
+					.zip(origin.storage_count(address, backend))
+					.map(|(cleared, count)| cleared + count);
+			}
+			for ((_, index), value) in self.storages.range(storage_range(address)) {
+				let original = if reset {
+					H256::default()
+				} else {
+					origin.storage(address, *index, backend)
+				};
+
+				if original == H256::default() && *value != H256::default() {
+					growth.new_storage_slots += 1;
+				} else if original != H256::default() && *value == H256::default() {
+					growth.cleared_storage_slots =
+						growth.cleared_storage_slots.map(|cleared| cleared + 1);
+				}
+			}
+		}
+
+		growth
+	}
+
 	pub fn enter(&mut self, gas_limit: u64, is_static: bool) {
 		let mut entering = Self {
 			metadata: self.metadata.spit_child(gas_limit, is_static),
@@ -188,7 +349,7 @@ impl<'config> MemoryStackSubstate<'config> {
 		self.known_account(address).map(|acc| acc.basic.clone())
 	}
 
-	pub fn known_code(&self, address: H160) -> Option<Vec<u8>> {
+	pub fn known_code(&self, address: H160) -> Option<Arc<Vec<u8>>> {
 		self.known_account(address).and_then(|acc| acc.code.clone())
 	}
 
@@ -338,7 +499,7 @@ impl<'config> MemoryStackSubstate<'config> {
 	}
 
 	pub fn set_code<B: Backend>(&mut self, address: H160, code: Vec<u8>, backend: &B) {
-		self.account_mut(address, backend).code = Some(code);
+		self.account_mut(address, backend).code = Some(Arc::new(code));
 	}
 
 	pub fn transfer<B: Backend>(
@@ -393,10 +554,40 @@ impl<'config> MemoryStackSubstate<'config> {
 	}
 }
 
+/// Code hashes memoized per address, together with the code they were
+/// computed from, so that they are only computed again when the code of the
+/// address is replaced.
+#[derive(Clone, Debug, Default)]
+#[allow(clippy::type_complexity)]
+struct CodeHashes(RefCell<BTreeMap<H160, (Arc<Vec<u8>>, H256)>>);
+
+impl CodeHashes {
+	/// Hash of the current code of an address.
+	fn get(&self, address: H160, code: Arc<Vec<u8>>) -> H256 {
+		if let Some((hashed, code_hash)) = self.0.borrow().get(&address) {
+			if Arc::ptr_eq(hashed, &code) {
+				return *code_hash;
+			}
+		}
+
+		let code_hash = H256::from_slice(Keccak256::digest(&code[..]).as_slice());
+		self.0.borrow_mut().insert(address, (code, code_hash));
+		code_hash
+	}
+}
+
 #[derive(Clone, Debug)]
 pub struct MemoryStackState<'backend, 'config, B> {
 	backend: &'backend B,
 	substate: MemoryStackSubstate<'config>,
+	/// Code hashes of backend accounts seen so far.
+	code_hashes: RefCell<BTreeMap<H160, H256>>,
+	/// Backend code seen so far, shared by all accounts with the same code.
+	codes: RefCell<BTreeMap<H256, Arc<Vec<u8>>>>,
+	/// Hashes of code set by the execution.
+	set_code_hashes: CodeHashes,
+	/// State at the start of the current transaction.
+	origin: TransactionOrigin,
 }
 
 impl<'backend, 'config, B: Backend> Backend for MemoryStackState<'backend, 'config, B> {
@@ -442,10 +633,30 @@ impl<'backend, 'config, B: Backend> Backend for MemoryStackState<'backend, 'conf
 			.unwrap_or_else(|| self.backend.basic(address))
 	}
 
-	fn code(&self, address: H160) -> Vec<u8> {
+	fn code(&self, address: H160) -> Arc<Vec<u8>> {
 		self.substate
 			.known_code(address)
-			.unwrap_or_else(|| self.backend.code(address))
+			.unwrap_or_else(|| self.backend_code(address).1)
+	}
+
+	fn code_size(&self, address: H160) -> usize {
+		if let Some(code) = self.substate.known_code(address) {
+			return code.len();
+		}
+		if let Some(code_hash) = self.code_hashes.borrow().get(&address) {
+			if let Some(code) = self.codes.borrow().get(code_hash) {
+				return code.len();
+			}
+		}
+
+		self.backend.code_size(address)
+	}
+
+	fn code_hash(&self, address: H160) -> H256 {
+		match self.substate.known_code(address) {
+			Some(code) => self.set_code_hashes.get(address, code),
+			None => self.backend_code(address).0,
+		}
 	}
 
 	fn storage(&self, address: H160, key: H256) -> H256 {
@@ -526,6 +737,10 @@ impl<'backend, 'config, B: Backend> StackState<'config> for MemoryStackState<'ba
 		self.substate.log(address, topics, data);
 	}
 
+	fn logs(&self) -> &[Log] {
+		self.substate.logs()
+	}
+
 	fn set_deleted(&mut self, address: H160) {
 		self.substate.set_deleted(address)
 	}
@@ -542,9 +757,17 @@ impl<'backend, 'config, B: Backend> StackState<'config> for MemoryStackState<'ba
 		self.substate.reset_balance(address, self.backend)
 	}
 
+	fn deposit(&mut self, address: H160, value: U256) {
+		self.substate.deposit(address, value, self.backend)
+	}
+
 	fn touch(&mut self, address: H160) {
 		self.substate.touch(address, self.backend)
 	}
+
+	fn start_transaction(&mut self) {
+		self.origin = self.substate.transaction_origin();
+	}
 }
 
 impl<'backend, 'config, B: Backend> MemoryStackState<'backend, 'config, B> {
@@ -552,14 +775,46 @@ impl<'backend, 'config, B: Backend> MemoryStackState<'backend, 'config, B> {
 		Self {
 			backend,
 			substate: MemoryStackSubstate::new(metadata),
+			code_hashes: RefCell::new(BTreeMap::new()),
+			codes: RefCell::new(BTreeMap::new()),
+			set_code_hashes: CodeHashes::default(),
+			origin: TransactionOrigin::default(),
 		}
 	}
 
+	/// Code hash and code of an address in the backend, fetched only once
+	/// per address.
+	fn backend_code(&self, address: H160) -> (H256, Arc<Vec<u8>>) {
+		if let Some(code_hash) = self.code_hashes.borrow().get(&address) {
+			if let Some(code) = self.codes.borrow().get(code_hash) {
+				return (*code_hash, code.clone());
+			}
+		}
+
+		let code_hash = self.backend.code_hash(address);
+		let code = self
+			.codes
+			.borrow_mut()
+			.entry(code_hash)
+			.or_insert_with(|| self.backend.code(address))
+			.clone();
+		self.code_hashes.borrow_mut().insert(address, code_hash);
+
+		(code_hash, code)
+	}
+
 	/// Returns a mutable reference to an account given its address
 	pub fn account_mut(&mut self, address: H160) -> &mut MemoryStackAccount {
 		self.substate.account_mut(address, self.backend)
 	}
 
+	/// State growth of the current transaction, or of all the changes if the
+	/// state was not used by an executor. Panic if the executor is not in the
+	/// top-level substate.
+	pub fn state_growth(&self) -> StateGrowth {
+		self.substate.state_growth(&self.origin, self.backend)
+	}
+
 	#[must_use]
 	pub fn deconstruct(
 		self,
@@ -567,6 +822,9 @@ impl<'backend, 'config, B: Backend> MemoryStackState<'backend, 'config, B> {
 		impl IntoIterator<Item = Apply<impl IntoIterator<Item = (H256, H256)>>>,
 		impl IntoIterator<Item = Log>,
 	) {
+		// Code set by the execution is moved out rather than copied when it is
+		// no longer shared.
+		drop(self.set_code_hashes);
 		self.substate.deconstruct(self.backend)
 	}
 
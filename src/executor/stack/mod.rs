@@ -2,14 +2,17 @@
 //! A memory-based state is provided, but can replaced by a custom
 //! implementation, for exemple one interacting with a database.
 
+mod code_cache;
 mod executor;
 mod memory;
 
+pub use self::code_cache::{AnalysedCode, CodeCache};
 pub use self::executor::{
-	Accessed, PrecompileFailure, PrecompileFn, PrecompileHandle, PrecompileOutput, PrecompileSet,
-	StackExecutor, StackExitKind, StackState, StackSubstateMetadata,
+	Accessed, PrecompileFailure, PrecompileFn, PrecompileHandle, PrecompileOutput,
+	PrecompileResult, PrecompileSet, StackExecutor, StackExitKind, StackState,
+	StackSubstateMetadata, StateAccess, StatefulPrecompileHandle, TransactionOutcome,
 };
 
-pub use self::memory::{MemoryStackAccount, MemoryStackState, MemoryStackSubstate};
+pub use self::memory::{MemoryStackAccount, MemoryStackState, MemoryStackSubstate, StateGrowth};
 
 pub use ethereum::Log;
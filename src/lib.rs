@@ -26,5 +26,13 @@ macro_rules! event {
 	($x:expr) => {};
 }
 
+pub mod abi;
 pub mod backend;
+pub mod chain_spec;
+pub mod cheatcodes;
 pub mod executor;
+#[cfg(feature = "tracing")]
+pub mod profiler;
+pub mod revert;
+pub mod source_map;
+pub mod testing;
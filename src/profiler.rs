@@ -0,0 +1,431 @@
+//! Gas profiler built on the tracing events.
+
+use crate::gasometer::tracing as gasometer_tracing;
+use crate::tracing as executor_tracing;
+use crate::Opcode;
+use alloc::{collections::BTreeMap, rc::Rc, string::String, vec::Vec};
+use core::cell::RefCell;
+use core::fmt::Write;
+use evm_runtime::tracing as runtime_tracing;
+use primitive_types::H160;
+
+/// Gas used and number of executed opcodes.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct GasStats {
+	/// Gas used.
+	pub gas: u64,
+	/// Number of executed opcodes.
+	pub count: u64,
+}
+
+/// Aggregated gas usage of profiled transactions.
+///
+/// Gas of call frames is only counted in the frame that used it, so the gas
+/// of a `CALL` or `CREATE` opcode does not include the gas of the sub-call,
+/// and the call stipend given to the callee is deducted from it. Gas burnt
+/// by a failing frame is counted in its last opcode.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct GasProfile {
+	/// Intrinsic gas of the transactions.
+	pub intrinsic_gas: u64,
+	/// Total gas used, including intrinsic gas and before refunds.
+	pub total_gas: u64,
+	/// Usage by opcode.
+	pub by_opcode: BTreeMap<Opcode, GasStats>,
+	/// Usage by code address.
+	pub by_address: BTreeMap<H160, GasStats>,
+	/// Usage by code address and program counter.
+	pub by_position: BTreeMap<(H160, usize), GasStats>,
+	/// Usage by call path, as the code addresses of the call frames from the
+	/// outermost one.
+	pub by_call_path: BTreeMap<Vec<H160>, GasStats>,
+}
+
+struct Frame {
+	path: Vec<H160>,
+	step: Option<(Opcode, usize)>,
+	/// Gas limit of the frame, once its substate is entered.
+	gas_limit: Option<u64>,
+	returned: u64,
+	gas: u64,
+}
+
+#[derive(Default)]
+struct State {
+	frames: Vec<Frame>,
+	profile: GasProfile,
+}
+
+/// Gas profiler, listening to the executor, runtime and gasometer events of
+/// everything executed within `GasProfiler::profile`.
+///
+/// When `Config::precharge_block_gas` is enabled, the static gas of a run of
+/// opcodes is counted in its first opcode.
+#[derive(Clone, Default)]
+pub struct GasProfiler {
+	state: Rc<RefCell<State>>,
+}
+
+impl GasProfiler {
+	/// Create a new profiler.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Run `f`, profiling all transactions it executes.
+	pub fn profile<R, F: FnOnce() -> R>(&self, f: F) -> R {
+		let mut executor = Listener(self.state.clone());
+		let mut runtime = Listener(self.state.clone());
+		let mut gasometer = Listener(self.state.clone());
+
+		executor_tracing::using(&mut executor, || {
+			runtime_tracing::using(&mut runtime, || gasometer_tracing::using(&mut gasometer, f))
+		})
+	}
+
+	/// Gas usage aggregated so far.
+	pub fn report(&self) -> GasProfile {
+		self.state.borrow().profile.clone()
+	}
+}
+
+struct Listener(Rc<RefCell<State>>);
+
+impl executor_tracing::EventListener for Listener {
+	fn event(&mut self, event: executor_tracing::Event) {
+		use executor_tracing::Event;
+
+		let mut state = self.0.borrow_mut();
+		match event {
+			Event::Call { code_address, .. } => state.enter(code_address),
+			Event::Create { address, .. } => state.enter(address),
+			Event::Enter { gas_limit, .. } => {
+				if let Some(frame) = state.frames.last_mut() {
+					frame.gas_limit = Some(gas_limit);
+				}
+			}
+			Event::Exit { .. } => state.exit(),
+			_ => (),
+		}
+	}
+}
+
+impl runtime_tracing::EventListener for Listener {
+	fn event(&mut self, event: runtime_tracing::Event) {
+		if let runtime_tracing::Event::Step {
+			context,
+			opcode,
+			position: Ok(position),
+			..
+		} = event
+		{
+			self.0.borrow_mut().step(context.address, opcode, *position);
+		}
+	}
+}
+
+impl gasometer_tracing::EventListener for Listener {
+	fn event(&mut self, event: gasometer_tracing::Event) {
+		use gasometer_tracing::Event;
+
+		let mut state = self.0.borrow_mut();
+		match event {
+			Event::RecordCost { cost, .. } => state.charge(cost as i128),
+			Event::RecordDynamicCost {
+				gas_cost,
+				memory_gas,
+				snapshot,
+				..
+			} => {
+				let memory_cost =
+					memory_gas.saturating_sub(snapshot.map(|s| s.memory_gas).unwrap_or(0));
+				state.charge(gas_cost.saturating_add(memory_cost) as i128);
+			}
+			Event::RecordStipend { stipend, .. } => {
+				// Gas left by a frame is returned to its parent as it exits.
+				if let Some(frame) = state.frames.last_mut() {
+					frame.returned = frame.returned.saturating_add(stipend);
+				}
+			}
+			Event::RecordTransaction { cost, .. } => {
+				state.profile.intrinsic_gas = state.profile.intrinsic_gas.saturating_add(cost);
+				state.profile.total_gas = state.profile.total_gas.saturating_add(cost);
+			}
+			Event::RecordRefund { .. } => (),
+		}
+	}
+}
+
+impl State {
+	/// Push the frame of a call or create. Until its substate is entered,
+	/// gas is still recorded by the parent.
+	fn enter(&mut self, address: H160) {
+		let mut path = self
+			.frames
+			.last()
+			.map(|frame| frame.path.clone())
+			.unwrap_or_default();
+		path.push(address);
+
+		self.frames.push(Frame {
+			path,
+			step: None,
+			gas_limit: None,
+			returned: 0,
+			gas: 0,
+		});
+	}
+
+	fn exit(&mut self) {
+		let (used, gas_limit) = match self.frames.last() {
+			// The gas a frame used is its gas limit, minus what was returned.
+			// The difference with what its opcodes have recorded is gas burnt
+			// on failure.
+			Some(&Frame {
+				gas_limit: Some(gas_limit),
+				returned,
+				gas,
+				..
+			}) => {
+				let used = gas_limit.saturating_sub(returned);
+				self.charge(used as i128 - gas as i128);
+				(used, gas_limit)
+			}
+			// Exiting before the substate was entered, without using gas.
+			Some(_) => {
+				self.frames.pop();
+				return;
+			}
+			None => return,
+		};
+
+		self.frames.pop();
+		if self.current().is_none() {
+			self.profile.total_gas = self.profile.total_gas.saturating_add(used);
+			return;
+		}
+
+		// The parent recorded the gas forwarded to the frame, and the frame
+		// the gas it used out of its limit. Replace the former with the
+		// latter, which leaves any call stipend in the cost of the parent
+		// opcode.
+		self.charge(-(gas_limit as i128));
+		if let Some(parent) = self.current() {
+			let parent = &mut self.frames[parent];
+			parent.gas = parent.gas.saturating_add(used);
+		}
+	}
+
+	fn step(&mut self, address: H160, opcode: Opcode, position: usize) {
+		if self.frames.is_empty() {
+			// Profiling a runtime outside of an executor, without gas limit.
+			self.enter(address);
+		}
+
+		if let Some(frame) = self.frames.last_mut() {
+			frame.step = Some((opcode, position));
+		}
+		self.record(0, 1);
+	}
+
+	/// Index of the frame recording gas: the innermost one, unless it has
+	/// not entered its substate yet.
+	fn current(&self) -> Option<usize> {
+		let last = self.frames.len().checked_sub(1)?;
+		let frame = &self.frames[last];
+		if frame.gas_limit.is_some() || frame.step.is_some() {
+			Some(last)
+		} else {
+			last.checked_sub(1)
+		}
+	}
+
+	fn charge(&mut self, gas: i128) {
+		if let Some(current) = self.current() {
+			let frame = &mut self.frames[current];
+			frame.gas = apply(frame.gas, gas);
+		}
+		self.record(gas, 0);
+	}
+
+	fn record(&mut self, gas: i128, count: u64) {
+		let frame = match self.current() {
+			Some(current) => &self.frames[current],
+			None => return,
+		};
+		let address = frame.path[frame.path.len() - 1];
+
+		let add = |stats: &mut GasStats| {
+			stats.gas = apply(stats.gas, gas);
+			stats.count += count;
+		};
+
+		add(self.profile.by_address.entry(address).or_default());
+		add(self
+			.profile
+			.by_call_path
+			.entry(frame.path.clone())
+			.or_default());
+		if let Some((opcode, position)) = frame.step {
+			add(self.profile.by_opcode.entry(opcode).or_default());
+			add(self
+				.profile
+				.by_position
+				.entry((address, position))
+				.or_default());
+		}
+	}
+}
+
+fn apply(value: u64, delta: i128) -> u64 {
+	if delta < 0 {
+		value.saturating_sub((-delta) as u64)
+	} else {
+		value.saturating_add(delta as u64)
+	}
+}
+
+fn opcode_name(opcode: Opcode) -> String {
+	match opcode.name() {
+		Some(name) => String::from(name),
+		None => alloc::format!("{:#04x}", opcode.0),
+	}
+}
+
+impl GasProfile {
+	/// Gas used by the frames of a call path and all their sub-calls.
+	pub fn inclusive_gas(&self, path: &[H160]) -> u64 {
+		self.by_call_path
+			.range(path.to_vec()..)
+			.take_while(|(other, _)| other.starts_with(path))
+			.fold(0, |gas, (_, stats)| gas.saturating_add(stats.gas))
+	}
+
+	/// Export the gas used by each call path in the folded stack format used
+	/// by flamegraph tools, one line per path.
+	pub fn to_folded(&self) -> String {
+		let mut out = String::new();
+		for (path, stats) in &self.by_call_path {
+			if stats.gas == 0 {
+				continue;
+			}
+
+			let frames: Vec<String> = path.iter().map(|a| alloc::format!("{:?}", a)).collect();
+			let _ = writeln!(out, "{} {}", frames.join(";"), stats.gas);
+		}
+		out
+	}
+
+	/// Export the profile as JSON.
+	pub fn to_json(&self) -> String {
+		let mut out = String::new();
+		let _ = write!(
+			out,
+			"{{\"intrinsic_gas\":{},\"total_gas\":{},\"by_opcode\":[",
+			self.intrinsic_gas, self.total_gas
+		);
+		write_entries(&mut out, &self.by_opcode, |out, opcode| {
+			out.push_str("\"opcode\":");
+			write_string(out, &opcode_name(*opcode));
+		});
+		out.push_str("],\"by_address\":[");
+		write_entries(&mut out, &self.by_address, |out, address| {
+			out.push_str("\"address\":");
+			write_string(out, &alloc::format!("{:?}", address));
+		});
+		out.push_str("],\"by_position\":[");
+		write_entries(&mut out, &self.by_position, |out, (address, position)| {
+			out.push_str("\"address\":");
+			write_string(out, &alloc::format!("{:?}", address));
+			let _ = write!(out, ",\"pc\":{}", position);
+		});
+		out.push_str("],\"by_call_path\":[");
+		write_entries(&mut out, &self.by_call_path, |out, path| {
+			out.push_str("\"path\":[");
+			for (i, address) in path.iter().enumerate() {
+				if i > 0 {
+					out.push(',');
+				}
+				write_string(out, &alloc::format!("{:?}", address));
+			}
+			out.push(']');
+		});
+		out.push_str("]}");
+		out
+	}
+}
+
+fn write_entries<K, F: Fn(&mut String, &K)>(
+	out: &mut String,
+	entries: &BTreeMap<K, GasStats>,
+	write_key: F,
+) {
+	for (i, (key, stats)) in entries.iter().enumerate() {
+		if i > 0 {
+			out.push(',');
+		}
+		out.push('{');
+		write_key(out, key);
+		let _ = write!(out, ",\"gas\":{},\"count\":{}}}", stats.gas, stats.count);
+	}
+}
+
+/// Write a JSON string, escaping quotes, backslashes and control characters.
+fn write_string(out: &mut String, value: &str) {
+	out.push('"');
+	for c in value.chars() {
+		match c {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			'\n' => out.push_str("\\n"),
+			'\r' => out.push_str("\\r"),
+			'\t' => out.push_str("\\t"),
+			c if (c as u32) < 0x20 => {
+				let _ = write!(out, "\\u{:04x}", c as u32);
+			}
+			c => out.push(c),
+		}
+	}
+	out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn json_strings_are_escaped() {
+		let mut out = String::new();
+		write_string(&mut out, "a\"b\\c\nd\u{1}");
+		assert_eq!(out, r#""a\"b\\c\nd\u0001""#);
+	}
+
+	#[test]
+	fn json_export() {
+		let mut profile = GasProfile {
+			intrinsic_gas: 21_000,
+			total_gas: 21_003,
+			..Default::default()
+		};
+		let stats = GasStats { gas: 3, count: 1 };
+		profile.by_opcode.insert(Opcode::ADD, stats);
+		profile.by_opcode.insert(Opcode(0x0c), stats);
+		profile.by_address.insert(H160::zero(), stats);
+		profile.by_position.insert((H160::zero(), 2), stats);
+		profile.by_call_path.insert(vec![H160::zero()], stats);
+
+		let zero = "\"0x0000000000000000000000000000000000000000\"";
+		assert_eq!(
+			profile.to_json(),
+			alloc::format!(
+				"{{\"intrinsic_gas\":21000,\"total_gas\":21003,\
+				\"by_opcode\":[{{\"opcode\":\"ADD\",\"gas\":3,\"count\":1}},\
+				{{\"opcode\":\"0x0c\",\"gas\":3,\"count\":1}}],\
+				\"by_address\":[{{\"address\":{0},\"gas\":3,\"count\":1}}],\
+				\"by_position\":[{{\"address\":{0},\"pc\":2,\"gas\":3,\"count\":1}}],\
+				\"by_call_path\":[{{\"path\":[{0}],\"gas\":3,\"count\":1}}]}}",
+				zero
+			)
+		);
+	}
+}
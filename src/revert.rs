@@ -0,0 +1,486 @@
+//! Decoding of revert reasons.
+
+use crate::abi::{self, AbiType, AbiValue};
+use crate::ExitReason;
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::fmt;
+use primitive_types::U256;
+use sha3::{Digest, Keccak256};
+
+/// Selector of `Error(string)`.
+pub const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// Selector of `Panic(uint256)`.
+pub const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Panic code of `Panic(uint256)`, as raised by Solidity.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PanicCode {
+	/// Generic compiler inserted panic.
+	Generic,
+	/// Failed assertion.
+	Assert,
+	/// Arithmetic overflow or underflow.
+	ArithmeticOverflow,
+	/// Division or modulo by zero.
+	DivisionByZero,
+	/// Conversion of a value too big or negative into an enum.
+	InvalidEnumValue,
+	/// Access to an incorrectly encoded storage byte array.
+	InvalidStorageByteArray,
+	/// `pop()` on an empty array.
+	EmptyArrayPop,
+	/// Array, `bytesN` or slice index out of bounds.
+	IndexOutOfBounds,
+	/// Too much memory allocated, or array too large.
+	OutOfMemory,
+	/// Call to a zero-initialized internal function.
+	ZeroInitializedFunction,
+	/// Unknown panic code.
+	Other(U256),
+}
+
+impl PanicCode {
+	/// Panic code from its numeric value.
+	pub fn from_code(code: U256) -> Self {
+		if code > U256::from(u8::MAX) {
+			return Self::Other(code);
+		}
+
+		match code.low_u32() {
+			0x00 => Self::Generic,
+			0x01 => Self::Assert,
+			0x11 => Self::ArithmeticOverflow,
+			0x12 => Self::DivisionByZero,
+			0x21 => Self::InvalidEnumValue,
+			0x22 => Self::InvalidStorageByteArray,
+			0x31 => Self::EmptyArrayPop,
+			0x32 => Self::IndexOutOfBounds,
+			0x41 => Self::OutOfMemory,
+			0x51 => Self::ZeroInitializedFunction,
+			_ => Self::Other(code),
+		}
+	}
+
+	/// Numeric value of the panic code.
+	pub fn code(&self) -> U256 {
+		U256::from(match self {
+			Self::Generic => 0x00,
+			Self::Assert => 0x01,
+			Self::ArithmeticOverflow => 0x11,
+			Self::DivisionByZero => 0x12,
+			Self::InvalidEnumValue => 0x21,
+			Self::InvalidStorageByteArray => 0x22,
+			Self::EmptyArrayPop => 0x31,
+			Self::IndexOutOfBounds => 0x32,
+			Self::OutOfMemory => 0x41,
+			Self::ZeroInitializedFunction => 0x51,
+			Self::Other(code) => return *code,
+		})
+	}
+
+	/// Human readable meaning of the panic code.
+	pub fn description(&self) -> &'static str {
+		match self {
+			Self::Generic => "generic compiler panic",
+			Self::Assert => "assertion failed",
+			Self::ArithmeticOverflow => "arithmetic operation overflowed",
+			Self::DivisionByZero => "division or modulo by zero",
+			Self::InvalidEnumValue => "invalid enum value",
+			Self::InvalidStorageByteArray => "incorrectly encoded storage byte array",
+			Self::EmptyArrayPop => "pop on empty array",
+			Self::IndexOutOfBounds => "array index out of bounds",
+			Self::OutOfMemory => "too much memory allocated",
+			Self::ZeroInitializedFunction => "call to zero-initialized internal function",
+			Self::Other(_) => "unknown panic code",
+		}
+	}
+}
+
+/// Decoded revert reason.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RevertReason {
+	/// Reverted without return data.
+	Empty,
+	/// `Error(string)`, as raised by `require` and `revert`.
+	Error(String),
+	/// `Panic(uint256)`, as raised by Solidity on internal errors.
+	Panic(PanicCode),
+	/// Known custom error, with its ABI encoded arguments.
+	Custom {
+		/// Name of the error.
+		name: String,
+		/// Canonical signature of the error.
+		signature: String,
+		/// ABI encoded arguments.
+		data: Vec<u8>,
+	},
+	/// Return data not matching any known error.
+	Unknown(Vec<u8>),
+}
+
+impl RevertReason {
+	/// Decode `Error(string)` and `Panic(uint256)` revert data.
+	pub fn decode(output: &[u8]) -> Self {
+		RevertDecoder::new().decode(output)
+	}
+
+	/// Decode the revert data of an exit, if it is a revert.
+	pub fn from_exit(reason: &ExitReason, output: &[u8]) -> Option<Self> {
+		RevertDecoder::new().decode_exit(reason, output)
+	}
+}
+
+impl fmt::Display for RevertReason {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Empty => write!(f, "reverted without a reason"),
+			Self::Error(message) => write!(f, "reverted with reason string '{}'", message),
+			Self::Panic(code) => write!(
+				f,
+				"reverted with panic code {:#x} ({})",
+				code.code(),
+				code.description()
+			),
+			Self::Custom { name, data, .. } => {
+				write!(f, "reverted with custom error {}(0x", name)?;
+				write_hex(f, data)?;
+				write!(f, ")")
+			}
+			Self::Unknown(data) => {
+				write!(f, "reverted with unrecognized return data 0x")?;
+				write_hex(f, data)
+			}
+		}
+	}
+}
+
+fn write_hex(f: &mut fmt::Formatter<'_>, data: &[u8]) -> fmt::Result {
+	for byte in data {
+		write!(f, "{:02x}", byte)?;
+	}
+	Ok(())
+}
+
+/// The ABI given to `RevertDecoder::add_abi` is invalid.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct InvalidAbi;
+
+/// Revert decoder, recognizing `Error(string)`, `Panic(uint256)` and a set of
+/// custom errors.
+#[derive(Clone, Debug, Default)]
+pub struct RevertDecoder {
+	errors: BTreeMap<[u8; 4], (String, String)>,
+}
+
+impl RevertDecoder {
+	/// Create a decoder without custom errors.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Add a custom error by its canonical signature, such as
+	/// `InsufficientBalance(uint256,uint256)`.
+	pub fn add_error(&mut self, signature: &str) {
+		let name = signature.split('(').next().unwrap_or(signature);
+		self.errors.insert(
+			selector(signature),
+			(String::from(name), String::from(signature)),
+		);
+	}
+
+	/// Add all custom errors of a JSON ABI.
+	#[cfg(feature = "with-serde")]
+	pub fn add_abi(&mut self, abi: &str) -> Result<(), InvalidAbi> {
+		let abi: serde_json::Value = serde_json::from_str(abi).map_err(|_| InvalidAbi)?;
+
+		for item in abi.as_array().ok_or(InvalidAbi)? {
+			if item.get("type").and_then(|t| t.as_str()) != Some("error") {
+				continue;
+			}
+
+			let name = item
+				.get("name")
+				.and_then(|name| name.as_str())
+				.ok_or(InvalidAbi)?;
+			let inputs = item.get("inputs").ok_or(InvalidAbi)?;
+			let signature = alloc::format!("{}({})", name, canonical_types(inputs)?);
+			self.add_error(&signature);
+		}
+
+		Ok(())
+	}
+
+	/// Decode revert data.
+	pub fn decode(&self, output: &[u8]) -> RevertReason {
+		if output.is_empty() {
+			return RevertReason::Empty;
+		}
+		if output.len() < 4 {
+			return RevertReason::Unknown(output.to_vec());
+		}
+
+		let (selector, data) = output.split_at(4);
+		if selector == ERROR_SELECTOR {
+			if let Some(message) = decode_string(data) {
+				return RevertReason::Error(message);
+			}
+		} else if selector == PANIC_SELECTOR {
+			if data.len() == 32 {
+				return RevertReason::Panic(PanicCode::from_code(U256::from_big_endian(data)));
+			}
+		} else if let Some((name, signature)) = self.errors.get(selector) {
+			return RevertReason::Custom {
+				name: name.clone(),
+				signature: signature.clone(),
+				data: data.to_vec(),
+			};
+		}
+
+		RevertReason::Unknown(output.to_vec())
+	}
+
+	/// Decode the revert data of an exit, if it is a revert.
+	pub fn decode_exit(&self, reason: &ExitReason, output: &[u8]) -> Option<RevertReason> {
+		match reason {
+			ExitReason::Revert(_) => Some(self.decode(output)),
+			_ => None,
+		}
+	}
+}
+
+fn selector(signature: &str) -> [u8; 4] {
+	let hash = Keccak256::digest(signature.as_bytes());
+	[hash[0], hash[1], hash[2], hash[3]]
+}
+
+fn decode_string(data: &[u8]) -> Option<String> {
+	match abi::decode(&[AbiType::String], data).ok()?.pop()? {
+		AbiValue::String(message) => Some(message),
+		_ => None,
+	}
+}
+
+#[cfg(feature = "with-serde")]
+fn canonical_types(params: &serde_json::Value) -> Result<String, InvalidAbi> {
+	let mut types = Vec::new();
+	for param in params.as_array().ok_or(InvalidAbi)? {
+		let ty = param
+			.get("type")
+			.and_then(|ty| ty.as_str())
+			.ok_or(InvalidAbi)?;
+
+		// Tuples are written as `tuple`, `tuple[]` or `tuple[N]`, with their
+		// types in `components`.
+		match ty.strip_prefix("tuple") {
+			Some(suffix) => {
+				let components = param.get("components").ok_or(InvalidAbi)?;
+				types.push(alloc::format!(
+					"({}){}",
+					canonical_types(components)?,
+					suffix
+				));
+			}
+			None => types.push(String::from(ty)),
+		}
+	}
+
+	Ok(types.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{ExitError, ExitRevert};
+
+	fn error(message: &str) -> Vec<u8> {
+		let mut output = ERROR_SELECTOR.to_vec();
+		output.extend_from_slice(&abi::encode(&[AbiValue::String(String::from(message))]));
+		output
+	}
+
+	fn panic(code: U256) -> Vec<u8> {
+		let mut output = PANIC_SELECTOR.to_vec();
+		output.extend_from_slice(&abi::encode(&[AbiValue::Uint(code, 256)]));
+		output
+	}
+
+	#[test]
+	fn selectors() {
+		assert_eq!(selector("Error(string)"), ERROR_SELECTOR);
+		assert_eq!(selector("Panic(uint256)"), PANIC_SELECTOR);
+	}
+
+	#[test]
+	fn error_string() {
+		let reason = RevertReason::decode(&error("insufficient balance"));
+		assert_eq!(
+			reason,
+			RevertReason::Error(String::from("insufficient balance"))
+		);
+		assert_eq!(
+			reason.to_string(),
+			"reverted with reason string 'insufficient balance'"
+		);
+		assert_eq!(
+			RevertReason::decode(&error("")),
+			RevertReason::Error(String::new())
+		);
+	}
+
+	#[test]
+	fn panic_codes() {
+		let codes = [
+			(0x00, PanicCode::Generic),
+			(0x01, PanicCode::Assert),
+			(0x11, PanicCode::ArithmeticOverflow),
+			(0x12, PanicCode::DivisionByZero),
+			(0x21, PanicCode::InvalidEnumValue),
+			(0x22, PanicCode::InvalidStorageByteArray),
+			(0x31, PanicCode::EmptyArrayPop),
+			(0x32, PanicCode::IndexOutOfBounds),
+			(0x41, PanicCode::OutOfMemory),
+			(0x51, PanicCode::ZeroInitializedFunction),
+		];
+		for (code, expected) in codes {
+			let code = U256::from(code);
+			assert_eq!(PanicCode::from_code(code), expected);
+			assert_eq!(expected.code(), code);
+			assert_ne!(expected.description(), PanicCode::Other(code).description());
+			assert_eq!(
+				RevertReason::decode(&panic(code)),
+				RevertReason::Panic(expected)
+			);
+		}
+
+		for code in [U256::from(0x02), U256::from(0x100), U256::MAX] {
+			assert_eq!(PanicCode::from_code(code), PanicCode::Other(code));
+			assert_eq!(PanicCode::Other(code).code(), code);
+		}
+
+		assert_eq!(
+			RevertReason::decode(&panic(U256::from(0x11))).to_string(),
+			"reverted with panic code 0x11 (arithmetic operation overflowed)"
+		);
+	}
+
+	#[test]
+	fn custom_errors() {
+		let mut decoder = RevertDecoder::new();
+		decoder.add_error("InsufficientBalance(uint256,uint256)");
+
+		let data = abi::encode(&[
+			AbiValue::Uint(U256::from(1), 256),
+			AbiValue::Uint(U256::from(2), 256),
+		]);
+		let mut output = selector("InsufficientBalance(uint256,uint256)").to_vec();
+		output.extend_from_slice(&data);
+
+		let reason = decoder.decode(&output);
+		assert_eq!(
+			reason,
+			RevertReason::Custom {
+				name: String::from("InsufficientBalance"),
+				signature: String::from("InsufficientBalance(uint256,uint256)"),
+				data: data.clone(),
+			}
+		);
+		assert!(reason
+			.to_string()
+			.starts_with("reverted with custom error InsufficientBalance(0x0000"));
+
+		// Unknown to the default decoder.
+		assert_eq!(RevertReason::decode(&output), RevertReason::Unknown(output));
+	}
+
+	#[cfg(feature = "with-serde")]
+	#[test]
+	fn custom_errors_from_abi() {
+		let abi = r#"[
+			{"type": "function", "name": "f", "inputs": [], "outputs": []},
+			{"type": "error", "name": "Unauthorized", "inputs": []},
+			{"type": "error", "name": "Bad", "inputs": [
+				{"name": "items", "type": "tuple[]", "components": [
+					{"name": "a", "type": "uint256"},
+					{"name": "b", "type": "address"}
+				]},
+				{"name": "flag", "type": "bool"}
+			]}
+		]"#;
+		let mut decoder = RevertDecoder::new();
+		decoder.add_abi(abi).unwrap();
+
+		let signatures: Vec<_> = decoder
+			.errors
+			.values()
+			.map(|(_, signature)| signature.as_str())
+			.collect();
+		assert_eq!(signatures.len(), 2);
+		assert!(signatures.contains(&"Unauthorized()"));
+		assert!(signatures.contains(&"Bad((uint256,address)[],bool)"));
+
+		assert_eq!(decoder.add_abi("{}"), Err(InvalidAbi));
+		assert_eq!(
+			decoder.add_abi(r#"[{"type": "error", "inputs": []}]"#),
+			Err(InvalidAbi)
+		);
+	}
+
+	#[test]
+	fn malformed_payloads() {
+		assert_eq!(RevertReason::decode(&[]), RevertReason::Empty);
+		assert_eq!(
+			RevertReason::decode(&[0x08, 0xc3, 0x79]),
+			RevertReason::Unknown(alloc::vec![0x08, 0xc3, 0x79])
+		);
+
+		let message = error("message");
+		let mut malformed = alloc::vec![
+			// Selector alone.
+			ERROR_SELECTOR.to_vec(),
+			// Truncated string.
+			message[..message.len() - 32].to_vec(),
+			// Panic code not a full word, or followed by extra data.
+			panic(U256::one())[..35].to_vec(),
+			[panic(U256::one()), alloc::vec![0]].concat(),
+		];
+
+		// Offset out of bounds.
+		let mut offset = message.clone();
+		offset[4 + 31] = 0xff;
+		malformed.push(offset);
+
+		// Length past the end of the data.
+		let mut length = message.clone();
+		length[4 + 63] = 0xff;
+		malformed.push(length);
+
+		// Invalid UTF-8.
+		let mut utf8 = message;
+		utf8[4 + 64] = 0xff;
+		malformed.push(utf8);
+
+		for output in malformed {
+			assert_eq!(
+				RevertReason::decode(&output),
+				RevertReason::Unknown(output.clone())
+			);
+		}
+	}
+
+	#[test]
+	fn decode_exit() {
+		let output = error("message");
+		assert_eq!(
+			RevertReason::from_exit(&ExitReason::Revert(ExitRevert::Reverted), &output),
+			Some(RevertReason::Error(String::from("message")))
+		);
+		assert_eq!(
+			RevertReason::from_exit(&ExitReason::Revert(ExitRevert::Reverted), &[]),
+			Some(RevertReason::Empty)
+		);
+		assert_eq!(
+			RevertReason::from_exit(&ExitReason::Error(ExitError::OutOfGas), &output),
+			None
+		);
+	}
+}
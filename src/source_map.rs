@@ -0,0 +1,770 @@
+//! Map program counters of deployed contracts back to their Solidity source,
+//! using the source maps emitted by solc.
+
+use crate::Opcode;
+#[cfg(feature = "tracing")]
+use crate::{
+	revert::{RevertDecoder, RevertReason},
+	tracing as executor_tracing, ExitReason,
+};
+#[cfg(feature = "tracing")]
+use alloc::rc::Rc;
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+#[cfg(feature = "tracing")]
+use core::cell::RefCell;
+use core::fmt;
+#[cfg(feature = "tracing")]
+use evm_runtime::tracing as runtime_tracing;
+use primitive_types::H160;
+
+/// Source map error.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SourceMapError {
+	/// The source map string is malformed.
+	InvalidSourceMap,
+	/// The bytecode is not valid hex.
+	InvalidBytecode,
+	/// The compiler output is not valid JSON, or misses a required field.
+	InvalidArtifact,
+	/// The requested contract is not part of the compiler output.
+	MissingContract,
+}
+
+/// Kind of jump of an instruction in a source map.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum JumpType {
+	/// Jump into a function.
+	In,
+	/// Return from a function.
+	Out,
+	/// Regular jump, or no jump.
+	Regular,
+}
+
+/// Source range of a single instruction.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SourceMapEntry {
+	/// Byte offset in the source file.
+	pub offset: usize,
+	/// Byte length in the source file.
+	pub length: usize,
+	/// Index of the source file, if the instruction maps to one.
+	pub file: Option<usize>,
+	/// Kind of jump.
+	pub jump: JumpType,
+}
+
+/// A decompressed solc source map, with one entry per instruction.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SourceMap {
+	entries: Vec<SourceMapEntry>,
+}
+
+impl SourceMap {
+	/// Parse a compressed source map, as `s:l:f:j;...` entries where empty
+	/// fields repeat the previous entry.
+	pub fn parse(source_map: &str) -> Result<Self, SourceMapError> {
+		let mut entries = Vec::new();
+		let mut current = SourceMapEntry {
+			offset: 0,
+			length: 0,
+			file: None,
+			jump: JumpType::Regular,
+		};
+
+		if source_map.is_empty() {
+			return Ok(Self { entries });
+		}
+
+		for entry in source_map.split(';') {
+			for (i, field) in entry.split(':').enumerate() {
+				if field.is_empty() {
+					continue;
+				}
+
+				match i {
+					0 => current.offset = parse_number(field)?,
+					1 => current.length = parse_number(field)?,
+					2 => {
+						current.file = match field {
+							"-1" => None,
+							field => Some(parse_number(field)?),
+						}
+					}
+					3 => {
+						current.jump = match field {
+							"i" => JumpType::In,
+							"o" => JumpType::Out,
+							"-" => JumpType::Regular,
+							_ => return Err(SourceMapError::InvalidSourceMap),
+						}
+					}
+					// Modifier depth is not needed.
+					_ => (),
+				}
+			}
+
+			entries.push(current);
+		}
+
+		Ok(Self { entries })
+	}
+
+	/// All entries, indexed by instruction.
+	pub fn entries(&self) -> &[SourceMapEntry] {
+		&self.entries
+	}
+}
+
+fn parse_number(field: &str) -> Result<usize, SourceMapError> {
+	field.parse().map_err(|_| SourceMapError::InvalidSourceMap)
+}
+
+/// Location in a source file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SourceLocation {
+	/// Path of the source file.
+	pub file: String,
+	/// Line, starting from 1.
+	pub line: usize,
+	/// Column, starting from 1.
+	pub column: usize,
+	/// Name of the enclosing function, if any.
+	pub function: Option<String>,
+}
+
+/// Source map of a deployed contract, together with its sources.
+#[derive(Clone, Debug)]
+pub struct ContractSourceMap {
+	name: String,
+	instructions: Vec<usize>,
+	source_map: SourceMap,
+	files: BTreeMap<usize, (String, String)>,
+}
+
+impl ContractSourceMap {
+	/// Create a new source map for the contract `name`, with its deployed
+	/// code, its runtime source map, and the path and content of each source
+	/// file by index.
+	pub fn new(
+		name: String,
+		code: &[u8],
+		source_map: SourceMap,
+		files: BTreeMap<usize, (String, String)>,
+	) -> Self {
+		let mut instructions = Vec::new();
+		let mut pc = 0;
+		while pc < code.len() {
+			instructions.push(pc);
+			pc += 1 + Opcode(code[pc]).is_push().unwrap_or(0) as usize;
+		}
+
+		Self {
+			name,
+			instructions,
+			source_map,
+			files,
+		}
+	}
+
+	/// Load the contract `contract` of the source file `file` from solc
+	/// standard JSON output. `sources` gives the content of the source files
+	/// by path, so that offsets can be mapped to lines.
+	#[cfg(feature = "with-serde")]
+	pub fn from_standard_json(
+		output: &str,
+		file: &str,
+		contract: &str,
+		sources: &BTreeMap<String, String>,
+	) -> Result<Self, SourceMapError> {
+		let output: serde_json::Value =
+			serde_json::from_str(output).map_err(|_| SourceMapError::InvalidArtifact)?;
+
+		let bytecode = output
+			.get("contracts")
+			.and_then(|contracts| contracts.get(file))
+			.and_then(|contracts| contracts.get(contract))
+			.ok_or(SourceMapError::MissingContract)?
+			.pointer("/evm/deployedBytecode")
+			.ok_or(SourceMapError::InvalidArtifact)?;
+		let object = bytecode
+			.get("object")
+			.and_then(|object| object.as_str())
+			.ok_or(SourceMapError::InvalidArtifact)?;
+		let source_map = bytecode
+			.get("sourceMap")
+			.and_then(|source_map| source_map.as_str())
+			.ok_or(SourceMapError::InvalidArtifact)?;
+
+		let mut files = BTreeMap::new();
+		if let Some(output_sources) = output.get("sources").and_then(|s| s.as_object()) {
+			for (path, source) in output_sources {
+				if let (Some(id), Some(content)) = (
+					source.get("id").and_then(|id| id.as_u64()),
+					sources.get(path),
+				) {
+					files.insert(id as usize, (path.clone(), content.clone()));
+				}
+			}
+		}
+
+		Ok(Self::new(
+			String::from(contract),
+			&decode_bytecode(object)?,
+			SourceMap::parse(source_map)?,
+			files,
+		))
+	}
+
+	/// Name of the contract.
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	/// Source location of the instruction at the given program counter.
+	pub fn location(&self, pc: usize) -> Option<SourceLocation> {
+		let index = self.instructions.binary_search(&pc).ok()?;
+		let entry = self.source_map.entries().get(index)?;
+		let (path, content) = self.files.get(&entry.file?)?;
+
+		let before = content.get(..entry.offset)?;
+		let line = before.matches('\n').count() + 1;
+		let column = before.len() - before.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+
+		Some(SourceLocation {
+			file: path.clone(),
+			line,
+			column,
+			function: enclosing_function(content, entry.offset),
+		})
+	}
+}
+
+/// Decode hex bytecode, with unlinked library placeholders set to zero.
+#[cfg(feature = "with-serde")]
+fn decode_bytecode(object: &str) -> Result<Vec<u8>, SourceMapError> {
+	let object = object.strip_prefix("0x").unwrap_or(object).as_bytes();
+	if object.len() % 2 != 0 {
+		return Err(SourceMapError::InvalidBytecode);
+	}
+
+	let nibble = |c: u8| match c {
+		b'0'..=b'9' => Some(c - b'0'),
+		b'a'..=b'f' => Some(c - b'a' + 10),
+		b'A'..=b'F' => Some(c - b'A' + 10),
+		_ => None,
+	};
+
+	let mut code = Vec::with_capacity(object.len() / 2);
+	let mut i = 0;
+	while i < object.len() {
+		if object[i] == b'_' {
+			// Library placeholders are 40 characters long, `__$...$__`.
+			if i + 40 > object.len() {
+				return Err(SourceMapError::InvalidBytecode);
+			}
+			code.extend_from_slice(&[0u8; 20]);
+			i += 40;
+			continue;
+		}
+
+		match (nibble(object[i]), nibble(object[i + 1])) {
+			(Some(high), Some(low)) => code.push(high << 4 | low),
+			_ => return Err(SourceMapError::InvalidBytecode),
+		}
+		i += 2;
+	}
+
+	Ok(code)
+}
+
+/// Find the innermost function, modifier, constructor, fallback or receive
+/// body containing `offset`, by scanning the source text outside of comments
+/// and string literals.
+fn enclosing_function(content: &str, offset: usize) -> Option<String> {
+	const KEYWORDS: [&str; 5] = ["function", "modifier", "constructor", "fallback", "receive"];
+
+	let bytes = content.as_bytes();
+	let is_code = code_mask(bytes);
+	let is_ident = |c: u8| c.is_ascii_alphanumeric() || c == b'_' || c == b'$';
+	let mut best: Option<(usize, String)> = None;
+
+	for keyword in KEYWORDS.iter() {
+		for (start, _) in content.match_indices(keyword) {
+			let end = start + keyword.len();
+			if start > offset
+				|| !is_code[start]
+				|| (start > 0 && is_ident(bytes[start - 1]))
+				|| bytes.get(end).map(|c| is_ident(*c)).unwrap_or(false)
+			{
+				continue;
+			}
+
+			let name = if *keyword == "function" || *keyword == "modifier" {
+				let rest = content[end..].trim_start();
+				let len = rest.bytes().take_while(|c| is_ident(*c)).count();
+				String::from(&rest[..len])
+			} else {
+				String::from(*keyword)
+			};
+
+			let open = match (end..bytes.len())
+				.find(|i| is_code[*i] && (bytes[*i] == b'{' || bytes[*i] == b';'))
+			{
+				Some(i) if bytes[i] == b'{' => i,
+				_ => continue,
+			};
+
+			let mut depth = 0;
+			let mut close = None;
+			for (i, c) in bytes.iter().enumerate().skip(open) {
+				if !is_code[i] {
+					continue;
+				}
+				match c {
+					b'{' => depth += 1,
+					b'}' => {
+						depth -= 1;
+						if depth == 0 {
+							close = Some(i);
+							break;
+						}
+					}
+					_ => (),
+				}
+			}
+
+			let inside = match close {
+				Some(close) => offset <= close,
+				None => true,
+			};
+			if inside && best.as_ref().map(|(s, _)| start > *s).unwrap_or(true) {
+				best = Some((start, name));
+			}
+		}
+	}
+
+	best.map(|(_, name)| name)
+}
+
+/// Whether each byte of Solidity source is code, rather than part of a
+/// comment or a string literal.
+fn code_mask(bytes: &[u8]) -> Vec<bool> {
+	let mut is_code = alloc::vec![true; bytes.len()];
+	let mut i = 0;
+	while i < bytes.len() {
+		let end = match (bytes[i], bytes.get(i + 1)) {
+			(b'/', Some(b'/')) => bytes[i..]
+				.iter()
+				.position(|c| *c == b'\n')
+				.map(|n| i + n)
+				.unwrap_or(bytes.len()),
+			(b'/', Some(b'*')) => bytes[i + 2..]
+				.windows(2)
+				.position(|w| w == b"*/")
+				.map(|n| i + 2 + n + 2)
+				.unwrap_or(bytes.len()),
+			(quote @ b'"', _) | (quote @ b'\'', _) => {
+				let mut j = i + 1;
+				while j < bytes.len() && bytes[j] != quote && bytes[j] != b'\n' {
+					j += if bytes[j] == b'\\' { 2 } else { 1 };
+				}
+				(j + 1).min(bytes.len())
+			}
+			_ => {
+				i += 1;
+				continue;
+			}
+		};
+
+		for c in &mut is_code[i..end] {
+			*c = false;
+		}
+		i = end;
+	}
+	is_code
+}
+
+/// Source maps of contracts, by address.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMaps {
+	contracts: BTreeMap<H160, ContractSourceMap>,
+}
+
+impl SourceMaps {
+	/// Create an empty set of source maps.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Set the source map of the contract deployed at `address`.
+	pub fn insert(&mut self, address: H160, contract: ContractSourceMap) {
+		self.contracts.insert(address, contract);
+	}
+
+	/// Source map of the contract deployed at `address`.
+	pub fn get(&self, address: H160) -> Option<&ContractSourceMap> {
+		self.contracts.get(&address)
+	}
+
+	/// Resolve the source locations of a stack trace.
+	pub fn resolve(&self, frames: &[(H160, usize)]) -> Vec<StackFrame> {
+		frames
+			.iter()
+			.rev()
+			.map(|(address, pc)| {
+				let contract = self.get(*address);
+				StackFrame {
+					address: *address,
+					pc: *pc,
+					contract: contract.map(|c| String::from(c.name())),
+					location: contract.and_then(|c| c.location(*pc)),
+				}
+			})
+			.collect()
+	}
+}
+
+/// A call frame of a stack trace.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StackFrame {
+	/// Code address of the frame.
+	pub address: H160,
+	/// Program counter of the last executed instruction of the frame.
+	pub pc: usize,
+	/// Name of the contract, if its source map is known.
+	pub contract: Option<String>,
+	/// Source location of the program counter, if known.
+	pub location: Option<SourceLocation>,
+}
+
+impl fmt::Display for StackFrame {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match (&self.contract, &self.location) {
+			(Some(contract), Some(location)) => {
+				match &location.function {
+					Some(function) => write!(f, "at {}.{} ", contract, function)?,
+					None => write!(f, "at {} ", contract)?,
+				}
+				write!(
+					f,
+					"({}:{}:{})",
+					location.file, location.line, location.column
+				)
+			}
+			(Some(contract), None) => write!(f, "at {} (pc {:#x})", contract, self.pc),
+			(None, _) => write!(f, "at {:?} (pc {:#x})", self.address, self.pc),
+		}
+	}
+}
+
+/// Stack trace of a failed transaction, from the innermost frame.
+#[cfg(feature = "tracing")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StackTrace {
+	/// Exit reason of the innermost failing frame.
+	pub reason: ExitReason,
+	/// Return value of the innermost failing frame.
+	pub return_value: Vec<u8>,
+	/// Decoded return value, if the innermost failing frame reverted.
+	pub revert: Option<RevertReason>,
+	/// Call frames, from the innermost one.
+	pub frames: Vec<StackFrame>,
+}
+
+#[cfg(feature = "tracing")]
+impl fmt::Display for StackTrace {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match &self.reason {
+			ExitReason::Revert(_) => match &self.revert {
+				Some(revert) => writeln!(f, "Error: execution {}", revert)?,
+				None => writeln!(f, "Error: execution reverted")?,
+			},
+			ExitReason::Error(e) => writeln!(f, "Error: {:?}", e)?,
+			ExitReason::Fatal(e) => writeln!(f, "Error: fatal {:?}", e)?,
+			ExitReason::Succeed(s) => writeln!(f, "Error: {:?}", s)?,
+		}
+		for frame in &self.frames {
+			writeln!(f, "    {}", frame)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(feature = "tracing")]
+struct Failure {
+	frames: Vec<(H160, usize)>,
+	reason: ExitReason,
+	return_value: Vec<u8>,
+}
+
+#[cfg(feature = "tracing")]
+#[derive(Default)]
+struct TracerState {
+	frames: Vec<(H160, usize)>,
+	failure: Option<Failure>,
+	last: Option<Failure>,
+}
+
+/// Stack tracer, listening to the executor and runtime events of everything
+/// executed within `StackTracer::trace`, and keeping the stack trace of the
+/// last failed transaction.
+///
+/// When a frame fails with the same return value as a sub-call that failed
+/// before it, the failure is considered bubbled up, and the trace of the
+/// sub-call is kept.
+#[cfg(feature = "tracing")]
+#[derive(Clone)]
+pub struct StackTracer {
+	source_maps: Rc<SourceMaps>,
+	revert_decoder: Rc<RevertDecoder>,
+	state: Rc<RefCell<TracerState>>,
+}
+
+#[cfg(feature = "tracing")]
+impl StackTracer {
+	/// Create a new tracer, resolving locations with the given source maps.
+	pub fn new(source_maps: SourceMaps) -> Self {
+		Self {
+			source_maps: Rc::new(source_maps),
+			revert_decoder: Default::default(),
+			state: Default::default(),
+		}
+	}
+
+	/// Source maps used by the tracer.
+	pub fn source_maps(&self) -> &SourceMaps {
+		&self.source_maps
+	}
+
+	/// Set the decoder of revert reasons, to recognize custom errors.
+	pub fn set_revert_decoder(&mut self, revert_decoder: RevertDecoder) {
+		self.revert_decoder = Rc::new(revert_decoder);
+	}
+
+	/// Run `f`, tracing all transactions it executes.
+	pub fn trace<R, F: FnOnce() -> R>(&self, f: F) -> R {
+		let mut executor = TracerListener(self.state.clone());
+		let mut runtime = TracerListener(self.state.clone());
+
+		executor_tracing::using(&mut executor, || runtime_tracing::using(&mut runtime, f))
+	}
+
+	/// Stack trace of the last transaction, if it failed.
+	pub fn stack_trace(&self) -> Option<StackTrace> {
+		let state = self.state.borrow();
+		let failure = state.last.as_ref()?;
+
+		Some(StackTrace {
+			reason: failure.reason.clone(),
+			return_value: failure.return_value.clone(),
+			revert: self
+				.revert_decoder
+				.decode_exit(&failure.reason, &failure.return_value),
+			frames: self.source_maps.resolve(&failure.frames),
+		})
+	}
+}
+
+#[cfg(feature = "tracing")]
+struct TracerListener(Rc<RefCell<TracerState>>);
+
+#[cfg(feature = "tracing")]
+impl executor_tracing::EventListener for TracerListener {
+	fn event(&mut self, event: executor_tracing::Event) {
+		use executor_tracing::Event;
+
+		let mut state = self.0.borrow_mut();
+		match event {
+			Event::Call { code_address, .. } => state.frames.push((code_address, 0)),
+			Event::Create { address, .. } => state.frames.push((address, 0)),
+			Event::Exit {
+				reason,
+				return_value,
+				..
+			} => state.exit(reason, return_value),
+			_ => (),
+		}
+	}
+}
+
+#[cfg(feature = "tracing")]
+impl runtime_tracing::EventListener for TracerListener {
+	fn event(&mut self, event: runtime_tracing::Event) {
+		if let runtime_tracing::Event::Step {
+			context,
+			position: Ok(position),
+			..
+		} = event
+		{
+			let mut state = self.0.borrow_mut();
+			match state.frames.last_mut() {
+				Some(frame) => frame.1 = *position,
+				None => state.frames.push((context.address, *position)),
+			}
+		}
+	}
+}
+
+#[cfg(feature = "tracing")]
+impl TracerState {
+	fn exit(&mut self, reason: &ExitReason, return_value: &[u8]) {
+		if reason.is_succeed() {
+			self.failure = None;
+		} else {
+			let depth = self.frames.len();
+			let bubbled = match &self.failure {
+				Some(failure) => {
+					failure.frames.len() > depth && failure.return_value == return_value
+				}
+				None => false,
+			};
+
+			if !bubbled {
+				self.failure = Some(Failure {
+					frames: self.frames.clone(),
+					reason: reason.clone(),
+					return_value: return_value.to_vec(),
+				});
+			}
+		}
+
+		self.frames.pop();
+		if self.frames.is_empty() {
+			self.last = self.failure.take();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn entry(offset: usize, length: usize, file: Option<usize>, jump: JumpType) -> SourceMapEntry {
+		SourceMapEntry {
+			offset,
+			length,
+			file,
+			jump,
+		}
+	}
+
+	#[test]
+	fn parse_inherits_empty_fields() {
+		let source_map = SourceMap::parse("1:2:0:i;;3::-1;:4::o;5:6:1:-:2").unwrap();
+		assert_eq!(
+			source_map.entries(),
+			&[
+				entry(1, 2, Some(0), JumpType::In),
+				entry(1, 2, Some(0), JumpType::In),
+				entry(3, 2, None, JumpType::In),
+				entry(3, 4, None, JumpType::Out),
+				entry(5, 6, Some(1), JumpType::Regular),
+			]
+		);
+
+		assert_eq!(SourceMap::parse("").unwrap().entries(), &[]);
+	}
+
+	#[test]
+	fn parse_rejects_malformed_entries() {
+		for source_map in ["1:x", "1:2:0:z", "-2:1:0", "1:2:-2"] {
+			assert_eq!(
+				SourceMap::parse(source_map),
+				Err(SourceMapError::InvalidSourceMap),
+				"{}",
+				source_map
+			);
+		}
+	}
+
+	#[test]
+	fn location_skips_push_immediates() {
+		let content = "contract C {\n\tfunction f() public {\n\t\tx = 1;\n\t}\n}\n";
+		let statement = content.find("x = 1").unwrap();
+		let mut files = BTreeMap::new();
+		files.insert(0, (String::from("C.sol"), String::from(content)));
+
+		// PUSH2 0xaabb, PUSH1 0, ADD, STOP
+		let code = [0x61, 0xaa, 0xbb, 0x60, 0x00, 0x01, 0x00];
+		let source_map =
+			SourceMap::parse(&alloc::format!("0:10:0;{}:6;;::-1;", statement)).unwrap();
+		let contract = ContractSourceMap::new(String::from("C"), &code, source_map, files);
+
+		assert_eq!(
+			contract.location(0),
+			Some(SourceLocation {
+				file: String::from("C.sol"),
+				line: 1,
+				column: 1,
+				function: None,
+			})
+		);
+		let location = SourceLocation {
+			file: String::from("C.sol"),
+			line: 3,
+			column: 3,
+			function: Some(String::from("f")),
+		};
+		assert_eq!(contract.location(3), Some(location.clone()));
+		assert_eq!(contract.location(5), Some(location));
+
+		// Immediates are not instructions, and the last one maps to no file.
+		assert_eq!(contract.location(1), None);
+		assert_eq!(contract.location(4), None);
+		assert_eq!(contract.location(6), None);
+		assert_eq!(contract.location(7), None);
+	}
+
+	#[cfg(feature = "with-serde")]
+	#[test]
+	fn decode_bytecode_with_library_placeholders() {
+		let placeholder = alloc::format!("__${}$__", "0123456789abcdef0123456789abcdef01");
+		assert_eq!(placeholder.len(), 40);
+
+		let mut expected = alloc::vec![0x73];
+		expected.extend_from_slice(&[0; 20]);
+		expected.push(0xFF);
+		assert_eq!(
+			decode_bytecode(&alloc::format!("0x73{}Ff", placeholder)),
+			Ok(expected)
+		);
+		assert_eq!(decode_bytecode(""), Ok(Vec::new()));
+
+		for object in [
+			String::from("0x600"),
+			String::from("60zz"),
+			alloc::format!("73{}", &placeholder[..38]),
+		] {
+			assert_eq!(
+				decode_bytecode(&object),
+				Err(SourceMapError::InvalidBytecode),
+				"{}",
+				object
+			);
+		}
+	}
+
+	#[test]
+	fn enclosing_function_skips_comments_and_strings() {
+		let content = "contract C {\n\
+			\t// function commented() {\n\
+			\t/* function block() { */\n\
+			\tstring s = \"function quoted() {\";\n\
+			\tbytes1 b = '{';\n\
+			\tfunction real() public {\n\
+			\t\temit Log(\"}\", \"\\\"}\");\n\
+			\t\tx = 1;\n\
+			\t}\n\
+			\tfunction(uint) external g;\n\
+			\treceive() external payable { y = 2; }\n\
+			}\n";
+		let at = |text: &str| enclosing_function(content, content.find(text).unwrap());
+
+		assert_eq!(at("string s"), None);
+		assert_eq!(at("bytes1 b"), None);
+		assert_eq!(at("x = 1"), Some(String::from("real")));
+		assert_eq!(at("function(uint)"), None);
+		assert_eq!(at("y = 2"), Some(String::from("receive")));
+	}
+}
@@ -0,0 +1,333 @@
+//! Test harness for deploying and calling contracts on an in-memory chain.
+
+use crate::abi::{self, AbiError, AbiValue, Event, Function};
+use crate::backend::{ApplyBackend, Log, MemoryAccount, MemoryBackend, MemoryVicinity};
+use crate::executor::stack::{
+	MemoryStackState, PrecompileSet, StackExecutor, StackSubstateMetadata,
+};
+use crate::revert::RevertReason;
+use crate::{Config, ExitReason};
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::mem;
+use primitive_types::{H160, H256, U256};
+
+/// Result of a transaction executed on a `TestChain`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CallResult {
+	/// Exit reason.
+	pub reason: ExitReason,
+	/// Return data, or revert data.
+	pub output: Vec<u8>,
+	/// Gas used, including intrinsic gas and after refunds.
+	pub used_gas: u64,
+	/// Logs emitted by the transaction.
+	pub logs: Vec<Log>,
+	/// Address of the deployed contract, for successful deployments.
+	pub address: Option<H160>,
+}
+
+impl CallResult {
+	/// Whether the transaction succeeded.
+	pub fn is_success(&self) -> bool {
+		self.reason.is_succeed()
+	}
+
+	/// Decoded revert reason, if the transaction reverted.
+	pub fn revert_reason(&self) -> Option<RevertReason> {
+		RevertReason::from_exit(&self.reason, &self.output)
+	}
+
+	/// Decode the return data as the outputs of `function`.
+	pub fn decode(&self, function: &Function) -> Result<Vec<AbiValue>, AbiError> {
+		function.decode_output(&self.output)
+	}
+
+	/// Decode the logs of `event`, in emission order.
+	pub fn events(&self, event: &Event) -> Vec<Vec<AbiValue>> {
+		self.logs
+			.iter()
+			.filter_map(|log| event.decode_log(log).ok())
+			.collect()
+	}
+
+	/// Panic if the transaction did not succeed.
+	pub fn assert_success(&self) -> &Self {
+		if !self.is_success() {
+			match self.revert_reason() {
+				Some(revert) => panic!("expected success, transaction {}", revert),
+				None => panic!(
+					"expected success, transaction failed with {:?}",
+					self.reason
+				),
+			}
+		}
+		self
+	}
+
+	/// Panic if the transaction did not revert.
+	pub fn assert_revert(&self) -> &Self {
+		if self.revert_reason().is_none() {
+			panic!("expected revert, transaction exited with {:?}", self.reason);
+		}
+		self
+	}
+
+	/// Panic if the transaction did not revert with the given reason.
+	pub fn assert_revert_with(&self, expected: &RevertReason) -> &Self {
+		match self.revert_reason() {
+			Some(ref revert) if revert == expected => (),
+			Some(revert) => panic!("expected {}, transaction {}", expected, revert),
+			None => panic!(
+				"expected {}, transaction exited with {:?}",
+				expected, self.reason
+			),
+		}
+		self
+	}
+
+	/// Panic if no log of `event` was emitted with the given values.
+	pub fn assert_log(&self, event: &Event, values: &[AbiValue]) -> &Self {
+		if !self.events(event).iter().any(|logged| logged == values) {
+			panic!(
+				"expected log {} with {:?}, found {:?}",
+				event.signature(),
+				values,
+				self.events(event)
+			);
+		}
+		self
+	}
+}
+
+/// In-memory chain for contract tests, wrapping `MemoryBackend`,
+/// `MemoryVicinity` and `StackExecutor`.
+///
+/// The origin of each transaction is its sender, nonces are incremented by
+/// the executor, and the gas price is zero unless set in the vicinity.
+/// Precompiles are given unmetered state access, as needed by `Cheatcodes`.
+pub struct TestChain<P: PrecompileSet = ()> {
+	config: Config,
+	vicinity: MemoryVicinity,
+	state: BTreeMap<H160, MemoryAccount>,
+	precompiles: P,
+	gas_limit: u64,
+	snapshots: Vec<(MemoryVicinity, BTreeMap<H160, MemoryAccount>)>,
+}
+
+impl TestChain<()> {
+	/// Create an empty chain without precompiles.
+	pub fn new(config: Config) -> Self {
+		Self::with_precompiles(config, ())
+	}
+}
+
+impl<P: PrecompileSet> TestChain<P> {
+	/// Create an empty chain with the given precompiles.
+	pub fn with_precompiles(config: Config, precompiles: P) -> Self {
+		let gas_limit = 30_000_000;
+
+		Self {
+			config,
+			vicinity: MemoryVicinity {
+				gas_price: U256::zero(),
+				origin: H160::default(),
+				chain_id: U256::one(),
+				block_hashes: Vec::new(),
+				block_number: U256::zero(),
+				block_coinbase: H160::default(),
+				block_timestamp: U256::zero(),
+				block_difficulty: U256::zero(),
+				block_gas_limit: U256::from(gas_limit),
+				block_base_fee_per_gas: U256::zero(),
+			},
+			state: BTreeMap::new(),
+			precompiles,
+			gas_limit,
+			snapshots: Vec::new(),
+		}
+	}
+
+	/// Configuration of the chain.
+	pub fn config(&self) -> &Config {
+		&self.config
+	}
+
+	/// Block environment of the chain.
+	pub fn vicinity(&self) -> &MemoryVicinity {
+		&self.vicinity
+	}
+
+	/// Mutable block environment of the chain.
+	pub fn vicinity_mut(&mut self) -> &mut MemoryVicinity {
+		&mut self.vicinity
+	}
+
+	/// Accounts of the chain.
+	pub fn state(&self) -> &BTreeMap<H160, MemoryAccount> {
+		&self.state
+	}
+
+	/// Mutable accounts of the chain.
+	pub fn state_mut(&mut self) -> &mut BTreeMap<H160, MemoryAccount> {
+		&mut self.state
+	}
+
+	/// Precompiles of the chain.
+	pub fn precompiles(&self) -> &P {
+		&self.precompiles
+	}
+
+	/// Set the gas limit of the following transactions.
+	pub fn set_gas_limit(&mut self, gas_limit: u64) {
+		self.gas_limit = gas_limit;
+	}
+
+	/// Set the balance of an account.
+	pub fn fund(&mut self, address: H160, balance: U256) {
+		self.state.entry(address).or_default().balance = balance;
+	}
+
+	/// Balance of an account.
+	pub fn balance(&self, address: H160) -> U256 {
+		self.state
+			.get(&address)
+			.map(|account| account.balance)
+			.unwrap_or_default()
+	}
+
+	/// Nonce of an account.
+	pub fn nonce(&self, address: H160) -> U256 {
+		self.state
+			.get(&address)
+			.map(|account| account.nonce)
+			.unwrap_or_default()
+	}
+
+	/// Code of an account.
+	pub fn code(&self, address: H160) -> &[u8] {
+		self.state
+			.get(&address)
+			.map(|account| &account.code[..])
+			.unwrap_or_default()
+	}
+
+	/// Storage value of an account.
+	pub fn storage(&self, address: H160, index: H256) -> H256 {
+		self.state
+			.get(&address)
+			.and_then(|account| account.storage.get(&index).copied())
+			.unwrap_or_default()
+	}
+
+	/// Advance the chain by `blocks` blocks and `seconds` seconds.
+	pub fn advance_block(&mut self, blocks: u64, seconds: u64) {
+		self.vicinity.block_number = self.vicinity.block_number.saturating_add(blocks.into());
+		self.vicinity.block_timestamp =
+			self.vicinity.block_timestamp.saturating_add(seconds.into());
+	}
+
+	/// Set the current block number.
+	pub fn set_block_number(&mut self, number: U256) {
+		self.vicinity.block_number = number;
+	}
+
+	/// Set the current block timestamp.
+	pub fn set_timestamp(&mut self, timestamp: U256) {
+		self.vicinity.block_timestamp = timestamp;
+	}
+
+	/// Take a snapshot of the accounts and block environment, returning its
+	/// id.
+	pub fn snapshot(&mut self) -> usize {
+		self.snapshots
+			.push((self.vicinity.clone(), self.state.clone()));
+		self.snapshots.len() - 1
+	}
+
+	/// Revert to a snapshot, discarding it and all later snapshots. Returns
+	/// `false` if the snapshot does not exist.
+	pub fn revert_to(&mut self, id: usize) -> bool {
+		if id >= self.snapshots.len() {
+			return false;
+		}
+
+		self.snapshots.truncate(id + 1);
+		if let Some((vicinity, state)) = self.snapshots.pop() {
+			self.vicinity = vicinity;
+			self.state = state;
+		}
+		true
+	}
+
+	/// Deploy a contract, with its constructor arguments ABI encoded after the
+	/// bytecode.
+	pub fn deploy(&mut self, from: H160, bytecode: &[u8], args: &[AbiValue]) -> CallResult {
+		let mut init_code = bytecode.to_vec();
+		init_code.extend_from_slice(&abi::encode(args));
+
+		self.transact(from, true, Action::Create { init_code })
+	}
+
+	/// Execute a call without committing its changes.
+	pub fn call(&mut self, from: H160, to: H160, data: Vec<u8>) -> CallResult {
+		self.transact(
+			from,
+			false,
+			Action::Call {
+				to,
+				value: U256::zero(),
+				data,
+			},
+		)
+	}
+
+	/// Execute a transaction and commit its changes.
+	pub fn send(&mut self, from: H160, to: H160, value: U256, data: Vec<u8>) -> CallResult {
+		self.transact(from, true, Action::Call { to, value, data })
+	}
+
+	fn transact(&mut self, from: H160, commit: bool, action: Action) -> CallResult {
+		self.vicinity.origin = from;
+
+		let mut backend = MemoryBackend::new(&self.vicinity, mem::take(&mut self.state));
+		let metadata = StackSubstateMetadata::new(self.gas_limit, &self.config);
+		let state = MemoryStackState::new(metadata, &backend);
+		let mut executor =
+			StackExecutor::new_with_precompiles(state, &self.config, &self.precompiles);
+		executor.set_state_access(true);
+
+		let outcome = match action {
+			Action::Create { init_code } => {
+				executor.transact_create(from, U256::zero(), init_code, self.gas_limit, Vec::new())
+			}
+			Action::Call { to, value, data } => {
+				executor.transact_call(from, to, value, data, self.gas_limit, Vec::new())
+			}
+		};
+
+		let (values, _) = executor.into_state().deconstruct();
+		if commit {
+			backend.apply(values, Vec::new(), !self.config.empty_considered_exists);
+		}
+		self.state = mem::take(backend.state_mut());
+
+		CallResult {
+			reason: outcome.reason,
+			output: outcome.output,
+			used_gas: outcome.used_gas,
+			logs: outcome.logs,
+			address: outcome.address,
+		}
+	}
+}
+
+enum Action {
+	Create {
+		init_code: Vec<u8>,
+	},
+	Call {
+		to: H160,
+		value: U256,
+		data: Vec<u8>,
+	},
+}
@@ -1,5 +1,6 @@
 //! Allows to listen to runtime events.
 
+use crate::revert::RevertReason;
 use crate::Context;
 use evm_runtime::{CreateScheme, ExitReason, Transfer};
 use primitive_types::{H160, H256, U256};
@@ -28,6 +29,8 @@ pub enum Event<'a> {
 		init_code: &'a [u8],
 		target_gas: Option<u64>,
 	},
+	/// The substate of a call or create is entered, with the gas it can use.
+	Enter { gas_limit: u64, is_static: bool },
 	Suicide {
 		address: H160,
 		target: H160,
@@ -36,6 +39,8 @@ pub enum Event<'a> {
 	Exit {
 		reason: &'a ExitReason,
 		return_value: &'a [u8],
+		/// Decoded return value, if the frame reverted.
+		revert: &'a Option<RevertReason>,
 	},
 	TransactCall {
 		caller: H160,
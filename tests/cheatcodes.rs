@@ -0,0 +1,333 @@
+//! Cheatcodes called by a contract executed by a `StackExecutor`.
+
+mod common;
+
+use common::{contract, revert_with, vicinity, CALLER};
+use evm::abi::{AbiValue, Function};
+use evm::backend::{Backend, MemoryBackend};
+use evm::cheatcodes::{Cheatcodes, CHEATCODE_ADDRESS};
+use evm::executor::stack::{
+	MemoryStackState, StackExecutor, StackSubstateMetadata, TransactionOutcome,
+};
+use evm::revert::RevertReason;
+use evm::Config;
+use primitive_types::{H160, H256, U256};
+use std::collections::BTreeMap;
+
+const TARGET: H160 = H160([0x30; 20]);
+const SENDER: H160 = H160([0x40; 20]);
+// Return CALLER, TIMESTAMP and NUMBER.
+const WHOAMI: H160 = H160([0x50; 20]);
+const TIMESTAMP: H160 = H160([0x51; 20]);
+const NUMBER: H160 = H160([0x52; 20]);
+// Revert with `Error("nope")`.
+const REVERTER: H160 = H160([0x60; 20]);
+// CALL `WHOAMI` or `REVERTER`, and return or revert with its return data.
+const WHOAMI_FORWARDER: H160 = H160([0x70; 20]);
+const REVERTER_FORWARDER: H160 = H160([0x71; 20]);
+
+/// Code making each call in order, storing the first word returned by call
+/// `i` in slot `i`, and reverting with the return data of the first failing
+/// call.
+fn script(calls: &[(H160, Vec<u8>)]) -> String {
+	// Each call takes 56 bytes of code, followed by 11 bytes of revert code
+	// and the call data.
+	let fail = 56 * calls.len() + 1;
+	let mut data_offset = fail + 11;
+
+	let mut code = String::new();
+	for (i, (address, data)) in calls.iter().enumerate() {
+		let output = 0x1000 + 32 * i;
+		// CODECOPY the call data to memory.
+		code += &format!("61{:04x} 61{:04x} 6000 39", data.len(), data_offset);
+		// CALL with it, returning 32 bytes at `output`.
+		code += &format!(
+			"6020 61{:04x} 61{:04x} 6000 6000 73{} 5a f1",
+			output,
+			data.len(),
+			hex::encode(address)
+		);
+		// Jump to the revert code on failure.
+		code += &format!("15 61{:04x} 57", fail);
+		// SSTORE the output.
+		code += &format!("61{:04x} 51 60{:02x} 55", output, i);
+		data_offset += data.len();
+	}
+	code += "00";
+	// Revert with the return data.
+	code += "5b 3d 6000 6000 3e 3d 6000 fd";
+	for (_, data) in calls {
+		code += &hex::encode(data);
+	}
+
+	code.replace(' ', "")
+}
+
+fn forwarder(to: H160) -> String {
+	// CALL, RETURNDATACOPY, then RETURN or REVERT.
+	format!(
+		"6000600060006000600073{} 5a f1 3d 6000 6000 3e 602e 57 3d 6000 fd 5b 3d 6000 f3",
+		hex::encode(to)
+	)
+	.replace(' ', "")
+}
+
+fn cheat(signature: &str, args: &[AbiValue]) -> (H160, Vec<u8>) {
+	let input = Function::parse(signature)
+		.unwrap()
+		.encode_input(args)
+		.unwrap();
+	(CHEATCODE_ADDRESS, input)
+}
+
+fn call(to: H160) -> (H160, Vec<u8>) {
+	(to, Vec::new())
+}
+
+fn nope() -> RevertReason {
+	RevertReason::Error(String::from("nope"))
+}
+
+fn word(value: u64) -> AbiValue {
+	AbiValue::Uint(U256::from(value), 256)
+}
+
+fn bytes32(value: u64) -> AbiValue {
+	AbiValue::FixedBytes(H256::from_low_u64_be(value).as_bytes().to_vec())
+}
+
+struct Test {
+	vicinity: evm::backend::MemoryVicinity,
+	state: BTreeMap<H160, evm::backend::MemoryAccount>,
+	config: Config,
+	cheatcodes: Cheatcodes,
+}
+
+impl Test {
+	fn new() -> Self {
+		let mut state = BTreeMap::new();
+		state.insert(WHOAMI, contract("3360005260206000f3"));
+		state.insert(TIMESTAMP, contract("4260005260206000f3"));
+		state.insert(NUMBER, contract("4360005260206000f3"));
+		state.insert(REVERTER, contract(&revert_with("nope")));
+		state.insert(WHOAMI_FORWARDER, contract(&forwarder(WHOAMI)));
+		state.insert(REVERTER_FORWARDER, contract(&forwarder(REVERTER)));
+
+		let mut vicinity = vicinity();
+		vicinity.block_number = U256::from(10);
+		vicinity.block_timestamp = U256::from(100);
+
+		Self {
+			vicinity,
+			state,
+			config: Config::london(),
+			cheatcodes: Cheatcodes::new(()),
+		}
+	}
+
+	/// Run each script as a transaction of a single executor, returning the
+	/// outcomes, and the state of the executor.
+	fn run<F: FnOnce(&MemoryStackState<MemoryBackend>)>(
+		&self,
+		state_access: bool,
+		scripts: &[&[(H160, Vec<u8>)]],
+		check: F,
+	) -> Vec<TransactionOutcome> {
+		let mut state = self.state.clone();
+		let scripts: Vec<_> = scripts
+			.iter()
+			.enumerate()
+			.map(|(i, calls)| {
+				let address = H160::from_low_u64_be(0x1000 + i as u64);
+				state.insert(address, contract(&script(calls)));
+				address
+			})
+			.collect();
+
+		let backend = MemoryBackend::new(&self.vicinity, state);
+		let metadata = StackSubstateMetadata::new(1_000_000, &self.config);
+		let state = MemoryStackState::new(metadata, &backend);
+		let mut executor =
+			StackExecutor::new_with_precompiles(state, &self.config, &self.cheatcodes);
+		executor.set_state_access(state_access);
+
+		let outcomes = scripts
+			.into_iter()
+			.map(|address| {
+				executor.transact_call(
+					CALLER,
+					address,
+					U256::zero(),
+					Vec::new(),
+					1_000_000,
+					Vec::new(),
+				)
+			})
+			.collect();
+		check(executor.state());
+		outcomes
+	}
+}
+
+fn output(state: &MemoryStackState<MemoryBackend>, script: u64, i: u64) -> H256 {
+	state.storage(
+		H160::from_low_u64_be(0x1000 + script),
+		H256::from_low_u64_be(i),
+	)
+}
+
+#[test]
+fn warp_and_roll() {
+	let test = Test::new();
+	let first: &[_] = &[
+		call(TIMESTAMP),
+		cheat("warp(uint256)", &[word(1_000)]),
+		cheat("roll(uint256)", &[word(77)]),
+		call(TIMESTAMP),
+		call(NUMBER),
+	];
+	let second: &[_] = &[call(TIMESTAMP), call(NUMBER)];
+
+	let outcomes = test.run(true, &[first, second], |state| {
+		assert_eq!(output(state, 0, 0), H256::from_low_u64_be(100));
+		assert_eq!(output(state, 0, 3), H256::from_low_u64_be(1_000));
+		assert_eq!(output(state, 0, 4), H256::from_low_u64_be(77));
+
+		// Overrides end with the transaction.
+		assert_eq!(output(state, 1, 0), H256::from_low_u64_be(100));
+		assert_eq!(output(state, 1, 1), H256::from_low_u64_be(10));
+	});
+	for outcome in outcomes {
+		assert!(outcome.reason.is_succeed(), "{:?}", outcome.reason);
+	}
+}
+
+#[test]
+fn deal_store_load_and_etch() {
+	let test = Test::new();
+	let calls: &[_] = &[
+		cheat(
+			"deal(address,uint256)",
+			&[AbiValue::Address(TARGET), word(5)],
+		),
+		cheat(
+			"store(address,bytes32,bytes32)",
+			&[AbiValue::Address(TARGET), bytes32(1), bytes32(0xabc)],
+		),
+		cheat(
+			"load(address,bytes32)",
+			&[AbiValue::Address(TARGET), bytes32(1)],
+		),
+		// Return 42.
+		cheat(
+			"etch(address,bytes)",
+			&[
+				AbiValue::Address(TARGET),
+				AbiValue::Bytes(hex::decode("602a60005260206000f3").unwrap()),
+			],
+		),
+		call(TARGET),
+	];
+
+	let outcomes = test.run(true, &[calls], |state| {
+		assert_eq!(state.basic(TARGET).balance, U256::from(5));
+		assert_eq!(
+			state.storage(TARGET, H256::from_low_u64_be(1)),
+			H256::from_low_u64_be(0xabc)
+		);
+		assert_eq!(output(state, 0, 2), H256::from_low_u64_be(0xabc));
+		assert_eq!(output(state, 0, 4), H256::from_low_u64_be(42));
+	});
+	assert!(outcomes[0].reason.is_succeed(), "{:?}", outcomes[0].reason);
+}
+
+#[test]
+fn state_access_is_opt_in() {
+	let test = Test::new();
+	let outcomes = test.run(false, &[&[cheat("warp(uint256)", &[word(1)])]], |_| ());
+	assert_eq!(
+		outcomes[0].revert_reason(),
+		Some(RevertReason::Error(String::from(
+			"cheatcodes need state access"
+		)))
+	);
+
+	// Pranks do not need it.
+	let outcomes = test.run(
+		false,
+		&[&[cheat("prank(address)", &[AbiValue::Address(SENDER)])]],
+		|_| (),
+	);
+	assert!(outcomes[0].reason.is_succeed());
+}
+
+#[test]
+fn prank() {
+	let test = Test::new();
+	let sender = [AbiValue::Address(SENDER)];
+	let calls: &[_] = &[
+		cheat("prank(address)", &sender),
+		call(WHOAMI),
+		call(WHOAMI),
+		cheat("startPrank(address)", &sender),
+		call(WHOAMI),
+		// Only calls of the contract setting the prank are pranked.
+		call(WHOAMI_FORWARDER),
+		cheat("stopPrank()", &[]),
+		call(WHOAMI),
+	];
+
+	let script = H160::from_low_u64_be(0x1000);
+	let outcomes = test.run(true, &[calls], |state| {
+		let caller = |i| H160::from(output(state, 0, i));
+		assert_eq!(caller(1), SENDER);
+		assert_eq!(caller(2), script);
+		assert_eq!(caller(4), SENDER);
+		assert_eq!(caller(5), WHOAMI_FORWARDER);
+		assert_eq!(caller(7), script);
+	});
+	assert!(outcomes[0].reason.is_succeed(), "{:?}", outcomes[0].reason);
+}
+
+#[test]
+fn expect_revert() {
+	let test = Test::new();
+	let mut reason = Vec::new();
+	reason.extend_from_slice(&evm::revert::ERROR_SELECTOR);
+	reason.extend_from_slice(&evm::abi::encode(&[AbiValue::String(String::from("nope"))]));
+
+	let succeeding: &[_] = &[
+		cheat("expectRevert()", &[]),
+		call(REVERTER),
+		// A revert bubbled up by a nested call.
+		cheat("expectRevert(bytes)", &[AbiValue::Bytes(reason.clone())]),
+		call(REVERTER_FORWARDER),
+		call(TIMESTAMP),
+	];
+	let not_reverting: &[_] = &[cheat("expectRevert()", &[]), call(WHOAMI)];
+	let other_data: &[_] = &[
+		cheat("expectRevert(bytes)", &[AbiValue::Bytes(vec![1, 2, 3])]),
+		call(REVERTER),
+	];
+	let unexpected: &[_] = &[call(REVERTER_FORWARDER)];
+
+	let outcomes = test.run(
+		true,
+		&[succeeding, not_reverting, other_data, unexpected],
+		|state| assert_eq!(output(state, 0, 4), H256::from_low_u64_be(100)),
+	);
+	assert!(outcomes[0].reason.is_succeed(), "{:?}", outcomes[0].reason);
+	assert_eq!(
+		outcomes[1].revert_reason(),
+		Some(RevertReason::Error(String::from(
+			"call did not revert as expected"
+		)))
+	);
+	assert_eq!(
+		outcomes[2].revert_reason(),
+		Some(RevertReason::Error(String::from(
+			"call reverted with unexpected data"
+		)))
+	);
+	assert_eq!(outcomes[3].revert_reason(), Some(nope()));
+}
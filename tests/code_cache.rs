@@ -0,0 +1,128 @@
+//! A code cache shared by the executors of several transactions.
+
+mod common;
+
+use common::{contract, vicinity, CALLER};
+use evm::backend::MemoryBackend;
+use evm::executor::stack::{CodeCache, MemoryStackState, StackExecutor, StackSubstateMetadata};
+use evm::Config;
+use primitive_types::{H160, H256, U256};
+use sha3::{Digest, Keccak256};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+fn call(backend: &MemoryBackend, config: &Config, cache: &Arc<CodeCache>, to: H160) {
+	let metadata = StackSubstateMetadata::new(100_000, config);
+	let state = MemoryStackState::new(metadata, backend);
+	let mut executor = StackExecutor::new_with_precompiles(state, config, &());
+	executor.set_code_cache(cache.clone());
+
+	let outcome = executor.transact_call(CALLER, to, U256::zero(), Vec::new(), 100_000, Vec::new());
+	assert!(outcome.reason.is_succeed(), "{:?}", outcome.reason);
+}
+
+fn code_hash(code: &[u8]) -> H256 {
+	H256::from_slice(&Keccak256::digest(code))
+}
+
+#[test]
+fn hits_shared_across_transactions() {
+	let config = Config::london();
+	let vicinity = vicinity();
+	let a = H160([0xa0; 20]);
+	let b = H160([0xb0; 20]);
+	let c = H160([0xc0; 20]);
+
+	// `b` and `c` have the same code, and share an entry.
+	let mut state = BTreeMap::new();
+	state.insert(a, contract("6001600055"));
+	state.insert(b, contract("5b00"));
+	state.insert(c, contract("5b00"));
+	let backend = MemoryBackend::new(&vicinity, state);
+	let cache = Arc::new(CodeCache::new(8));
+
+	call(&backend, &config, &cache, a);
+	assert_eq!(cache.len(), 1);
+	let first = cache.get(code_hash(&backend.state()[&a].code)).unwrap();
+
+	call(&backend, &config, &cache, a);
+	call(&backend, &config, &cache, b);
+	call(&backend, &config, &cache, c);
+	assert_eq!(cache.len(), 2);
+
+	let second = cache.get(code_hash(&backend.state()[&a].code)).unwrap();
+	assert!(Arc::ptr_eq(&first.valids, &second.valids));
+	assert!(second.gas_blocks.is_none());
+
+	// An executor precharging block gas completes the cached analysis.
+	let precharged = Config {
+		precharge_block_gas: true,
+		..Config::london()
+	};
+	call(&backend, &precharged, &cache, a);
+	let third = cache.get(code_hash(&backend.state()[&a].code)).unwrap();
+	assert!(Arc::ptr_eq(&first.valids, &third.valids));
+	assert!(third.gas_blocks.is_some());
+	assert_eq!(cache.len(), 2);
+}
+
+#[test]
+fn shared_across_threads() {
+	let cache = Arc::new(CodeCache::new(8));
+	let addresses = [H160([0xa0; 20]), H160([0xb0; 20])];
+
+	let threads = addresses
+		.iter()
+		.map(|&address| {
+			let cache = cache.clone();
+			std::thread::spawn(move || {
+				let config = Config::london();
+				let vicinity = vicinity();
+				let mut state = BTreeMap::new();
+				state.insert(address, contract("5b00"));
+				let backend = MemoryBackend::new(&vicinity, state);
+				call(&backend, &config, &cache, address);
+			})
+		})
+		.collect::<Vec<_>>();
+	for thread in threads {
+		thread.join().unwrap();
+	}
+
+	// Both threads ran the same code, analysed at most twice but cached once.
+	assert_eq!(cache.len(), 1);
+	assert!(cache.get(code_hash(&[0x5b, 0x00])).is_some());
+}
+
+#[test]
+fn code_created_in_transaction() {
+	let config = Config::london();
+	let vicinity = vicinity();
+	let factory = H160([0xf0; 20]);
+
+	// Deploy `PUSH1 1, PUSH1 0, SSTORE, STOP` with CREATE, then call it twice,
+	// so that its hash is looked up for code set in the transaction.
+	let runtime = "600160005500";
+	// PUSH6 runtime, PUSH1 0, MSTORE, PUSH1 6, PUSH1 26, RETURN
+	let init = format!("65{}6000526006601af3", runtime);
+	// PUSH1 0 x5, DUP6, GAS, CALL, POP
+	let call_created = "60006000600060006000855af150";
+	// PUSH15 init, PUSH1 0, MSTORE, PUSH1 15, PUSH1 17, PUSH1 0, CREATE,
+	// then call the created address twice.
+	let code = format!(
+		"6e{}600052600f60116000f0{}{}00",
+		init, call_created, call_created
+	);
+
+	let mut state = BTreeMap::new();
+	state.insert(factory, contract(&code));
+	let backend = MemoryBackend::new(&vicinity, state);
+	let cache = Arc::new(CodeCache::new(8));
+
+	call(&backend, &config, &cache, factory);
+	assert_eq!(cache.len(), 2);
+	let created = cache
+		.get(code_hash(&hex::decode(runtime).unwrap()))
+		.unwrap();
+	assert_eq!(created.code[..], hex::decode(runtime).unwrap()[..]);
+}
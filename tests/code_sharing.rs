@@ -0,0 +1,105 @@
+//! Code is shared through `Arc` from the memory backend to the executor,
+//! rather than copied on each access.
+
+mod common;
+
+use common::{contract, vicinity, CALLER};
+use evm::backend::{Backend, MemoryAccount, MemoryBackend};
+use evm::executor::stack::{CodeCache, MemoryStackState, StackExecutor, StackSubstateMetadata};
+use evm::{Config, Handler};
+use primitive_types::{H160, H256, U256};
+use sha3::{Digest, Keccak256};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+const CONTRACT: H160 = H160([0x20; 20]);
+const CALLEE: H160 = H160([0x30; 20]);
+
+fn state() -> BTreeMap<H160, MemoryAccount> {
+	// EXTCODECOPY the first two bytes of the callee, then CALL it.
+	let code = format!(
+		"600260006000 73{0} 3c 6000600060006000600073{0} 5af1 50 00",
+		hex::encode(CALLEE)
+	)
+	.replace(' ', "");
+
+	let mut state = BTreeMap::new();
+	for (address, code) in [(CONTRACT, code.as_str()), (CALLEE, "5b00")] {
+		state.insert(address, contract(code));
+	}
+	state
+}
+
+fn code_hash(code: &[u8]) -> H256 {
+	H256::from_slice(&Keccak256::digest(code))
+}
+
+#[test]
+fn memory_backend_shares_code() {
+	let vicinity = vicinity();
+	let mut backend = MemoryBackend::new(&vicinity, state());
+
+	let code = backend.code(CALLEE);
+	assert!(Arc::ptr_eq(&code, &backend.code(CALLEE)));
+	assert!(Arc::ptr_eq(&code, &backend.state()[&CALLEE].code));
+	assert_eq!(backend.code_hash(CALLEE), code_hash(&code));
+	assert_eq!(backend.code_hash(CALLEE), code_hash(&code));
+	assert_eq!(backend.code_hash(H160::default()), code_hash(&[]));
+
+	// Replaced code is hashed again.
+	backend.state_mut().get_mut(&CALLEE).unwrap().code = vec![0x00].into();
+	assert_eq!(backend.code_hash(CALLEE), code_hash(&[0x00]));
+}
+
+#[test]
+fn memory_backend_is_thread_safe() {
+	fn assert_send_sync<T: Send + Sync>() {}
+	assert_send_sync::<MemoryAccount>();
+	assert_send_sync::<MemoryBackend>();
+}
+
+#[cfg(feature = "with-serde")]
+#[test]
+fn memory_account_serializes_code_as_bytes() {
+	let account = contract("5b00");
+	let json = serde_json::to_string(&account).unwrap();
+	assert!(json.contains("\"code\":[91,0]"), "{}", json);
+	assert_eq!(
+		serde_json::from_str::<MemoryAccount>(&json).unwrap(),
+		account
+	);
+}
+
+#[test]
+fn executor_shares_backend_code() {
+	let config = Config::london();
+	let vicinity = vicinity();
+	let backend = MemoryBackend::new(&vicinity, state());
+	let backend_code = backend.state()[&CALLEE].code.clone();
+
+	let metadata = StackSubstateMetadata::new(100_000, &config);
+	let state = MemoryStackState::new(metadata, &backend);
+	let mut executor = StackExecutor::new_with_precompiles(state, &config, &());
+	let cache = Arc::new(CodeCache::new(8));
+	executor.set_code_cache(cache.clone());
+
+	let outcome = executor.transact_call(
+		CALLER,
+		CONTRACT,
+		U256::zero(),
+		Vec::new(),
+		100_000,
+		Vec::new(),
+	);
+	assert!(outcome.reason.is_succeed(), "{:?}", outcome.reason);
+
+	// EXTCODECOPY reads the code through `Handler::code`, and CALL runs the
+	// code in the cache.
+	assert!(Arc::ptr_eq(
+		&backend_code,
+		&Handler::code(&executor, CALLEE)
+	));
+	assert!(Arc::ptr_eq(&backend_code, &executor.state().code(CALLEE)));
+	let analysed = cache.get(code_hash(&backend_code)).unwrap();
+	assert!(Arc::ptr_eq(&backend_code, &analysed.code));
+}
@@ -0,0 +1,51 @@
+//! Helpers shared by the integration tests running a `StackExecutor` over a
+//! `MemoryBackend`.
+
+#![allow(dead_code)]
+
+use evm::abi::{self, AbiValue};
+use evm::backend::{MemoryAccount, MemoryVicinity};
+use evm::revert::ERROR_SELECTOR;
+use primitive_types::{H160, U256};
+
+pub const CALLER: H160 = H160([0x10; 20]);
+
+/// Block environment with a zero gas price, and `CALLER` as origin.
+pub fn vicinity() -> MemoryVicinity {
+	MemoryVicinity {
+		gas_price: U256::zero(),
+		origin: CALLER,
+		chain_id: U256::one(),
+		block_hashes: Vec::new(),
+		block_number: U256::zero(),
+		block_coinbase: H160::default(),
+		block_timestamp: U256::zero(),
+		block_difficulty: U256::zero(),
+		block_gas_limit: U256::from(30_000_000),
+		block_base_fee_per_gas: U256::zero(),
+	}
+}
+
+/// Contract account with the given hex encoded code.
+pub fn contract(code: &str) -> MemoryAccount {
+	MemoryAccount {
+		nonce: U256::one(),
+		code: hex::decode(code).unwrap().into(),
+		..Default::default()
+	}
+}
+
+/// Code reverting with `Error(message)`.
+pub fn revert_with(message: &str) -> String {
+	let mut output = ERROR_SELECTOR.to_vec();
+	output.extend_from_slice(&abi::encode(&[AbiValue::String(String::from(message))]));
+	assert!(output.len() < 0x100);
+
+	// CODECOPY the output following the 12 bytes of code, then REVERT.
+	format!(
+		"60{0:02x} 600c 6000 39 60{0:02x} 6000 fd {1}",
+		output.len(),
+		hex::encode(&output)
+	)
+	.replace(' ', "")
+}
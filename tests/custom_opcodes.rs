@@ -0,0 +1,121 @@
+//! Execution of custom opcodes registered on `Config`.
+
+mod common;
+
+use common::{contract, vicinity, CALLER};
+use evm::backend::MemoryBackend;
+use evm::executor::stack::{
+	MemoryStackState, StackExecutor, StackSubstateMetadata, TransactionOutcome,
+};
+use evm::{Config, CustomOpcode, ExitError, ExitReason, Machine, Opcode, OpcodeRegistry};
+use primitive_types::{H160, H256, U256};
+use std::collections::BTreeMap;
+
+const CONTRACT: H160 = H160([0x20; 20]);
+
+/// Add the byte of immediate data to the top of the stack.
+fn addi(machine: &mut Machine) -> Result<(), ExitError> {
+	let position = *machine
+		.position()
+		.as_ref()
+		.map_err(|_| ExitError::InvalidRange)?;
+	let immediate = machine.code().get(position).copied().unwrap_or(0);
+	let value = machine.stack_mut().pop()?;
+	machine.stack_mut().push(value + U256::from(immediate))
+}
+
+/// Duplicate the top of the stack.
+fn dup(machine: &mut Machine) -> Result<(), ExitError> {
+	let value = machine.stack().peek(0)?;
+	machine.stack_mut().push(value)
+}
+
+static OPCODES: [CustomOpcode; 2] = [
+	CustomOpcode {
+		opcode: Opcode(0x0c),
+		gas: 5,
+		inputs: 1,
+		outputs: 1,
+		immediates: 1,
+		eval: addi,
+	},
+	CustomOpcode {
+		opcode: Opcode(0x0d),
+		gas: 5,
+		inputs: 1,
+		outputs: 2,
+		immediates: 0,
+		eval: dup,
+	},
+];
+
+fn config() -> Config {
+	Config {
+		custom_opcodes: OpcodeRegistry::new(&OPCODES).unwrap(),
+		..Config::london()
+	}
+}
+
+fn call(config: &Config, code: &str) -> TransactionOutcome {
+	let mut state = BTreeMap::new();
+	state.insert(CONTRACT, contract(&code.replace(' ', "")));
+
+	let vicinity = vicinity();
+	let backend = MemoryBackend::new(&vicinity, state);
+	let metadata = StackSubstateMetadata::new(100_000, config);
+	let state = MemoryStackState::new(metadata, &backend);
+	let mut executor = StackExecutor::new_with_precompiles(state, config, &());
+
+	executor.transact_call(
+		CALLER,
+		CONTRACT,
+		U256::zero(),
+		Vec::new(),
+		100_000,
+		Vec::new(),
+	)
+}
+
+#[test]
+fn stack_effect_and_gas() {
+	// PUSH1 2, ADDI 0x5b, then return the top of the stack.
+	let code = "6002 0c5b 600052 60206000f3";
+	let mut config = config();
+	for precharge_block_gas in [false, true] {
+		config.precharge_block_gas = precharge_block_gas;
+		let outcome = call(&config, code);
+		assert!(outcome.reason.is_succeed(), "{:?}", outcome.reason);
+		assert_eq!(outcome.output, H256::from_low_u64_be(0x5d).as_bytes());
+		assert_eq!(outcome.used_gas, 21_000 + 3 + 5 + 3 + 3 + 3 + 3 + 3);
+	}
+}
+
+#[test]
+fn immediate_data_is_not_a_jump_destination() {
+	// Jump to the immediate data of ADDI, which is a JUMPDEST byte.
+	let code = "6004 56 0c5b 00";
+	assert_eq!(
+		call(&config(), code).reason,
+		ExitReason::Error(ExitError::InvalidJump)
+	);
+	// Without the registry, 0x0c is an invalid opcode taking no immediate.
+	assert!(call(&Config::london(), code).reason.is_succeed());
+}
+
+#[test]
+fn stack_underflow_and_overflow() {
+	assert_eq!(
+		call(&config(), "0c01 00").reason,
+		ExitReason::Error(ExitError::StackUnderflow)
+	);
+
+	let config = Config {
+		stack_limit: 1,
+		..config()
+	};
+	assert!(call(&config, "6001 0c01 00").reason.is_succeed());
+	assert_eq!(
+		call(&config, "6001 0d 00").reason,
+		ExitReason::Error(ExitError::StackOverflow)
+	);
+}
@@ -0,0 +1,98 @@
+//! Gas of the same code executed under Ethereum Classic forks, compared to
+//! the Ethereum forks they follow.
+
+mod common;
+
+use common::{contract, vicinity, CALLER};
+use evm::backend::MemoryBackend;
+use evm::executor::stack::{
+	MemoryStackState, StackExecutor, StackSubstateMetadata, TransactionOutcome,
+};
+use evm::{Config, ExitError, ExitReason, Opcode};
+use primitive_types::{H160, H256, U256};
+use std::collections::BTreeMap;
+
+const CONTRACT: H160 = H160([0x20; 20]);
+
+/// Call a contract with `code`, and slot 0 holding 1.
+fn call(config: &Config, code: &str) -> TransactionOutcome {
+	let mut account = contract(&code.replace(' ', ""));
+	account
+		.storage
+		.insert(H256::zero(), H256::from_low_u64_be(1));
+	let mut state = BTreeMap::new();
+	state.insert(CONTRACT, account);
+
+	let vicinity = vicinity();
+	let backend = MemoryBackend::new(&vicinity, state);
+	let metadata = StackSubstateMetadata::new(100_000, config);
+	let state = MemoryStackState::new(metadata, &backend);
+	let mut executor = StackExecutor::new_with_precompiles(state, config, &());
+
+	executor.transact_call(
+		CALLER,
+		CONTRACT,
+		U256::zero(),
+		Vec::new(),
+		100_000,
+		Vec::new(),
+	)
+}
+
+#[test]
+fn sload_gas() {
+	// SLOAD slot 0, POP.
+	let code = "6000 54 50 00";
+	for (config, sload) in [
+		(Config::atlantis(), 200),
+		(Config::agharta(), 200),
+		// EIP-1884
+		(Config::phoenix(), 800),
+		// EIP-2929, cold slot.
+		(Config::magneto(), 2100),
+		(Config::mystique(), 2100),
+	] {
+		let outcome = call(&config, code);
+		assert!(outcome.reason.is_succeed());
+		assert_eq!(outcome.used_gas, 21_000 + 3 + sload + 2);
+	}
+}
+
+#[test]
+fn sstore_of_same_value() {
+	// SSTORE 1 at slot 0, which already holds 1.
+	let code = "6001 6000 55 00";
+	// Agharta drops the net gas metering of Constantinople, which Phoenix
+	// brings back with EIP-2200.
+	assert_eq!(call(&Config::agharta(), code).used_gas, 21_000 + 6 + 5_000);
+	assert_eq!(call(&Config::phoenix(), code).used_gas, 21_000 + 6 + 800);
+}
+
+#[test]
+fn mystique_refunds() {
+	// SSTORE 0 at slot 0, which holds 1.
+	let code = "6000 6000 55 00";
+	let used = 21_000 + 6 + 2_100 + 2_900;
+
+	// Refund of 15_000, capped at half the gas used.
+	let magneto = call(&Config::magneto(), code);
+	assert_eq!(magneto.used_gas_before_refund, used);
+	assert_eq!(magneto.refunded_gas, used / 2);
+
+	// EIP-3529: refund of 4_800, capped at a fifth of the gas used.
+	let mystique = call(&Config::mystique(), code);
+	assert_eq!(mystique.used_gas_before_refund, used);
+	assert_eq!(mystique.refunded_gas, 4_800);
+	assert_eq!(call(&Config::london(), code).refunded_gas, 4_800);
+}
+
+#[test]
+fn mystique_has_no_base_fee() {
+	// BASEFEE, POP.
+	let code = "48 50 00";
+	assert!(call(&Config::london(), code).reason.is_succeed());
+	assert_eq!(
+		call(&Config::mystique(), code).reason,
+		ExitReason::Error(ExitError::InvalidCode(Opcode::BASEFEE))
+	);
+}
@@ -0,0 +1,98 @@
+//! Interrupting a transaction aborts it, discarding its state changes.
+
+mod common;
+
+use common::{contract, vicinity, CALLER};
+use evm::backend::{Apply, MemoryBackend};
+use evm::executor::stack::{MemoryStackState, StackExecutor, StackSubstateMetadata};
+use evm::{Config, ExitFatal, ExitReason};
+use primitive_types::{H160, H256, U256};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+const CONTRACT: H160 = H160([0x20; 20]);
+const CALLEE: H160 = H160([0x30; 20]);
+
+/// Call the contract, polling `interrupt` on every opcode. Return the exit
+/// reason, and the storage written by the transaction.
+fn run(
+	interrupt: Option<Arc<dyn Fn() -> bool + Send + Sync>>,
+) -> (ExitReason, Vec<(H160, H256, H256)>) {
+	// SSTORE 1 at slot 0, then CALL the callee with all gas left.
+	let code = format!(
+		"6001600055 6000600060006000600073{} 5af1 00",
+		hex::encode(CALLEE)
+	)
+	.replace(' ', "");
+	// SSTORE 2 at slot 1, then loop forever.
+	let callee = "6002600155 5b 6005 56".replace(' ', "");
+
+	let mut state = BTreeMap::new();
+	state.insert(CONTRACT, contract(&code));
+	state.insert(CALLEE, contract(&callee));
+
+	let vicinity = vicinity();
+	let backend = MemoryBackend::new(&vicinity, state);
+	let config = Config::london();
+	let metadata = StackSubstateMetadata::new(1_000_000, &config);
+	let state = MemoryStackState::new(metadata, &backend);
+	let mut executor = StackExecutor::new_with_precompiles(state, &config, &());
+	if let Some(interrupt) = interrupt {
+		executor.set_interrupt(1, move || interrupt());
+	}
+
+	let outcome = executor.transact_call(
+		CALLER,
+		CONTRACT,
+		U256::zero(),
+		Vec::new(),
+		1_000_000,
+		Vec::new(),
+	);
+
+	let (values, _) = executor.into_state().deconstruct();
+	let mut writes = Vec::new();
+	for apply in values {
+		if let Apply::Modify {
+			address, storage, ..
+		} = apply
+		{
+			for (index, value) in storage {
+				writes.push((address, index, value));
+			}
+		}
+	}
+	(outcome.reason, writes)
+}
+
+#[test]
+fn interrupt_in_nested_call() {
+	// Interrupt on the 20th poll, once the callee wrote to storage and is
+	// looping: the caller executes 11 opcodes up to the CALL, and the callee
+	// 3 up to the SSTORE.
+	let polls = Arc::new(AtomicUsize::new(0));
+	let interrupt = {
+		let polls = polls.clone();
+		Arc::new(move || polls.fetch_add(1, Ordering::Relaxed) + 1 >= 20)
+	};
+
+	let (reason, writes) = run(Some(interrupt));
+	assert_eq!(reason, ExitReason::Fatal(ExitFatal::Interrupted));
+	assert_eq!(polls.load(Ordering::Relaxed), 20);
+	assert_eq!(writes, Vec::new());
+}
+
+#[test]
+fn uninterrupted_call_runs_out_of_gas() {
+	// The callee runs out of gas, and the write of the caller is kept.
+	let expected = vec![(CONTRACT, H256::zero(), H256::from_low_u64_be(1))];
+	let (reason, writes) = run(None);
+	assert!(reason.is_succeed(), "{:?}", reason);
+	assert_eq!(writes, expected);
+
+	// An interrupt returning false does not change the outcome.
+	let (reason, writes) = run(Some(Arc::new(|| false)));
+	assert!(reason.is_succeed(), "{:?}", reason);
+	assert_eq!(writes, expected);
+}
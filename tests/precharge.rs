@@ -0,0 +1,138 @@
+//! Block gas precharging must not change gas usage nor exit reasons. Each
+//! test runs the same code with and without `Config::precharge_block_gas`,
+//! for every gas limit up to the one needed for the code to complete.
+
+mod common;
+
+use common::CALLER;
+use evm::backend::MemoryAccount;
+use evm::testing::TestChain;
+use evm::{Config, ExitError, ExitReason, Opcode};
+use primitive_types::{H160, H256, U256};
+
+const CONTRACT: H160 = H160([0x20; 20]);
+const CALLEE: H160 = H160([0x30; 20]);
+
+#[derive(Debug, Eq, PartialEq)]
+struct Outcome {
+	reason: ExitReason,
+	used_gas: u64,
+	output: Vec<u8>,
+	storage: H256,
+}
+
+fn run(config: &Config, code: &str, callee: &str, gas: u64) -> Outcome {
+	let mut chain = TestChain::new(config.clone());
+	for (address, code) in [(CONTRACT, code), (CALLEE, callee)] {
+		chain.state_mut().insert(
+			address,
+			MemoryAccount {
+				nonce: U256::one(),
+				code: hex::decode(code).unwrap().into(),
+				..Default::default()
+			},
+		);
+	}
+
+	chain.set_gas_limit(21_000 + gas);
+	let result = chain.send(CALLER, CONTRACT, U256::zero(), Vec::new());
+
+	Outcome {
+		reason: result.reason,
+		used_gas: result.used_gas,
+		output: result.output,
+		storage: chain.storage(CONTRACT, H256::zero()),
+	}
+}
+
+/// Compare both modes for every gas limit up to `max_gas`, and return the
+/// outcome with the largest gas limit.
+fn assert_equivalent(config: Config, code: &str, callee: &str, max_gas: u64) -> Outcome {
+	let precharged = Config {
+		precharge_block_gas: true,
+		..config.clone()
+	};
+
+	for gas in 0..max_gas {
+		assert_eq!(
+			run(&config, code, callee, gas),
+			run(&precharged, code, callee, gas),
+			"gas limit {}",
+			gas
+		);
+	}
+
+	let outcome = run(&config, code, callee, max_gas);
+	assert_eq!(outcome, run(&precharged, code, callee, max_gas));
+	outcome
+}
+
+#[test]
+fn out_of_gas_in_block() {
+	// PUSH1 1, PUSH1 2, ADD, PUSH1 3, MUL, POP, STOP
+	let outcome = assert_equivalent(Config::london(), "600160020160030250", "", 30);
+	assert_eq!(
+		outcome.reason,
+		ExitReason::Succeed(evm::ExitSucceed::Stopped)
+	);
+	assert_eq!(outcome.used_gas, 21_000 + 19);
+}
+
+#[test]
+fn error_in_block() {
+	// PUSH1 1, PUSH1 2, ADD, ADD: the second ADD underflows the stack once
+	// it is paid for, and runs out of gas otherwise.
+	let outcome = assert_equivalent(Config::london(), "6001600201010000", "", 30);
+	assert_eq!(outcome.reason, ExitReason::Error(ExitError::StackUnderflow));
+	assert_eq!(
+		run(&Config::london(), "6001600201010000", "", 11).reason,
+		ExitReason::Error(ExitError::OutOfGas)
+	);
+}
+
+#[test]
+fn jump_into_block() {
+	// Count to 5 in a loop jumping back to the middle of the straight line
+	// code it first falls through, then return the counter.
+	//
+	// 00 PUSH1 0
+	// 02 JUMPDEST
+	// 03 PUSH1 1, ADD, DUP1, PUSH1 5, GT, PUSH1 2, JUMPI
+	// 0d PUSH1 0, MSTORE, PUSH1 32, PUSH1 0, RETURN
+	let code = "60005b6001018060051160025760005260206000f3";
+	let outcome = assert_equivalent(Config::london(), code, "", 250);
+	assert_eq!(outcome.output, H256::from_low_u64_be(5).as_bytes());
+}
+
+#[test]
+fn dynamic_cost_ending_block() {
+	// PUSH1 0, SLOAD, POP, GAS, PUSH1 0, MSTORE, PUSH1 32, PUSH1 0, RETURN:
+	// runs end before SLOAD and MSTORE, and right after GAS, which observes
+	// the gas left.
+	let code = "600054505a60005260206000f3";
+	let outcome = assert_equivalent(Config::london(), code, "", 2_200);
+	assert!(outcome.reason.is_succeed());
+}
+
+#[test]
+fn pre_validate_rejects_after_precharge() {
+	// PUSH1 1, PUSH1 2, ADD, CHAINID: CHAINID is rejected by
+	// `Handler::pre_validate` before Istanbul, after the run before it was
+	// precharged.
+	let outcome = assert_equivalent(Config::petersburg(), "600160020146", "", 30);
+	assert_eq!(
+		outcome.reason,
+		ExitReason::Error(ExitError::InvalidCode(Opcode::CHAINID))
+	);
+
+	// STATICCALL a callee writing storage, then return the call result and
+	// the gas left. The callee precharges PUSH1 1, PUSH1 0 before SSTORE is
+	// rejected as a static call violation.
+	let code = format!(
+		"60006000600060007f{:0>64}6103e8fa5a60005260205260406000f3",
+		hex::encode(CALLEE)
+	);
+	let outcome = assert_equivalent(Config::london(), &code, "600160005500", 3_800);
+	assert!(outcome.reason.is_succeed());
+	assert_eq!(outcome.output[32..], H256::zero()[..]);
+}
@@ -0,0 +1,116 @@
+//! Gas of profiled transactions, by call frame and by opcode.
+
+#![cfg(feature = "tracing")]
+
+use evm::backend::MemoryAccount;
+use evm::profiler::{GasProfile, GasProfiler, GasStats};
+use evm::testing::{CallResult, TestChain};
+use evm::{Config, Opcode};
+use primitive_types::{H160, U256};
+
+const CALLER: H160 = H160([0x10; 20]);
+const CONTRACT: H160 = H160([0x20; 20]);
+const CALLEE: H160 = H160([0x30; 20]);
+
+fn profile(code: &str, callee: &str) -> (CallResult, GasProfile) {
+	let mut chain = TestChain::new(Config::london());
+	for (address, code) in [(CONTRACT, code), (CALLEE, callee)] {
+		chain.state_mut().insert(
+			address,
+			MemoryAccount {
+				nonce: U256::one(),
+				code: hex::decode(code.replace(' ', "")).unwrap().into(),
+				..Default::default()
+			},
+		);
+	}
+	chain.fund(CONTRACT, U256::one());
+
+	let profiler = GasProfiler::new();
+	let result = profiler.profile(|| chain.send(CALLER, CONTRACT, U256::zero(), Vec::new()));
+	(result, profiler.report())
+}
+
+fn stats(profile: &GasProfile, opcode: Opcode) -> GasStats {
+	profile.by_opcode.get(&opcode).copied().unwrap_or_default()
+}
+
+/// The gas of all frames and opcodes adds up to the gas of the transaction.
+fn assert_consistent(result: &CallResult, profile: &GasProfile) {
+	assert_eq!(profile.intrinsic_gas, 21_000);
+	assert_eq!(profile.total_gas, result.used_gas);
+
+	let executed = result.used_gas - profile.intrinsic_gas;
+	assert_eq!(profile.inclusive_gas(&[CONTRACT]), executed);
+	let by_opcode: u64 = profile.by_opcode.values().map(|stats| stats.gas).sum();
+	assert_eq!(by_opcode, executed);
+	let by_address: u64 = profile.by_address.values().map(|stats| stats.gas).sum();
+	assert_eq!(by_address, executed);
+}
+
+#[test]
+fn nested_call() {
+	// SSTORE 1 at slot 0, then CALL the callee with a value of 1 and all gas
+	// left. The callee does SSTORE 2 at slot 0.
+	let code = format!(
+		"6001600055 6000600060006000 6001 73{} 5a f1 00",
+		hex::encode(CALLEE)
+	);
+	let (result, profile) = profile(&code, "6002600055 00");
+	result.assert_success();
+	assert_consistent(&result, &profile);
+
+	// The callee uses 22_106 gas, of which 2_300 are the call stipend.
+	let callee = 3 + 3 + 22_100;
+	assert_eq!(profile.by_call_path[&vec![CONTRACT, CALLEE]].gas, callee);
+	assert_eq!(profile.inclusive_gas(&[CONTRACT, CALLEE]), callee);
+	assert_eq!(profile.by_address[&CALLEE].gas, callee);
+	assert_eq!(
+		profile.by_call_path[&vec![CONTRACT]].gas,
+		result.used_gas - 21_000 - callee
+	);
+
+	assert_eq!(
+		stats(&profile, Opcode::SSTORE),
+		GasStats {
+			gas: 2 * 22_100,
+			count: 2
+		}
+	);
+	// Cold account access and value transfer, less the stipend.
+	assert_eq!(
+		stats(&profile, Opcode::CALL),
+		GasStats {
+			gas: 2_600 + 9_000 - 2_300,
+			count: 1
+		}
+	);
+}
+
+#[test]
+fn failing_nested_call() {
+	// CALL the callee with 1000 gas, which it burns on INVALID.
+	let code = format!(
+		"6000600060006000 6000 73{} 6103e8 f1 00",
+		hex::encode(CALLEE)
+	);
+	let (result, profile) = profile(&code, "fe");
+	result.assert_success();
+	assert_consistent(&result, &profile);
+
+	assert_eq!(
+		stats(&profile, Opcode::INVALID),
+		GasStats {
+			gas: 1_000,
+			count: 1
+		}
+	);
+	assert_eq!(profile.inclusive_gas(&[CONTRACT, CALLEE]), 1_000);
+	assert_eq!(
+		stats(&profile, Opcode::CALL),
+		GasStats {
+			gas: 2_600,
+			count: 1
+		}
+	);
+}
@@ -0,0 +1,194 @@
+//! Resource usage metered alongside gas by `StackExecutor`.
+
+mod common;
+
+use common::{contract, vicinity, CALLER};
+use evm::backend::MemoryBackend;
+use evm::executor::stack::{
+	MemoryStackState, StackExecutor, StackState, StackSubstateMetadata, TransactionOutcome,
+};
+use evm::gasometer::ResourceUsage;
+use evm::{Config, ExitError, ExitReason};
+use primitive_types::{H160, H256, U256};
+use std::collections::BTreeMap;
+
+const CONTRACT: H160 = H160([0x20; 20]);
+const COMMITTING: H160 = H160([0x30; 20]);
+const REVERTING: H160 = H160([0x40; 20]);
+
+// Read slot 0, set slot 1 from zero and log a byte.
+const ACCESSES: &str = "600054 50 6001600155 60016000a0";
+
+/// Call `CONTRACT` running `code` with `input`, metering resources with the
+/// given limits. Slot 0 of each contract holds 1.
+fn call(code: &str, input: &[u8], limits: ResourceUsage) -> TransactionOutcome {
+	let mut state = BTreeMap::new();
+	for (address, code) in [
+		(CONTRACT, code.to_string()),
+		(COMMITTING, format!("{} 00", ACCESSES)),
+		(REVERTING, format!("{} 60006000fd", ACCESSES)),
+	] {
+		let mut account = contract(&code.replace(' ', ""));
+		account
+			.storage
+			.insert(H256::zero(), H256::from_low_u64_be(1));
+		state.insert(address, account);
+	}
+
+	let vicinity = vicinity();
+	let backend = MemoryBackend::new(&vicinity, state);
+	let config = Config::london();
+	let metadata = StackSubstateMetadata::new(1_000_000, &config);
+	let state = MemoryStackState::new(metadata, &backend);
+	let mut executor = StackExecutor::new_with_precompiles(state, &config, &());
+	executor
+		.state_mut()
+		.metadata_mut()
+		.set_resource_limits(limits);
+
+	executor.transact_call(
+		CALLER,
+		CONTRACT,
+		U256::zero(),
+		input.to_vec(),
+		1_000_000,
+		Vec::new(),
+	)
+}
+
+fn used(code: &str) -> ResourceUsage {
+	let outcome = call(code, &[], ResourceUsage::unlimited());
+	assert!(outcome.reason.is_succeed(), "{:?}", outcome.reason);
+	outcome.resources.unwrap()
+}
+
+/// Code calling `target` with all the gas left.
+fn call_of(target: H160) -> String {
+	format!("6000600060006000600073{} 5a f1 50 00", hex::encode(target))
+}
+
+#[test]
+fn usage_of_each_resource() {
+	let outcome = call(
+		&format!("{} 00", ACCESSES),
+		&[1, 2, 3],
+		ResourceUsage::unlimited(),
+	);
+	assert!(outcome.reason.is_succeed());
+	assert_eq!(
+		outcome.resources,
+		Some(ResourceUsage {
+			// PUSH1 and POP, then PUSH1 twice, then LOG0 of a byte, with
+			// its memory expansion.
+			compute: 3 + 2 + 6 + 6 + 375 + 8 + 3,
+			state_reads: 1,
+			state_writes: 1,
+			new_state_bytes: 32,
+			calldata_bytes: 3,
+			log_bytes: 1,
+		})
+	);
+}
+
+#[test]
+fn new_state_of_slot_set_more_than_once() {
+	// Slot 1 set from zero, cleared and set again.
+	let usage = used("6001600155 6000600155 6002600155 00");
+	assert_eq!(usage.state_writes, 3);
+	assert_eq!(usage.new_state_bytes, 32);
+
+	// Slot 1 set from zero then cleared.
+	assert_eq!(used("6001600155 6000600155 00").new_state_bytes, 0);
+	// Slot 0 cleared then set again.
+	assert_eq!(used("6000600055 6002600055 00").new_state_bytes, 0);
+
+	// Slot 1 set from zero, then cleared by a committed sub-call to
+	// `CONTRACT` itself, with a byte of call data.
+	let code = format!(
+		"3615600b57 6000600155 00 5b 6001600155 6000600060016000600073{} 5a f1 00",
+		hex::encode(CONTRACT)
+	);
+	assert_eq!(used(&code).new_state_bytes, 0);
+}
+
+#[test]
+fn out_of_resource() {
+	for (limits, code) in [
+		(
+			ResourceUsage {
+				state_reads: 1,
+				..ResourceUsage::unlimited()
+			},
+			"600054 600054 00",
+		),
+		(
+			ResourceUsage {
+				new_state_bytes: 31,
+				..ResourceUsage::unlimited()
+			},
+			"6001600155 00",
+		),
+		(
+			ResourceUsage {
+				log_bytes: 31,
+				..ResourceUsage::unlimited()
+			},
+			"60206000a0 00",
+		),
+		(
+			ResourceUsage {
+				compute: 5,
+				..ResourceUsage::unlimited()
+			},
+			"6000 6000 00",
+		),
+	] {
+		let outcome = call(code, &[], limits);
+		assert_eq!(
+			outcome.reason,
+			ExitReason::Error(ExitError::OutOfResource),
+			"{}",
+			code
+		);
+		assert!(outcome.resources.unwrap().is_within(&limits));
+	}
+
+	let limits = ResourceUsage {
+		calldata_bytes: 2,
+		..ResourceUsage::unlimited()
+	};
+	assert_eq!(
+		call("00", &[1, 2, 3], limits).reason,
+		ExitReason::Error(ExitError::OutOfResource)
+	);
+}
+
+#[test]
+fn committed_and_reverted_sub_calls() {
+	let committed = used(&call_of(COMMITTING));
+	let reverted = used(&call_of(REVERTING));
+
+	assert_eq!(committed.state_reads, 1);
+	assert_eq!(committed.state_writes, 1);
+	assert_eq!(committed.new_state_bytes, 32);
+	assert_eq!(committed.log_bytes, 1);
+
+	// Like gas, the usage of a reverted sub-call is kept in full. It only
+	// differs by the two PUSH1 of the REVERT.
+	assert_eq!(
+		reverted,
+		ResourceUsage {
+			compute: committed.compute + 6,
+			..committed
+		}
+	);
+
+	// Nor is new state released by a reverted sub-call released from the
+	// caller, like a gas refund. Slot 1 is set from zero, then cleared by a
+	// reverted sub-call to `CONTRACT` itself, with a byte of call data.
+	let code = format!(
+		"3615600f57 6000600155 60006000fd 5b 6001600155 6000600060016000600073{} 5a f1 00",
+		hex::encode(CONTRACT)
+	);
+	assert_eq!(used(&code).new_state_bytes, 32);
+}
@@ -0,0 +1,78 @@
+//! Decoded revert reasons of transactions and of their call frames.
+
+mod common;
+
+use common::{contract, revert_with, vicinity, CALLER};
+use evm::backend::MemoryBackend;
+use evm::executor::stack::{
+	MemoryStackState, StackExecutor, StackSubstateMetadata, TransactionOutcome,
+};
+use evm::revert::RevertReason;
+use evm::Config;
+use primitive_types::{H160, U256};
+use std::collections::BTreeMap;
+
+const CONTRACT: H160 = H160([0x20; 20]);
+const CALLEE: H160 = H160([0x30; 20]);
+
+fn call(to: H160) -> TransactionOutcome {
+	// CALL the callee, then revert with the return data of the callee.
+	let code = format!(
+		"6000600060006000600073{} 5a f1 50 3d 6000 6000 3e 3d 6000 fd",
+		hex::encode(CALLEE)
+	)
+	.replace(' ', "");
+
+	let mut state = BTreeMap::new();
+	state.insert(CONTRACT, contract(&code));
+	state.insert(CALLEE, contract(&revert_with("not allowed")));
+	state.insert(H160::repeat_byte(0x40), contract("00"));
+
+	let vicinity = vicinity();
+	let backend = MemoryBackend::new(&vicinity, state);
+	let config = Config::london();
+	let metadata = StackSubstateMetadata::new(100_000, &config);
+	let state = MemoryStackState::new(metadata, &backend);
+	let mut executor = StackExecutor::new_with_precompiles(state, &config, &());
+
+	executor.transact_call(CALLER, to, U256::zero(), Vec::new(), 100_000, Vec::new())
+}
+
+#[test]
+fn transaction_revert_reason() {
+	let expected = RevertReason::Error(String::from("not allowed"));
+	assert_eq!(call(CALLEE).revert_reason(), Some(expected.clone()));
+	assert_eq!(call(CONTRACT).revert_reason(), Some(expected));
+
+	let outcome = call(H160::repeat_byte(0x40));
+	assert!(outcome.reason.is_succeed());
+	assert_eq!(outcome.revert_reason(), None);
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn exit_events_carry_revert_reason() {
+	use evm::tracing::{self, Event, EventListener};
+
+	#[derive(Default)]
+	struct Exits(Vec<Option<RevertReason>>);
+
+	impl EventListener for Exits {
+		fn event(&mut self, event: Event) {
+			if let Event::Exit { revert, .. } = event {
+				self.0.push(revert.clone());
+			}
+		}
+	}
+
+	let mut exits = Exits::default();
+	tracing::using(&mut exits, || call(CONTRACT));
+
+	// The callee, then the caller bubbling up its revert.
+	let expected = Some(RevertReason::Error(String::from("not allowed")));
+	assert_eq!(exits.0, vec![expected.clone(), expected]);
+
+	let mut exits = Exits::default();
+	tracing::using(&mut exits, || call(H160::repeat_byte(0x40)));
+	assert_eq!(exits.0, vec![None]);
+}
@@ -0,0 +1,99 @@
+//! Stack traces of failed transactions with nested calls.
+
+#![cfg(feature = "tracing")]
+
+use evm::backend::MemoryAccount;
+use evm::source_map::{ContractSourceMap, SourceMap, SourceMaps, StackTrace, StackTracer};
+use evm::testing::TestChain;
+use evm::{Config, ExitReason, ExitRevert};
+use primitive_types::{H160, U256};
+use std::collections::BTreeMap;
+
+const CALLER: H160 = H160([0x10; 20]);
+const CONTRACT: H160 = H160([0x20; 20]);
+const CALLEE: H160 = H160([0x30; 20]);
+
+// MSTORE 0xaa at 0, REVERT with the 32 bytes word.
+const CALLEE_CODE: &str = "60aa 6000 52 6020 6000 fd";
+const CALLEE_REVERT: usize = 9;
+
+const CALLER_CALL: usize = 32;
+
+/// CALL the callee, then `then`.
+fn caller_code(then: &str) -> String {
+	format!(
+		"6000600060006000600073{} 5a f1 50 {}",
+		hex::encode(CALLEE),
+		then
+	)
+}
+fn source_maps() -> SourceMaps {
+	let content = "contract Callee {\n\tfallback() external {\n\t\trevert();\n\t}\n}\n";
+	let revert = content.find("revert").unwrap();
+	let mut files = BTreeMap::new();
+	files.insert(0, (String::from("Callee.sol"), String::from(content)));
+
+	let code = hex::decode(CALLEE_CODE.replace(' ', "")).unwrap();
+	let source_map = SourceMap::parse(&format!("0:60:0;;;;;{}:9", revert)).unwrap();
+	let mut source_maps = SourceMaps::new();
+	source_maps.insert(
+		CALLEE,
+		ContractSourceMap::new(String::from("Callee"), &code, source_map, files),
+	);
+	source_maps
+}
+
+fn trace(code: &str) -> Option<StackTrace> {
+	let mut chain = TestChain::new(Config::london());
+	for (address, code) in [(CONTRACT, code), (CALLEE, CALLEE_CODE)] {
+		chain.state_mut().insert(
+			address,
+			MemoryAccount {
+				nonce: U256::one(),
+				code: hex::decode(code.replace(' ', "")).unwrap().into(),
+				..Default::default()
+			},
+		);
+	}
+
+	let tracer = StackTracer::new(source_maps());
+	tracer.trace(|| chain.send(CALLER, CONTRACT, U256::zero(), Vec::new()));
+	tracer.stack_trace()
+}
+
+#[test]
+fn bubbled_revert_keeps_callee_frames() {
+	// Revert with the return data of the callee.
+	let trace = trace(&caller_code("3d 6000 6000 3e 3d 6000 fd")).unwrap();
+	assert_eq!(trace.reason, ExitReason::Revert(ExitRevert::Reverted));
+	assert_eq!(trace.return_value[31], 0xaa);
+
+	let frames: Vec<_> = trace.frames.iter().map(|f| (f.address, f.pc)).collect();
+	assert_eq!(
+		frames,
+		vec![(CALLEE, CALLEE_REVERT), (CONTRACT, CALLER_CALL)]
+	);
+
+	let callee = &trace.frames[0];
+	assert_eq!(callee.contract.as_deref(), Some("Callee"));
+	let location = callee.location.as_ref().unwrap();
+	assert_eq!((location.line, location.column), (3, 3));
+	assert_eq!(location.function.as_deref(), Some("fallback"));
+	assert_eq!(callee.to_string(), "at Callee.fallback (Callee.sol:3:3)");
+	assert!(trace.frames[1].contract.is_none());
+}
+
+#[test]
+fn new_revert_replaces_callee_frames() {
+	// Revert with other data after the callee reverted.
+	let trace = trace(&caller_code("60bb 6000 52 6020 6000 fd")).unwrap();
+	assert_eq!(trace.return_value[31], 0xbb);
+
+	let frames: Vec<_> = trace.frames.iter().map(|f| (f.address, f.pc)).collect();
+	assert_eq!(frames, vec![(CONTRACT, CALLER_CALL + 11)]);
+}
+
+#[test]
+fn caught_revert_has_no_trace() {
+	assert_eq!(trace(&caller_code("00")), None);
+}
@@ -0,0 +1,212 @@
+//! State growth reported by `MemoryStackState::state_growth`.
+
+mod common;
+
+use common::{contract, vicinity, CALLER};
+use evm::backend::{MemoryAccount, MemoryBackend};
+use evm::executor::stack::{
+	MemoryStackState, StackExecutor, StackState, StackSubstateMetadata, StateGrowth,
+};
+use evm::{Config, Transfer};
+use primitive_types::{H160, H256, U256};
+use std::collections::BTreeMap;
+
+const CONTRACT: H160 = H160([0x20; 20]);
+const NEW: H160 = H160([0x30; 20]);
+
+fn slot(value: u64) -> H256 {
+	H256::from_low_u64_be(value)
+}
+
+/// State where `CALLER` holds a balance, and slots 0 and 1 of `CONTRACT`,
+/// running `code`, hold 1 and 2.
+fn accounts(code: &str) -> BTreeMap<H160, MemoryAccount> {
+	let mut state = BTreeMap::new();
+	state.insert(
+		CALLER,
+		MemoryAccount {
+			balance: U256::from(10),
+			..Default::default()
+		},
+	);
+	let mut account = contract(code);
+	account.storage.insert(slot(0), slot(1));
+	account.storage.insert(slot(1), slot(2));
+	state.insert(CONTRACT, account);
+	state
+}
+
+/// Apply `f` to the state of `accounts`, returning the state growth.
+fn growth<F: FnOnce(&mut MemoryStackState<MemoryBackend>)>(f: F) -> StateGrowth {
+	let vicinity = vicinity();
+	let backend = MemoryBackend::new(&vicinity, accounts("00"));
+	let config = Config::london();
+	let metadata = StackSubstateMetadata::new(100_000, &config);
+	let mut state = MemoryStackState::new(metadata, &backend);
+	f(&mut state);
+
+	state.state_growth()
+}
+
+#[test]
+fn new_account() {
+	let transfer = |value| {
+		move |state: &mut MemoryStackState<MemoryBackend>| {
+			state
+				.transfer(Transfer {
+					source: CALLER,
+					target: NEW,
+					value: U256::from(value),
+				})
+				.unwrap()
+		}
+	};
+	assert_eq!(
+		growth(transfer(1)),
+		StateGrowth {
+			new_accounts: 1,
+			..Default::default()
+		}
+	);
+	// Touched but empty.
+	assert_eq!(growth(transfer(0)), StateGrowth::default());
+}
+
+#[test]
+fn storage_transitions() {
+	// 0 -> x
+	assert_eq!(
+		growth(|state| state.set_storage(CONTRACT, slot(2), slot(1))),
+		StateGrowth {
+			new_storage_slots: 1,
+			..Default::default()
+		}
+	);
+	// x -> 0
+	assert_eq!(
+		growth(|state| state.set_storage(CONTRACT, slot(0), slot(0))),
+		StateGrowth {
+			cleared_storage_slots: Some(1),
+			..Default::default()
+		}
+	);
+	// x -> 0 -> x, in a committed substate.
+	assert_eq!(
+		growth(|state| {
+			state.set_storage(CONTRACT, slot(0), slot(0));
+			state.enter(0, false);
+			state.set_storage(CONTRACT, slot(0), slot(1));
+			state.exit_commit().unwrap();
+		}),
+		StateGrowth::default()
+	);
+	// 0 -> x in a reverted substate.
+	assert_eq!(
+		growth(|state| {
+			state.enter(0, false);
+			state.set_storage(CONTRACT, slot(2), slot(1));
+			state.exit_revert().unwrap();
+		}),
+		StateGrowth::default()
+	);
+}
+
+#[test]
+fn code_replacement() {
+	assert_eq!(
+		growth(|state| state.set_code(CONTRACT, vec![0x60, 0x00, 0x00])),
+		StateGrowth {
+			new_code_bytes: 3,
+			..Default::default()
+		}
+	);
+}
+
+#[test]
+fn reset_and_re_create() {
+	// Storage reset as by `CREATE`, then written along with new code.
+	let growth = growth(|state| {
+		state.enter(0, false);
+		state.reset_storage(CONTRACT);
+		state.inc_nonce(CONTRACT);
+		// Slot 1 is set again to the value it had before the reset.
+		state.set_storage(CONTRACT, slot(1), slot(2));
+		state.set_storage(CONTRACT, slot(2), slot(3));
+		state.set_code(CONTRACT, vec![0xaa]);
+		state.exit_commit().unwrap();
+	});
+	assert_eq!(
+		growth,
+		StateGrowth {
+			new_accounts: 0,
+			new_storage_slots: 2,
+			cleared_storage_slots: Some(2),
+			new_code_bytes: 1,
+		}
+	);
+}
+
+#[test]
+fn growth_of_each_transaction() {
+	// SSTORE the first word of call data at slot 2.
+	let vicinity = vicinity();
+	let backend = MemoryBackend::new(&vicinity, accounts("60003560025500"));
+	let config = Config::london();
+	let metadata = StackSubstateMetadata::new(1_000_000, &config);
+	let state = MemoryStackState::new(metadata, &backend);
+	let mut executor = StackExecutor::new_with_precompiles(state, &config, &());
+
+	let mut transact = |value: u64| {
+		let outcome = executor.transact_call(
+			CALLER,
+			CONTRACT,
+			U256::zero(),
+			slot(value).as_bytes().to_vec(),
+			100_000,
+			Vec::new(),
+		);
+		assert!(outcome.reason.is_succeed(), "{:?}", outcome.reason);
+		executor.state().state_growth()
+	};
+
+	assert_eq!(
+		transact(1),
+		StateGrowth {
+			new_storage_slots: 1,
+			..Default::default()
+		}
+	);
+	// Unchanged since the previous transaction.
+	assert_eq!(transact(1), StateGrowth::default());
+	// Cleared, though zero in the backend.
+	assert_eq!(
+		transact(0),
+		StateGrowth {
+			cleared_storage_slots: Some(1),
+			..Default::default()
+		}
+	);
+}
+
+#[test]
+fn reset_without_storage_count() {
+	// A `MemoryStackState` used as a backend cannot count storage slots.
+	let vicinity = vicinity();
+	let backend = MemoryBackend::new(&vicinity, accounts("00"));
+	let config = Config::london();
+	let inner = MemoryStackState::new(StackSubstateMetadata::new(100_000, &config), &backend);
+	let mut state = MemoryStackState::new(StackSubstateMetadata::new(100_000, &config), &inner);
+
+	state.enter(0, false);
+	state.reset_storage(CONTRACT);
+	state.set_storage(CONTRACT, slot(2), slot(3));
+	state.exit_commit().unwrap();
+	assert_eq!(
+		state.state_growth(),
+		StateGrowth {
+			new_storage_slots: 1,
+			cleared_storage_slots: None,
+			..Default::default()
+		}
+	);
+}
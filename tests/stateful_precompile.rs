@@ -0,0 +1,199 @@
+//! Gas and static checks of the metered state access given to precompiles
+//! through `StatefulPrecompileHandle`.
+
+mod common;
+
+use common::{contract, vicinity, CALLER};
+use evm::backend::{ApplyBackend, MemoryAccount, MemoryBackend};
+use evm::executor::stack::{
+	MemoryStackState, PrecompileHandle, PrecompileOutput, PrecompileResult, PrecompileSet,
+	StackExecutor, StackSubstateMetadata,
+};
+use evm::{Config, ExitError, ExitSucceed, Opcode};
+use primitive_types::{H160, H256, U256};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+const PRECOMPILE: H160 = H160([0x01; 20]);
+const TARGET: H160 = H160([0x30; 20]);
+const EMPTY: H160 = H160([0x40; 20]);
+// STATICCALL the precompile with the call data.
+const STATIC_CALLER: H160 = H160([0x50; 20]);
+
+const SLOAD: u8 = 0;
+const SSTORE: u8 = 1;
+const BALANCE: u8 = 2;
+const TRANSFER: u8 = 3;
+const TRANSFER_ZERO: u8 = 4;
+const CODE: u8 = 5;
+
+/// Precompile making one state access for each byte of its input, and
+/// recording the gas charged for each access and its error. Errors are
+/// swallowed, so that the state is kept.
+#[derive(Default)]
+struct Stateful {
+	accesses: RefCell<Vec<(u64, Result<(), ExitError>)>>,
+}
+
+impl PrecompileSet for Stateful {
+	fn execute(&self, handle: &mut impl PrecompileHandle) -> Option<PrecompileResult> {
+		if handle.code_address() != PRECOMPILE {
+			return None;
+		}
+
+		let input = handle.input().to_vec();
+		let handle = handle.as_stateful().unwrap();
+		for op in input {
+			let gas = handle.remaining_gas();
+			let result = match op {
+				SLOAD => handle.storage(H256::zero()).map(|_| ()),
+				SSTORE => handle.set_storage(H256::zero(), H256::from_low_u64_be(7)),
+				BALANCE => handle.balance(TARGET).map(|_| ()),
+				TRANSFER => handle.transfer(TARGET, U256::one()),
+				TRANSFER_ZERO => handle.transfer(EMPTY, U256::zero()),
+				CODE => handle.code(TARGET).map(|code| assert_eq!(code.len(), 33)),
+				_ => unreachable!(),
+			};
+			let charged = gas - handle.remaining_gas();
+			self.accesses.borrow_mut().push((charged, result));
+		}
+
+		Some(Ok(PrecompileOutput {
+			exit_status: ExitSucceed::Returned,
+			output: Vec::new(),
+		}))
+	}
+
+	fn is_precompile(&self, address: H160) -> bool {
+		address == PRECOMPILE
+	}
+}
+
+type Accesses = Vec<(u64, Result<(), ExitError>)>;
+
+/// Call `to` with `input` and the given gas left after the intrinsic gas,
+/// returning the accesses of the precompile, and the state once the
+/// transaction is applied.
+fn run(to: H160, input: &[u8], gas: u64) -> (Accesses, BTreeMap<H160, MemoryAccount>) {
+	let mut state = BTreeMap::new();
+	state.insert(
+		PRECOMPILE,
+		MemoryAccount {
+			balance: U256::from(10),
+			..Default::default()
+		},
+	);
+	// 33 bytes of code, STOP then PUSH32.
+	state.insert(TARGET, contract(&format!("007f{}", "00".repeat(31))));
+	state.insert(EMPTY, MemoryAccount::default());
+	let code = format!(
+		"366000600037 6000600036600073{} 5a fa 00",
+		hex::encode(PRECOMPILE)
+	);
+	state.insert(STATIC_CALLER, contract(&code.replace(' ', "")));
+
+	let vicinity = vicinity();
+	let mut backend = MemoryBackend::new(&vicinity, state);
+	let config = Config::london();
+	let precompiles = Stateful::default();
+
+	let intrinsic = 21_000 + input.len() as u64 * 16;
+	let gas_limit = intrinsic + gas;
+	let metadata = StackSubstateMetadata::new(gas_limit, &config);
+	let state = MemoryStackState::new(metadata, &backend);
+	let mut executor = StackExecutor::new_with_precompiles(state, &config, &precompiles);
+	let outcome = executor.transact_call(
+		CALLER,
+		to,
+		U256::zero(),
+		input.to_vec(),
+		gas_limit,
+		Vec::new(),
+	);
+	assert!(outcome.reason.is_succeed(), "{:?}", outcome.reason);
+
+	let (values, logs) = executor.into_state().deconstruct();
+	backend.apply(values, logs, true);
+	(precompiles.accesses.into_inner(), backend.state().clone())
+}
+
+fn charged(accesses: Accesses) -> Vec<u64> {
+	accesses
+		.into_iter()
+		.map(|(gas, result)| {
+			assert_eq!(result, Ok(()));
+			gas
+		})
+		.collect()
+}
+
+#[test]
+fn gas_of_each_access() {
+	// Cold then warm.
+	let (accesses, _) = run(PRECOMPILE, &[SLOAD, SLOAD], 100_000);
+	assert_eq!(charged(accesses), vec![2_100, 100]);
+	let (accesses, state) = run(PRECOMPILE, &[SSTORE, SSTORE], 100_000);
+	assert_eq!(charged(accesses), vec![2_100 + 20_000, 100]);
+	assert_eq!(
+		state[&PRECOMPILE].storage[&H256::zero()],
+		H256::from_low_u64_be(7)
+	);
+	let (accesses, _) = run(PRECOMPILE, &[BALANCE, BALANCE], 100_000);
+	assert_eq!(charged(accesses), vec![2_600, 100]);
+	let (accesses, _) = run(PRECOMPILE, &[CODE, CODE], 100_000);
+	assert_eq!(charged(accesses), vec![2_600 + 2 * 3, 100 + 2 * 3]);
+
+	let (accesses, state) = run(PRECOMPILE, &[TRANSFER, TRANSFER], 100_000);
+	assert_eq!(charged(accesses), vec![2_600 + 9_000, 100 + 9_000]);
+	assert_eq!(state[&PRECOMPILE].balance, U256::from(8));
+	assert_eq!(state[&TARGET].balance, U256::from(2));
+}
+
+#[test]
+fn zero_value_transfer_touches_target() {
+	let (accesses, state) = run(PRECOMPILE, &[TRANSFER_ZERO], 100_000);
+	assert_eq!(charged(accesses), vec![2_600]);
+	// The touched empty account is deleted.
+	assert!(!state.contains_key(&EMPTY));
+	assert_eq!(state[&PRECOMPILE].balance, U256::from(10));
+}
+
+#[test]
+fn static_call_rejects_writes() {
+	let (accesses, state) = run(
+		STATIC_CALLER,
+		&[SLOAD, SSTORE, TRANSFER, TRANSFER_ZERO],
+		100_000,
+	);
+	assert_eq!(
+		accesses,
+		vec![
+			(2_100, Ok(())),
+			(0, Err(ExitError::InvalidCode(Opcode::SSTORE))),
+			(0, Err(ExitError::InvalidCode(Opcode::CALL))),
+			(0, Err(ExitError::InvalidCode(Opcode::CALL))),
+		]
+	);
+	assert!(state[&PRECOMPILE].storage.is_empty());
+	assert_eq!(state[&PRECOMPILE].balance, U256::from(10));
+	assert!(state.contains_key(&EMPTY));
+}
+
+#[test]
+fn out_of_gas_leaves_state_untouched() {
+	// Enough for a warm access, not for a cold one. Running out of gas
+	// burns all the gas left.
+	for op in [SSTORE, BALANCE, TRANSFER, TRANSFER_ZERO, CODE] {
+		let (accesses, state) = run(PRECOMPILE, &[op], 2_000);
+		assert_eq!(
+			accesses,
+			vec![(2_000, Err(ExitError::OutOfGas))],
+			"op {}",
+			op
+		);
+		assert!(state[&PRECOMPILE].storage.is_empty());
+		assert_eq!(state[&PRECOMPILE].balance, U256::from(10));
+		assert_eq!(state[&TARGET].balance, U256::zero());
+		assert!(state.contains_key(&EMPTY));
+	}
+}
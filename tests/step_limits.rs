@@ -0,0 +1,129 @@
+//! `Config::step_limit` bounds the opcodes of each transaction, and
+//! `Config::call_step_limit` those of each call frame.
+
+mod common;
+
+use common::{contract, vicinity, CALLER};
+use evm::backend::{MemoryBackend, MemoryVicinity};
+use evm::executor::stack::{MemoryStackState, StackExecutor, StackSubstateMetadata};
+use evm::{Config, ExitFatal, ExitReason};
+use primitive_types::{H160, U256};
+use std::collections::BTreeMap;
+
+const CONTRACT: H160 = H160([0x20; 20]);
+const CALLEE: H160 = H160([0x30; 20]);
+const SHORT: H160 = H160([0x40; 20]);
+
+// Count to 5 in a loop, then STOP.
+const LOOP: &str = "60005b6001018060051160025700";
+
+fn backend(vicinity: &MemoryVicinity) -> MemoryBackend {
+	// CALL the callee with all gas left, then STOP.
+	let code = format!("6000600060006000600073{}5af100", hex::encode(CALLEE));
+
+	let mut state = BTreeMap::new();
+	state.insert(CONTRACT, contract(&code));
+	state.insert(CALLEE, contract(LOOP));
+	// PUSH1 1, PUSH1 1, ADD, ending without a STOP.
+	state.insert(SHORT, contract("6001600101"));
+	MemoryBackend::new(vicinity, state)
+}
+
+/// Run the given calls on a single executor, returning the exit reason and
+/// steps of each.
+fn run(config: &Config, calls: &[H160]) -> Vec<(ExitReason, u64)> {
+	let vicinity = vicinity();
+	let backend = backend(&vicinity);
+	let metadata = StackSubstateMetadata::new(100_000, config);
+	let state = MemoryStackState::new(metadata, &backend);
+	let mut executor = StackExecutor::new_with_precompiles(state, config, &());
+
+	calls
+		.iter()
+		.map(|&to| {
+			let outcome =
+				executor.transact_call(CALLER, to, U256::zero(), Vec::new(), 100_000, Vec::new());
+			(outcome.reason, executor.steps())
+		})
+		.collect()
+}
+
+fn with_limits(step_limit: Option<u64>, call_step_limit: Option<u64>) -> Config {
+	Config {
+		step_limit,
+		call_step_limit,
+		..Config::london()
+	}
+}
+
+#[test]
+fn step_limit_is_per_transaction() {
+	let steps = run(&Config::london(), &[CALLEE])[0].1;
+	assert!(steps > 0);
+
+	// The limit applies to each transaction of a reused executor.
+	let config = with_limits(Some(steps), None);
+	for (reason, used) in run(&config, &[CALLEE, CALLEE, CALLEE]) {
+		assert!(reason.is_succeed(), "{:?}", reason);
+		assert_eq!(used, steps);
+	}
+
+	// Going over it is a fatal error, distinct from running out of gas.
+	let config = with_limits(Some(steps - 1), None);
+	let (reason, _) = run(&config, &[CALLEE])[0].clone();
+	assert_eq!(reason, ExitReason::Fatal(ExitFatal::StepLimitReached));
+}
+
+#[test]
+fn step_limit_counts_nested_calls() {
+	let total = run(&Config::london(), &[CONTRACT])[0].1;
+	let callee = run(&Config::london(), &[CALLEE])[0].1;
+	assert!(total > callee);
+
+	let config = with_limits(Some(total), None);
+	assert!(run(&config, &[CONTRACT])[0].0.is_succeed());
+
+	// Steps of the callee count against the limit of the transaction.
+	let config = with_limits(Some(total - 1), None);
+	assert_eq!(
+		run(&config, &[CONTRACT])[0].0,
+		ExitReason::Fatal(ExitFatal::StepLimitReached)
+	);
+}
+
+#[test]
+fn call_step_limit_is_per_frame() {
+	let total = run(&Config::london(), &[CONTRACT])[0].1;
+	let callee = run(&Config::london(), &[CALLEE])[0].1;
+	let caller = total - callee;
+	assert!(caller < callee);
+
+	// Each frame is within the limit, though the transaction is not.
+	let config = with_limits(None, Some(callee));
+	let (reason, steps) = run(&config, &[CONTRACT])[0].clone();
+	assert!(reason.is_succeed(), "{:?}", reason);
+	assert_eq!(steps, total);
+
+	// The callee going over the limit aborts the whole transaction, rather
+	// than failing the call.
+	let config = with_limits(None, Some(callee - 1));
+	assert_eq!(
+		run(&config, &[CONTRACT])[0].0,
+		ExitReason::Fatal(ExitFatal::StepLimitReached)
+	);
+}
+
+#[test]
+fn implicit_stop_is_not_a_step() {
+	assert_eq!(run(&Config::london(), &[SHORT])[0].1, 3);
+
+	for config in [with_limits(Some(3), None), with_limits(None, Some(3))] {
+		let (reason, steps) = run(&config, &[SHORT])[0].clone();
+		assert!(reason.is_succeed(), "{:?}", reason);
+		assert_eq!(steps, 3);
+	}
+	assert_eq!(
+		run(&with_limits(None, Some(2)), &[SHORT])[0].0,
+		ExitReason::Fatal(ExitFatal::StepLimitReached)
+	);
+}
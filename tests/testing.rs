@@ -0,0 +1,171 @@
+//! Deploying and calling contracts on a `TestChain`.
+
+mod common;
+
+use common::{contract, revert_with};
+use evm::abi::AbiValue;
+use evm::revert::RevertReason;
+use evm::testing::TestChain;
+use evm::Config;
+use primitive_types::{H160, H256, U256};
+
+const ALICE: H160 = H160([0x10; 20]);
+const REVERTER: H160 = H160([0x30; 20]);
+
+/// Runtime code storing its call data in slot 0, or returning slot 0 when
+/// called with a zero word.
+///
+/// 00 PUSH1 0, CALLDATALOAD, DUP1, ISZERO, PUSH1 0x0c, JUMPI
+/// 08 PUSH1 0, SSTORE, STOP
+/// 0c JUMPDEST, POP, PUSH1 0, SLOAD, PUSH1 0, MSTORE, PUSH1 32, PUSH1 0, RETURN
+const RUNTIME: &str = "6000 35 80 15 600c 57 6000 55 00 5b 50 6000 54 6000 52 6020 6000 f3";
+
+/// Init code storing its last 32 bytes, the ABI encoded constructor
+/// argument, in slot 0 and deploying `RUNTIME`.
+fn init_code() -> Vec<u8> {
+	let runtime = RUNTIME.replace(' ', "");
+	let init = format!(
+		// CODECOPY the argument, SSTORE it, then CODECOPY and RETURN the
+		// runtime following the 28 bytes of init code.
+		"6020 38 6020 90 03 6000 39 6000 51 6000 55 60{0:02x} 601c 6000 39 60{0:02x} 6000 f3 {1}",
+		runtime.len() / 2,
+		runtime
+	);
+	hex::decode(init.replace(' ', "")).unwrap()
+}
+
+fn word(value: u64) -> Vec<u8> {
+	H256::from_low_u64_be(value).as_bytes().to_vec()
+}
+
+fn deploy(chain: &mut TestChain) -> H160 {
+	let result = chain.deploy(ALICE, &init_code(), &[AbiValue::Uint(U256::from(42), 256)]);
+	result.assert_success();
+	result.address.unwrap()
+}
+
+#[test]
+fn deploy_then_call() {
+	let mut chain = TestChain::new(Config::london());
+	let address = deploy(&mut chain);
+
+	assert_eq!(
+		chain.code(address),
+		&hex::decode(RUNTIME.replace(' ', "")).unwrap()[..]
+	);
+	assert_eq!(
+		chain.storage(address, H256::zero()),
+		H256::from_low_u64_be(42)
+	);
+	assert_eq!(chain.nonce(ALICE), U256::one());
+
+	let result = chain.call(ALICE, address, word(0));
+	result.assert_success();
+	assert_eq!(result.output, word(42));
+	assert_eq!(result.address, None);
+}
+
+#[test]
+fn call_does_not_commit() {
+	let mut chain = TestChain::new(Config::london());
+	let address = deploy(&mut chain);
+
+	chain.call(ALICE, address, word(7)).assert_success();
+	assert_eq!(
+		chain.storage(address, H256::zero()),
+		H256::from_low_u64_be(42)
+	);
+	assert_eq!(chain.nonce(ALICE), U256::one());
+
+	chain
+		.send(ALICE, address, U256::zero(), word(7))
+		.assert_success();
+	assert_eq!(
+		chain.storage(address, H256::zero()),
+		H256::from_low_u64_be(7)
+	);
+	assert_eq!(chain.nonce(ALICE), U256::from(2));
+	assert_eq!(chain.call(ALICE, address, word(0)).output, word(7));
+}
+
+#[test]
+fn snapshots() {
+	let mut chain = TestChain::new(Config::london());
+	let address = deploy(&mut chain);
+	let value = |chain: &TestChain| chain.storage(address, H256::zero()).to_low_u64_be();
+
+	let first = chain.snapshot();
+	chain.send(ALICE, address, U256::zero(), word(1));
+	chain.advance_block(1, 12);
+	let second = chain.snapshot();
+	chain.send(ALICE, address, U256::zero(), word(2));
+	chain.advance_block(1, 12);
+	let third = chain.snapshot();
+	chain.send(ALICE, address, U256::zero(), word(3));
+	assert_eq!((first, second, third), (0, 1, 2));
+	assert_eq!(value(&chain), 3);
+
+	// Reverting to a snapshot discards it and all later ones.
+	assert!(chain.revert_to(second));
+	assert_eq!(value(&chain), 1);
+	assert_eq!(chain.vicinity().block_number, U256::one());
+	assert_eq!(chain.vicinity().block_timestamp, U256::from(12));
+	assert!(!chain.revert_to(third));
+	assert!(!chain.revert_to(second));
+
+	// Earlier snapshots are kept, and ids are reused.
+	assert_eq!(chain.snapshot(), 1);
+	chain.send(ALICE, address, U256::zero(), word(4));
+	assert!(chain.revert_to(first));
+	assert_eq!(value(&chain), 42);
+	assert_eq!(chain.vicinity().block_number, U256::zero());
+	assert_eq!(chain.nonce(ALICE), U256::one());
+	assert!(!chain.revert_to(first));
+}
+
+fn reverting_chain() -> TestChain {
+	let mut chain = TestChain::new(Config::london());
+	chain
+		.state_mut()
+		.insert(REVERTER, contract(&revert_with("not allowed")));
+	chain
+}
+
+#[test]
+fn assert_revert_with() {
+	let mut chain = reverting_chain();
+	let result = chain.call(ALICE, REVERTER, Vec::new());
+	result
+		.assert_revert()
+		.assert_revert_with(&RevertReason::Error(String::from("not allowed")));
+	assert!(!result.is_success());
+}
+
+#[test]
+#[should_panic(expected = "expected reverted with reason string 'other', \
+	transaction reverted with reason string 'not allowed'")]
+fn assert_revert_with_other_reason() {
+	let mut chain = reverting_chain();
+	chain
+		.call(ALICE, REVERTER, Vec::new())
+		.assert_revert_with(&RevertReason::Error(String::from("other")));
+}
+
+#[test]
+#[should_panic(expected = "expected reverted without a reason, transaction exited with Succeed")]
+fn assert_revert_with_success() {
+	let mut chain = TestChain::new(Config::london());
+	let address = deploy(&mut chain);
+	chain
+		.call(ALICE, address, word(0))
+		.assert_revert_with(&RevertReason::Empty);
+}
+
+#[test]
+#[should_panic(
+	expected = "expected success, transaction reverted with reason string 'not allowed'"
+)]
+fn assert_success_on_revert() {
+	let mut chain = reverting_chain();
+	chain.call(ALICE, REVERTER, Vec::new()).assert_success();
+}
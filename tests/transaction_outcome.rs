@@ -0,0 +1,183 @@
+//! Fields of the `TransactionOutcome` returned by `StackExecutor`.
+
+mod common;
+
+use common::{contract, vicinity, CALLER};
+use evm::backend::{MemoryAccount, MemoryBackend, MemoryVicinity};
+use evm::executor::stack::{
+	MemoryStackState, StackExecutor, StackState, StackSubstateMetadata, TransactionOutcome,
+};
+use evm::{Config, CreateScheme, ExitError, ExitReason, ExitRevert, ExitSucceed};
+use primitive_types::{H160, H256, U256};
+use std::collections::BTreeMap;
+
+const CONTRACT: H160 = H160([0x20; 20]);
+
+fn backend<'a>(vicinity: &'a MemoryVicinity, code: &str) -> MemoryBackend<'a> {
+	let mut state = BTreeMap::new();
+	let mut account = contract(code);
+	account
+		.storage
+		.insert(H256::zero(), H256::from_low_u64_be(1));
+	state.insert(CONTRACT, account);
+	state.insert(
+		CALLER,
+		MemoryAccount {
+			balance: U256::from(1_000_000),
+			..Default::default()
+		},
+	);
+	MemoryBackend::new(vicinity, state)
+}
+
+/// Create a contract with `init_code`, returning the outcome and the address
+/// the contract would have.
+fn create(init_code: &str, gas_limit: u64) -> (TransactionOutcome, H160) {
+	let vicinity = vicinity();
+	let backend = backend(&vicinity, "00");
+	let config = Config::london();
+	let metadata = StackSubstateMetadata::new(gas_limit, &config);
+	let state = MemoryStackState::new(metadata, &backend);
+	let mut executor = StackExecutor::new_with_precompiles(state, &config, &());
+
+	let address = executor.create_address(CreateScheme::Legacy { caller: CALLER });
+	let outcome = executor.transact_create(
+		CALLER,
+		U256::zero(),
+		hex::decode(init_code).unwrap(),
+		gas_limit,
+		Vec::new(),
+	);
+	(outcome, address)
+}
+
+/// Call `CONTRACT` running `code` `times` times with the same executor.
+fn call(code: &str, times: usize) -> Vec<TransactionOutcome> {
+	let vicinity = vicinity();
+	let backend = backend(&vicinity, code);
+	let config = Config::london();
+	let metadata = StackSubstateMetadata::new(100_000, &config);
+	let state = MemoryStackState::new(metadata, &backend);
+	let mut executor = StackExecutor::new_with_precompiles(state, &config, &());
+
+	(0..times)
+		.map(|_| {
+			executor.transact_call(
+				CALLER,
+				CONTRACT,
+				U256::zero(),
+				Vec::new(),
+				100_000,
+				Vec::new(),
+			)
+		})
+		.collect()
+}
+
+#[test]
+fn address_of_successful_create() {
+	// Deploy the single byte of code 0xaa.
+	let (outcome, address) = create("60aa6000536001 6000f3".replace(' ', "").as_str(), 100_000);
+	assert_eq!(outcome.reason, ExitReason::Succeed(ExitSucceed::Returned));
+	assert_eq!(outcome.address, Some(address));
+	assert_eq!(outcome.output, Vec::<u8>::new());
+}
+
+#[test]
+fn no_address_of_failed_create() {
+	let (outcome, _) = create("60006000fd", 100_000);
+	assert_eq!(outcome.reason, ExitReason::Revert(ExitRevert::Reverted));
+	assert_eq!(outcome.address, None);
+
+	// Loop until out of gas.
+	let (outcome, _) = create("5b600056", 100_000);
+	assert_eq!(outcome.reason, ExitReason::Error(ExitError::OutOfGas));
+	assert_eq!(outcome.address, None);
+	assert_eq!(outcome.used_gas, 100_000);
+}
+
+#[test]
+fn refunded_gas_of_cleared_slot() {
+	// SSTORE 0 at slot 0, which holds 1.
+	let outcomes = call("6000600055 00".replace(' ', "").as_str(), 2);
+	for outcome in &outcomes {
+		assert!(outcome.reason.is_succeed());
+		assert_eq!(
+			outcome.used_gas_before_refund - outcome.used_gas,
+			outcome.refunded_gas
+		);
+	}
+
+	// Cold access and reset of the slot, refunding its clearing.
+	assert_eq!(
+		outcomes[0].used_gas_before_refund,
+		21_000 + 3 + 3 + 2_100 + 2_900
+	);
+	assert_eq!(outcomes[0].refunded_gas, 4_800);
+
+	// The slot is already cleared by the previous transaction.
+	assert_eq!(
+		outcomes[1].used_gas_before_refund,
+		21_000 + 3 + 3 + 2_100 + 100
+	);
+	assert_eq!(outcomes[1].refunded_gas, 0);
+}
+
+#[test]
+fn logs_of_each_transaction() {
+	// LOG0 of the 32 bytes word 0xaa.
+	let outcomes = call("60aa 6000 52 6020 6000 a0 00".replace(' ', "").as_str(), 2);
+
+	for outcome in &outcomes {
+		assert!(outcome.reason.is_succeed());
+		assert_eq!(outcome.logs.len(), 1);
+		assert_eq!(outcome.logs[0].address, CONTRACT);
+		assert_eq!(outcome.logs[0].data, H256::from_low_u64_be(0xaa).as_bytes());
+	}
+	assert_eq!(outcomes[0].used_gas, outcomes[1].used_gas);
+}
+
+/// Used gas of a call to `CONTRACT` with the given access list.
+fn used_gas<'config, S: StackState<'config>>(
+	executor: &mut StackExecutor<'config, '_, S, ()>,
+	access_list: Vec<(H160, Vec<H256>)>,
+) -> u64 {
+	executor
+		.transact_call(
+			CALLER,
+			CONTRACT,
+			U256::zero(),
+			Vec::new(),
+			100_000,
+			access_list,
+		)
+		.used_gas
+}
+
+#[test]
+fn accesses_are_cold_at_each_transaction() {
+	let vicinity = vicinity();
+	// SLOAD slot 0, then POP.
+	let backend = backend(&vicinity, "60005450");
+	let config = Config::london();
+	let metadata = StackSubstateMetadata::new(100_000, &config);
+	let state = MemoryStackState::new(metadata, &backend);
+	let mut executor = StackExecutor::new_with_precompiles(state, &config, &());
+
+	// The slot is cold in both transactions.
+	let cold = 21_000 + 3 + 2_100 + 2;
+	assert_eq!(used_gas(&mut executor, Vec::new()), cold);
+	assert_eq!(used_gas(&mut executor, Vec::new()), cold);
+
+	// Slots warmed before a transaction are cold again once it starts, and
+	// are warmed through its access list instead.
+	executor
+		.state_mut()
+		.metadata_mut()
+		.access_storage(CONTRACT, H256::zero());
+	assert_eq!(used_gas(&mut executor, Vec::new()), cold);
+	assert_eq!(
+		used_gas(&mut executor, vec![(CONTRACT, vec![H256::zero()])]),
+		21_000 + 2_400 + 1_900 + 3 + 100 + 2
+	);
+}